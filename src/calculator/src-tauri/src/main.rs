@@ -4,9 +4,9 @@ use math_lib;
 /// Calculates a mathematical expression provided as a string.
 ///
 /// This function serves as a Tauri command that allows the evaluation of a mathematical expression
-/// provided as a string. The expression may include numbers, operators, and constants like π (pi).
-/// It first replaces the π character with its numerical value before parsing and evaluating
-/// the expression using the `math_lib::parse` function.
+/// provided as a string. The expression may include numbers, operators, and constants like π (pi),
+/// which are understood by `math_lib::parse` itself. It is evaluated with decimals, so results
+/// like `0.1+0.2` are displayed without binary rounding errors.
 ///
 /// # Arguments
 /// * `equation` - A string slice that holds the mathematical expression to be evaluated.
@@ -18,20 +18,59 @@ use math_lib;
 ///
 /// # Example
 /// ```
-/// let result = calculate("3.14159 * 2".to_string());
-/// assert_eq!(result.unwrap(), "6.28318");
+/// let result = calculate("2π".to_string());
+/// assert_eq!(result.unwrap(), "6.283185307179586");
 /// ```
 #[tauri::command]
 fn calculate(equation: String) -> Result<String, String> {
-    let equation = equation
-        .replace("π", "3.14159265358979323846264338327950288");
-    Ok(math_lib::parse(equation.as_str())?.to_string())
+    let options = math_lib::DecimalOptions::default();
+    let mut context = math_lib::Context::new();
+    context.set_named_constants(true);
+    let result = math_lib::evaluate_decimal(equation.as_str(), &context, &options)
+        .map_err(|error| error.to_string())?;
+    Ok(result.to_string())
+}
+
+/// A constant of the `math_lib` catalogue, as it is sent to the frontend.
+#[derive(serde::Serialize)]
+struct ConstantEntry {
+    name: &'static str,
+    value: f64,
+    unit: &'static str,
+    description: &'static str,
+}
+
+/// Lists the named constants that can be written in an expression.
+///
+/// This function serves as a Tauri command so the frontend can show the catalogue of
+/// `math_lib::NamedConstant` with the value, unit and description of every constant.
+///
+/// # Returns
+/// The constants in the order of the catalogue, the mathematical constants first.
+///
+/// # Example
+/// ```
+/// let constants = constants();
+/// assert!(constants.iter().any(|constant| constant.name == "c" && constant.unit == "m/s"));
+/// ```
+#[tauri::command]
+fn constants() -> Vec<ConstantEntry> {
+    math_lib::NamedConstant::ALL
+        .iter()
+        .map(|constant| ConstantEntry {
+            name: constant.name,
+            value: constant.value,
+            unit: constant.unit,
+            description: constant.description,
+        })
+        .collect()
 }
 
 /// The main entry point for the Tauri application.
 ///
 /// This function sets up the Tauri application with necessary configurations and command handlers.
-/// It defines the command handler for the `calculate` function, enabling it to be called from the frontend.
+/// It defines the command handlers for the `calculate` and `constants` functions, enabling them to be called
+/// from the frontend.
 /// The application is configured and run within this function, and an error is thrown if the application
 /// fails to run correctly.
 ///
@@ -43,14 +82,14 @@ fn calculate(equation: String) -> Result<String, String> {
 /// ```no_run
 /// fn main() {
 ///     tauri::Builder::default()
-///         .invoke_handler(tauri::generate_handler![calculate])
+///         .invoke_handler(tauri::generate_handler![calculate, constants])
 ///         .run(tauri::generate_context!())
 ///         .expect("error while running tauri application");
 /// }
 /// ```
 fn main() {
     tauri::Builder::default()
-        .invoke_handler(tauri::generate_handler![calculate])
+        .invoke_handler(tauri::generate_handler![calculate, constants])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
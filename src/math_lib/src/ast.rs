@@ -0,0 +1,543 @@
+//! # Description
+//! This module provides the expression tree produced by the parser and its evaluation.
+
+use std::fmt;
+
+use crate::constants::Constant;
+use crate::context::Context;
+use crate::error::{ParseError, Span};
+use crate::functions::{self, AngleConversion, Function, Implementation, NativeFunction};
+use crate::integer::{integer_of, is_bitwise, IntegerMode};
+use crate::parser::Operator;
+use crate::special;
+
+/// A parsed mathematical expression.
+///
+/// The tree is produced by [`parse_to_ast`](crate::parse_to_ast) and can be evaluated any
+/// number of times, inspected, printed back as text or transformed without parsing the
+/// input again.
+///
+/// The `span` of a `Unary` or `Binary` node is the span of its operator symbol, so errors
+/// raised while evaluating it point at the operator.
+///
+/// # Examples
+/// ```
+/// use math_lib::{parse_to_ast, Expr, Operator};
+///
+/// let expr = parse_to_ast("2*(3+4)").unwrap();
+/// assert!(matches!(expr, Expr::Binary { op: Operator::Multiply, .. }));
+/// assert_eq!(expr.to_string(), "2*(3+4)");
+/// assert_eq!(expr.evaluate(), Ok(14.));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// A numeric literal.
+    ///
+    /// For a number read from the input, `literal` holds its exact value written as a
+    /// decimal like `1.5e3`, which evaluation modes more precise than `f64` read instead of
+    /// `value`.
+    Number { value: f64, literal: Option<String>, span: Span },
+    /// A variable whose value is looked up in the [`Context`].
+    Variable { name: String, span: Span },
+    /// A mathematical constant such as `π`.
+    Constant { constant: Constant, span: Span },
+    /// An operator applied to a single operand: `-x`, `+x`, `√x` or `x!`.
+    Unary { op: Operator, operand: Box<Expr>, span: Span },
+    /// An operator applied to two operands, for example `x+y` or `n√x`.
+    ///
+    /// `add_on` marks a `+` or `-` whose right operand is a percentage of the left one, see
+    /// [`PercentMode::AddOn`](crate::PercentMode::AddOn), so `left+left*right` is computed.
+    /// It is printed like the plain operator, so the text only means the same with that mode.
+    Binary { op: Operator, left: Box<Expr>, right: Box<Expr>, add_on: bool, span: Span },
+    /// A call of a named function, for example `max(1, 2)`.
+    Call { name: String, args: Vec<Expr>, span: Span },
+}
+
+impl Expr {
+    /// Returns the span of the node.
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Number { span, .. }
+            | Expr::Variable { span, .. }
+            | Expr::Constant { span, .. }
+            | Expr::Unary { span, .. }
+            | Expr::Binary { span, .. }
+            | Expr::Call { span, .. } => *span,
+        }
+    }
+
+    /// Evaluates the expression to a number.
+    ///
+    /// The expression is evaluated in an empty [`Context`], so it must not contain variables.
+    ///
+    /// # Returns
+    /// A Result containing the value of the expression or a ParseError if an arithmetic
+    /// error occurs (like division by zero) or the tree contains an invalid operator.
+    pub fn evaluate(&self) -> Result<f64, ParseError> {
+        self.evaluate_with(&Context::new())
+    }
+
+    /// Evaluates the expression to a number, looking variables up in `context`.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - The context providing the values of variables
+    ///
+    /// # Returns
+    /// A Result containing the value of the expression or a ParseError if a variable is
+    /// not defined in the context or an error occurs during evaluation.
+    pub fn evaluate_with(&self, context: &Context) -> Result<f64, ParseError> {
+        self.evaluate_in(&Scope { context, locals: &[], depth: 0 })
+    }
+
+    /// Evaluates the expression, looking variables up in the parameters of the current
+    /// function call first and in the context second.
+    fn evaluate_in(&self, scope: &Scope) -> Result<f64, ParseError> {
+        match self {
+            Expr::Number { value, .. } => Ok(*value),
+            Expr::Variable { name, span } => scope.variable(name).ok_or_else(|| ParseError::UnknownVariable {
+                name: name.clone(),
+                span: *span,
+            }),
+            Expr::Constant { constant, .. } => Ok(constant.value()),
+            Expr::Unary { op, operand, span } => apply_unary(*op, operand.evaluate_in(scope)?, *span),
+            Expr::Binary { .. } => evaluate_chain(
+                self,
+                |operand| operand.evaluate_in(scope),
+                |left, op, right, add_on, span| {
+                    let mut right = right.evaluate_in(scope)?;
+                    if add_on {
+                        right = apply_binary(Operator::Multiply, left, right, span)?;
+                    }
+                    apply_binary(op, left, right, span)
+                },
+            ),
+            Expr::Call { name, args, span } => {
+                let function = called_function(scope.context, name, args.len(), *span)?;
+                match function.implementation() {
+                    Implementation::Native(native) => {
+                        let values = args.iter().map(|arg| arg.evaluate_in(scope)).collect::<Result<Vec<_>, _>>()?;
+                        call_native(function, native.as_ref(), values, scope.context, *span)
+                    }
+                    Implementation::User(function) => {
+                        if scope.depth >= scope.context.max_call_depth() {
+                            return Err(ParseError::RecursionLimit { name: name.clone(), span: *span });
+                        }
+                        let mut locals = Vec::with_capacity(args.len());
+                        for (param, arg) in function.params.iter().zip(args) {
+                            locals.push((param.as_str(), arg.evaluate_in(scope)?));
+                        }
+                        // The body only sees its own parameters, not those of the caller
+                        function.body.evaluate_in(&Scope {
+                            context: scope.context,
+                            locals: &locals,
+                            depth: scope.depth + 1,
+                        })
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Evaluates `expr` together with the binary nodes below it on the left, like the additions
+/// of `1+2+3`, in a loop, so that long rows of operators do not overflow the stack.
+///
+/// `evaluate` evaluates the operand at the bottom of the row. `apply` is called for each
+/// binary node from the bottom up, with the value of its left operand, its operator, its
+/// right operand, whether it adds an add-on percentage and its span.
+pub(crate) fn evaluate_chain<T>(
+    expr: &Expr,
+    evaluate: impl FnOnce(&Expr) -> Result<T, ParseError>,
+    mut apply: impl FnMut(T, Operator, &Expr, bool, Span) -> Result<T, ParseError>,
+) -> Result<T, ParseError> {
+    let mut nodes = Vec::new();
+    let mut bottom = expr;
+    while let Expr::Binary { op, left, right, add_on, span } = bottom {
+        nodes.push((*op, right.as_ref(), *add_on, *span));
+        bottom = left;
+    }
+    let mut value = evaluate(bottom)?;
+    for (op, right, add_on, span) in nodes.into_iter().rev() {
+        value = apply(value, op, right, add_on, span)?;
+    }
+    Ok(value)
+}
+
+/// Looks up the function a call refers to and checks that it accepts `count` arguments.
+pub(crate) fn called_function<'c>(
+    context: &'c Context,
+    name: &str,
+    count: usize,
+    span: Span,
+) -> Result<&'c Function, ParseError> {
+    let function = context
+        .function(name)
+        .ok_or_else(|| ParseError::UnknownFunction { name: name.to_string(), span })?;
+    if !function.arity().accepts(count) {
+        return Err(ParseError::ArityMismatch {
+            name: name.to_string(),
+            expected: function.arity(),
+            found: count,
+            span,
+        });
+    }
+    Ok(function)
+}
+
+/// Calls a native function with already evaluated arguments, converting angles between
+/// the angle mode of `context` and radians as the function requires.
+pub(crate) fn call_native(
+    function: &Function,
+    native: &NativeFunction,
+    mut values: Vec<f64>,
+    context: &Context,
+    span: Span,
+) -> Result<f64, ParseError> {
+    let angle_mode = context.angle_mode();
+    if function.angle_conversion() == AngleConversion::Arguments {
+        values.iter_mut().for_each(|value| *value = angle_mode.to_radians(*value));
+    }
+    let result = native(&values).map_err(|message| ParseError::DomainError { message, span })?;
+    if function.angle_conversion() == AngleConversion::Result {
+        return Ok(angle_mode.from_radians(result));
+    }
+    Ok(result)
+}
+
+/// Variables visible while evaluating an expression.
+struct Scope<'a> {
+    context: &'a Context,
+    // Parameters of the function being called, empty outside of calls
+    locals: &'a [(&'a str, f64)],
+    // Number of function calls being evaluated
+    depth: usize,
+}
+
+impl Scope<'_> {
+    fn variable(&self, name: &str) -> Option<f64> {
+        self.locals
+            .iter()
+            .find(|(local, _)| *local == name)
+            .map(|(_, value)| *value)
+            .or_else(|| self.context.value(name))
+    }
+}
+
+/// A statement of a script parsed by [`parse_script`](crate::parse_script).
+///
+/// # Examples
+/// ```
+/// use math_lib::{parse_script, Context, Statement};
+///
+/// let statements = parse_script("a = 3; a^2").unwrap();
+/// assert!(matches!(&statements[0], Statement::Assign { name, .. } if name == "a"));
+///
+/// let mut context = Context::new();
+/// assert_eq!(statements[0].execute(&mut context), Ok(Some(3.)));
+/// assert_eq!(statements[1].execute(&mut context), Ok(Some(9.)));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    /// Binds the value of an expression to a variable: `name = value`.
+    /// The span is the span of the variable name.
+    Assign { name: String, value: Expr, span: Span },
+    /// Defines a function: `name(params) = body`.
+    /// The span is the span of the function name.
+    Define { name: String, params: Vec<String>, body: Expr, span: Span },
+    /// An expression evaluated for its value.
+    Expression(Expr),
+}
+
+impl Statement {
+    /// Executes the statement and returns its value.
+    ///
+    /// An assignment stores the value in `context` and returns it. A function definition
+    /// stores the function in `context` and has no value.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - The context providing and receiving variables and functions
+    ///
+    /// # Returns
+    /// A Result containing the value of the statement, if it has one, or a ParseError if
+    /// its expression cannot be evaluated.
+    pub fn execute(&self, context: &mut Context) -> Result<Option<f64>, ParseError> {
+        match self {
+            Statement::Assign { name, value, .. } => {
+                let value = value.evaluate_with(context)?;
+                context.set_variable(name, value);
+                Ok(Some(value))
+            }
+            Statement::Define { name, params, body, .. } => {
+                context.define_function(name, params.clone(), body.clone());
+                Ok(None)
+            }
+            Statement::Expression(expr) => expr.evaluate_with(context).map(Some),
+        }
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Statement::Assign { name, value, .. } => write!(f, "{name} = {value}"),
+            Statement::Define { name, params, body, .. } => write!(f, "{name}({}) = {body}", params.join(", ")),
+            Statement::Expression(expr) => write!(f, "{expr}"),
+        }
+    }
+}
+
+/// Applies a unary operator to an already evaluated operand.
+pub(crate) fn apply_unary(op: Operator, operand: f64, span: Span) -> Result<f64, ParseError> {
+    match op {
+        Operator::Plus => Ok(operand),
+        Operator::Minus => Ok(-operand),
+        Operator::Percent => Ok(operand / 100.),
+        Operator::Root => functions::root(2., operand).map_err(|message| ParseError::DomainError { message, span }),
+        Operator::Factorial => special::factorial(operand).map_err(|message| ParseError::DomainError { message, span }),
+        Operator::BitNot => {
+            let mode = IntegerMode::default();
+            Ok(mode.apply_unary(op, integer_of(operand, span)?, span)? as f64)
+        }
+        _ => Err(ParseError::InvalidOperator { op, span }),
+    }
+}
+
+/// Applies a binary operator to already evaluated operands.
+pub(crate) fn apply_binary(op: Operator, left: f64, right: f64, span: Span) -> Result<f64, ParseError> {
+    match op {
+        Operator::Plus => Ok(left + right),
+        Operator::Minus => Ok(left - right),
+        Operator::Multiply => Ok(left * right),
+        Operator::Divide => {
+            if right.abs() < f64::EPSILON {
+                return Err(ParseError::DivisionByZero { span });
+            }
+            Ok(left / right)
+        }
+        Operator::Percent => {
+            if right.abs() < f64::EPSILON {
+                return Err(ParseError::DomainError {
+                    message: "Cannot take the percentage of zero".to_string(),
+                    span,
+                });
+            }
+            Ok(left / right * 100.0)
+        }
+        Operator::Modulo => {
+            if right == 0. {
+                return Err(ParseError::DivisionByZero { span });
+            }
+            // The remainder takes the sign of the divisor
+            let remainder = left % right;
+            if remainder != 0. && (remainder < 0.) != (right < 0.) {
+                Ok(remainder + right)
+            } else {
+                Ok(remainder)
+            }
+        }
+        Operator::Power => Ok(left.powf(right)),
+        Operator::Root => functions::root(left, right).map_err(|message| ParseError::DomainError { message, span }),
+        // Bitwise operators work on 64-bit signed integers
+        op if is_bitwise(op) => {
+            let mode = IntegerMode::default();
+            Ok(mode.apply_binary(op, integer_of(left, span)?, integer_of(right, span)?, span)? as f64)
+        }
+        _ => Err(ParseError::InvalidOperator { op, span }),
+    }
+}
+
+/// Returns the symbol the operator is written with.
+pub(crate) fn symbol(op: Operator) -> &'static str {
+    match op {
+        Operator::Plus => "+",
+        Operator::Minus => "-",
+        Operator::Multiply => "*",
+        Operator::Divide => "/",
+        Operator::Percent => "%",
+        Operator::Modulo => " mod ",
+        Operator::Power => "^",
+        Operator::Root => "√",
+        Operator::Factorial => "!",
+        Operator::BitAnd => "&",
+        Operator::BitOr => "|",
+        Operator::BitXor => " xor ",
+        Operator::BitNot => "~",
+        Operator::ShiftLeft => "<<",
+        Operator::ShiftRight => ">>",
+        #[cfg(feature = "units")]
+        Operator::Convert => " to ",
+        Operator::OpenParen => "(",
+        Operator::CloseParen => ")",
+        Operator::EndOfInput => "$",
+    }
+}
+
+/// Writes an operand, wrapping it in parentheses unless it is a single number, variable,
+/// constant or call.
+fn write_operand(f: &mut fmt::Formatter<'_>, expr: &Expr) -> fmt::Result {
+    match expr {
+        Expr::Number { value, .. } if *value >= 0. => write!(f, "{expr}"),
+        Expr::Variable { .. } | Expr::Constant { .. } | Expr::Call { .. } => write!(f, "{expr}"),
+        _ => write!(f, "({expr})"),
+    }
+}
+
+/// Prints the expression in the syntax accepted by the parser.
+///
+/// Nested operations are always wrapped in parentheses, so the printed text does not
+/// depend on the precedence rules.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Number { value, .. } => write!(f, "{value}"),
+            Expr::Variable { name, .. } => write!(f, "{name}"),
+            Expr::Constant { constant, .. } => write!(f, "{}", constant.symbol()),
+            Expr::Unary { op: op @ (Operator::Factorial | Operator::Percent), operand, .. } => {
+                write_operand(f, operand)?;
+                write!(f, "{}", symbol(*op))
+            }
+            Expr::Unary { op, operand, .. } => {
+                write!(f, "{}", symbol(*op))?;
+                write_operand(f, operand)
+            }
+            Expr::Binary { op, left, right, .. } => {
+                write_operand(f, left)?;
+                write!(f, "{}", symbol(*op))?;
+                write_operand(f, right)
+            }
+            Expr::Call { name, args, .. } => {
+                write!(f, "{name}(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod display_tests {
+    use super::{Expr, Operator, Span};
+
+    fn number(value: f64) -> Box<Expr> {
+        Box::new(Expr::Number { value, literal: None, span: Span::new(0, 0) })
+    }
+
+    #[test]
+    fn nested_binary() {
+        let expr = Expr::Binary {
+            op: Operator::Multiply,
+            left: Box::new(Expr::Binary {
+                op: Operator::Minus,
+                left: number(3.),
+                right: number(1.),
+                add_on: false,
+                span: Span::new(0, 0),
+            }),
+            right: number(2.5),
+            add_on: false,
+            span: Span::new(0, 0),
+        };
+        assert_eq!("(3-1)*2.5", expr.to_string());
+    }
+
+    #[test]
+    fn unary_operators() {
+        let expr = Expr::Unary {
+            op: Operator::Minus,
+            operand: Box::new(Expr::Unary { op: Operator::Factorial, operand: number(3.), span: Span::new(0, 0) }),
+            span: Span::new(0, 0),
+        };
+        assert_eq!("-(3!)", expr.to_string());
+    }
+
+    #[test]
+    fn call() {
+        let expr = Expr::Call { name: "max".to_string(), args: vec![*number(1.), *number(2.)], span: Span::new(0, 0) };
+        assert_eq!("max(1, 2)", expr.to_string());
+    }
+}
+
+#[cfg(test)]
+mod evaluate_tests {
+    use super::{Context, Expr, Operator, ParseError, Span};
+
+    fn number(value: f64) -> Box<Expr> {
+        Box::new(Expr::Number { value, literal: None, span: Span::new(0, 1) })
+    }
+
+    #[test]
+    fn binary() {
+        let expr = Expr::Binary {
+            op: Operator::Power,
+            left: number(2.),
+            right: number(10.),
+            add_on: false,
+            span: Span::new(1, 2),
+        };
+        assert_eq!(Ok(1024.), expr.evaluate());
+    }
+
+    #[test]
+    fn square_root() {
+        let expr = Expr::Unary { op: Operator::Root, operand: number(16.), span: Span::new(0, 1) };
+        assert_eq!(Ok(4.), expr.evaluate());
+    }
+
+    #[test]
+    fn variable() {
+        let mut context = Context::new();
+        context.set_variable("x", 3.);
+        let expr = Expr::Binary {
+            op: Operator::Multiply,
+            left: number(2.),
+            right: Box::new(Expr::Variable { name: "x".to_string(), span: Span::new(2, 3) }),
+            add_on: false,
+            span: Span::new(1, 2),
+        };
+        assert_eq!(Ok(6.), expr.evaluate_with(&context));
+        assert_eq!(
+            Err(ParseError::UnknownVariable { name: "x".to_string(), span: Span::new(2, 3) }),
+            expr.evaluate()
+        );
+    }
+
+    #[test]
+    fn unknown_function() {
+        let expr = Expr::Call { name: "f".to_string(), args: vec![], span: Span::new(0, 3) };
+        assert_eq!(
+            Err(ParseError::UnknownFunction { name: "f".to_string(), span: Span::new(0, 3) }),
+            expr.evaluate()
+        );
+    }
+
+    #[test]
+    fn invalid_binary_operator() {
+        let expr = Expr::Binary {
+            op: Operator::OpenParen,
+            left: number(1.),
+            right: number(1.),
+            add_on: false,
+            span: Span::new(1, 2),
+        };
+        assert_eq!(
+            Err(ParseError::InvalidOperator { op: Operator::OpenParen, span: Span::new(1, 2) }),
+            expr.evaluate()
+        );
+    }
+
+    #[test]
+    fn invalid_unary_operator() {
+        let expr = Expr::Unary { op: Operator::Divide, operand: number(1.), span: Span::new(0, 1) };
+        assert_eq!(
+            Err(ParseError::InvalidOperator { op: Operator::Divide, span: Span::new(0, 1) }),
+            expr.evaluate()
+        );
+    }
+}
@@ -0,0 +1,434 @@
+//! # Description
+//! This module provides the evaluation of expressions with complex numbers.
+//! It is only compiled with the `complex` feature.
+
+use num_complex::Complex64;
+
+use crate::ast::{self, call_native, called_function, evaluate_chain, Expr};
+use crate::context::Context;
+use crate::error::{ParseError, Span};
+use crate::functions::{AngleMode, Arity, Implementation};
+use crate::integer::is_bitwise;
+use crate::parser::{parse_to_ast_with, Operator};
+use crate::special;
+
+/// How [`format_complex`] writes a complex number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ComplexFormat {
+    /// Real and imaginary part, like `3+4i`.
+    #[default]
+    Rectangular,
+    /// Magnitude and angle in the given unit, like `5∠53.13010235415598°`.
+    Polar(AngleMode),
+}
+
+/// Writes `value` in the given format.
+///
+/// Parts that are zero are left out of the rectangular format, so real numbers are written
+/// as usual and `2i` has no real part.
+///
+/// # Examples
+/// ```
+/// use math_lib::{evaluate_complex, format_complex, AngleMode, ComplexFormat, Context};
+///
+/// let value = evaluate_complex("(1+i)^2", &Context::new()).unwrap();
+/// assert_eq!(format_complex(value, ComplexFormat::Rectangular), "2i");
+/// assert_eq!(format_complex(value, ComplexFormat::Polar(AngleMode::Degrees)), "2∠90°");
+/// ```
+pub fn format_complex(value: Complex64, format: ComplexFormat) -> String {
+    match format {
+        ComplexFormat::Rectangular => {
+            let imaginary = match value.im {
+                1. => "i".to_string(),
+                -1. => "-i".to_string(),
+                im => format!("{im}i"),
+            };
+            match (value.re == 0., value.im == 0.) {
+                (_, true) => format!("{}", value.re + 0.),
+                (true, false) => imaginary,
+                (false, false) if value.im > 0. => format!("{}+{imaginary}", value.re),
+                (false, false) => format!("{}{imaginary}", value.re),
+            }
+        }
+        ComplexFormat::Polar(angle_mode) => {
+            let unit = match angle_mode {
+                AngleMode::Radians => "",
+                AngleMode::Degrees => "°",
+                AngleMode::Gradians => "ᵍ",
+            };
+            let (magnitude, angle) = value.to_polar();
+            format!("{magnitude}∠{}{unit}", angle_mode.from_radians(angle) + 0.)
+        }
+    }
+}
+
+/// Parses and evaluates `input` with complex numbers.
+///
+/// The input is parsed with the parser options of `context`, see
+/// [`Expr::evaluate_complex`] for how it is evaluated.
+///
+/// # Arguments
+///
+/// * `input` - A string slice containing the expression to evaluate
+/// * `context` - The context providing variables, functions and parser options
+///
+/// # Returns
+/// A Result containing the value of the expression or a ParseError if the expression
+/// cannot be parsed or evaluated.
+///
+/// # Examples
+/// ```
+/// use math_lib::{evaluate_complex, parse, Complex64, Context};
+///
+/// assert!(parse("2√(-4)").is_err());
+/// assert_eq!(evaluate_complex("2√(-4)", &Context::new()), Ok(Complex64::new(0., 2.)));
+/// assert_eq!(evaluate_complex("abs(3+4j)", &Context::new()), Ok(Complex64::new(5., 0.)));
+/// ```
+pub fn evaluate_complex(input: &str, context: &Context) -> Result<Complex64, ParseError> {
+    parse_to_ast_with(input, context.parser_options())?.evaluate_complex(context)
+}
+
+impl Expr {
+    /// Evaluates the expression with complex numbers, looking variables up in `context`.
+    ///
+    /// The names `i` and `j` stand for the imaginary unit unless they are variables.
+    /// Roots and powers take the principal value, so `√(-4)` is `2i` and `3√(-8)` is
+    /// `1+1.7320508075688772i`.
+    ///
+    /// The functions `re`, `im`, `abs`, `arg`, `conj`, `sqrt`, `exp` and `ln` accept
+    /// complex arguments, and `arg` returns its angle in the angle mode of `context`. The
+    /// other functions of `context` can only be called with real arguments, unless they
+    /// are user-defined.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - The context providing the values of variables and functions
+    ///
+    /// # Returns
+    /// A Result containing the value of the expression or a ParseError if an error occurs
+    /// during evaluation.
+    pub fn evaluate_complex(&self, context: &Context) -> Result<Complex64, ParseError> {
+        ComplexScope { context, locals: &[], depth: 0 }.evaluate(self)
+    }
+}
+
+/// Variables visible while evaluating an expression with complex numbers.
+struct ComplexScope<'a> {
+    context: &'a Context,
+    // Parameters of the function being called, empty outside of calls
+    locals: &'a [(&'a str, Complex64)],
+    // Number of function calls being evaluated
+    depth: usize,
+}
+
+impl ComplexScope<'_> {
+    fn evaluate(&self, expr: &Expr) -> Result<Complex64, ParseError> {
+        let value = match expr {
+            Expr::Number { value, .. } => Complex64::from(*value),
+            Expr::Variable { name, span } => self.variable(name, *span)?,
+            Expr::Constant { constant, .. } => Complex64::from(constant.value()),
+            Expr::Unary { op, operand, span } => apply_unary(*op, self.evaluate(operand)?, *span)?,
+            Expr::Binary { .. } => evaluate_chain(
+                expr,
+                |operand| self.evaluate(operand),
+                |left, op, right, add_on, span| {
+                    let mut right = self.evaluate(right)?;
+                    if add_on {
+                        right = apply_binary(Operator::Multiply, left, right, span)?;
+                    }
+                    apply_binary(op, left, right, span).map(positive_zero)
+                },
+            )?,
+            Expr::Call { name, args, span } => self.call(name, args, *span)?,
+        };
+        Ok(positive_zero(value))
+    }
+
+    /// Looks a variable up in the parameters, the context and finally the imaginary units.
+    fn variable(&self, name: &str, span: Span) -> Result<Complex64, ParseError> {
+        if let Some((_, value)) = self.locals.iter().find(|(local, _)| *local == name) {
+            return Ok(*value);
+        }
+        match (self.context.value(name), name) {
+            (Some(value), _) => Ok(Complex64::from(value)),
+            (None, "i" | "j") => Ok(Complex64::i()),
+            (None, _) => Err(ParseError::UnknownVariable { name: name.to_string(), span }),
+        }
+    }
+
+    /// Calls a function, preferring user-defined functions over the complex built-ins and
+    /// those over the native functions of the context.
+    fn call(&self, name: &str, args: &[Expr], span: Span) -> Result<Complex64, ParseError> {
+        let user_defined = self
+            .context
+            .function(name)
+            .is_some_and(|function| matches!(function.implementation(), Implementation::User(_)));
+        if let (false, Some(builtin)) = (user_defined, complex_builtin(name)) {
+            if args.len() != 1 {
+                return Err(ParseError::ArityMismatch {
+                    name: name.to_string(),
+                    expected: Arity::Fixed(1),
+                    found: args.len(),
+                    span,
+                });
+            }
+            let value = self.evaluate(&args[0])?;
+            let angle_mode = self.context.angle_mode();
+            return builtin(value, angle_mode).map_err(|message| ParseError::DomainError { message, span });
+        }
+
+        let function = called_function(self.context, name, args.len(), span)?;
+        match function.implementation() {
+            Implementation::Native(native) => {
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    let value = self.evaluate(arg)?;
+                    if value.im != 0. {
+                        return Err(ParseError::DomainError {
+                            message: format!("Function {name} is not defined for complex numbers"),
+                            span,
+                        });
+                    }
+                    values.push(value.re);
+                }
+                call_native(function, native.as_ref(), values, self.context, span).map(Complex64::from)
+            }
+            Implementation::User(function) => {
+                if self.depth >= self.context.max_call_depth() {
+                    return Err(ParseError::RecursionLimit { name: name.to_string(), span });
+                }
+                let mut locals = Vec::with_capacity(args.len());
+                for (param, arg) in function.params.iter().zip(args) {
+                    locals.push((param.as_str(), self.evaluate(arg)?));
+                }
+                // The body only sees its own parameters, not those of the caller
+                let scope = ComplexScope { context: self.context, locals: &locals, depth: self.depth + 1 };
+                scope.evaluate(&function.body)
+            }
+        }
+    }
+}
+
+/// Built-in function of a single complex argument.
+type ComplexFunction = fn(Complex64, AngleMode) -> Result<Complex64, String>;
+
+/// Returns the complex version of a built-in function, or `None` if there is none.
+fn complex_builtin(name: &str) -> Option<ComplexFunction> {
+    let function: ComplexFunction = match name {
+        "re" => |z, _| Ok(Complex64::from(z.re)),
+        "im" => |z, _| Ok(Complex64::from(z.im)),
+        "abs" => |z, _| Ok(Complex64::from(z.norm())),
+        "arg" => |z, angle_mode| Ok(Complex64::from(angle_mode.from_radians(z.arg()))),
+        "conj" => |z, _| Ok(z.conj()),
+        "sqrt" => |z, _| Ok(z.sqrt()),
+        "exp" => |z, _| Ok(z.exp()),
+        "ln" => |z, _| {
+            if z == Complex64::ZERO {
+                return Err("Cannot take the logarithm of zero".to_string());
+            }
+            Ok(z.ln())
+        },
+        _ => return None,
+    };
+    Some(function)
+}
+
+/// Applies a unary operator to an already evaluated operand.
+fn apply_unary(op: Operator, operand: Complex64, span: Span) -> Result<Complex64, ParseError> {
+    match op {
+        Operator::Plus => Ok(operand),
+        Operator::Minus => Ok(-operand),
+        Operator::Percent => Ok(operand / 100.),
+        Operator::Root => Ok(operand.sqrt()),
+        Operator::Factorial => {
+            if operand.im != 0. {
+                return Err(ParseError::DomainError {
+                    message: "Cannot take factorial of a complex number".to_string(),
+                    span,
+                });
+            }
+            special::factorial(operand.re)
+                .map(Complex64::from)
+                .map_err(|message| ParseError::DomainError { message, span })
+        }
+        Operator::BitNot => {
+            real_operand(op, operand, span)?;
+            ast::apply_unary(op, operand.re, span).map(Complex64::from)
+        }
+        _ => Err(ParseError::InvalidOperator { op, span }),
+    }
+}
+
+/// Returns `value` with a negative zero imaginary part replaced by zero.
+///
+/// Negating a real number gives it a negative zero imaginary part, which would put it on
+/// the other side of the branch cut of roots and logarithms.
+fn positive_zero(value: Complex64) -> Complex64 {
+    Complex64::new(value.re, value.im + 0.)
+}
+
+/// Applies a binary operator to already evaluated operands.
+fn apply_binary(op: Operator, left: Complex64, right: Complex64, span: Span) -> Result<Complex64, ParseError> {
+    match op {
+        Operator::Plus => Ok(left + right),
+        Operator::Minus => Ok(left - right),
+        Operator::Multiply => Ok(left * right),
+        Operator::Divide => {
+            if right == Complex64::ZERO {
+                return Err(ParseError::DivisionByZero { span });
+            }
+            Ok(left / right)
+        }
+        Operator::Percent => {
+            if right == Complex64::ZERO {
+                return Err(ParseError::DomainError {
+                    message: "Cannot take the percentage of zero".to_string(),
+                    span,
+                });
+            }
+            Ok(left / right * 100.)
+        }
+        Operator::Power => power(left, right, span),
+        op if op == Operator::Modulo || is_bitwise(op) => {
+            real_operand(op, left, span)?;
+            real_operand(op, right, span)?;
+            ast::apply_binary(op, left.re, right.re, span).map(Complex64::from)
+        }
+        Operator::Root => {
+            if left == Complex64::ZERO {
+                return Err(ParseError::DomainError { message: "Cannot take the 0th root".to_string(), span });
+            }
+            if left == Complex64::from(2.) {
+                return Ok(right.sqrt());
+            }
+            power(right, left.inv(), span)
+        }
+        _ => Err(ParseError::InvalidOperator { op, span }),
+    }
+}
+
+/// Returns an error unless `operand` of `op`, an operator only defined for real numbers,
+/// is real.
+fn real_operand(op: Operator, operand: Complex64, span: Span) -> Result<(), ParseError> {
+    if operand.im != 0. {
+        return Err(ParseError::DomainError {
+            message: format!("Operator {} is not defined for complex numbers", ast::symbol(op).trim()),
+            span,
+        });
+    }
+    Ok(())
+}
+
+/// Raises `base` to `exponent`, taking the principal value.
+///
+/// Whole exponents are applied by multiplication and real powers of positive numbers with
+/// `f64`, so both stay as exact as with real numbers.
+fn power(base: Complex64, exponent: Complex64, span: Span) -> Result<Complex64, ParseError> {
+    if base == Complex64::ZERO {
+        return match exponent.re {
+            re if re > 0. => Ok(Complex64::ZERO),
+            _ if exponent == Complex64::ZERO => Ok(Complex64::ONE),
+            _ => Err(ParseError::DivisionByZero { span }),
+        };
+    }
+    let whole = exponent.im == 0. && exponent.re.fract() == 0. && exponent.re.abs() <= i32::MAX as f64;
+    if whole {
+        return Ok(base.powi(exponent.re as i32));
+    }
+    if exponent.im == 0. && base.im == 0. && base.re > 0. {
+        return Ok(Complex64::from(base.re.powf(exponent.re)));
+    }
+    Ok(base.powc(exponent))
+}
+
+#[cfg(test)]
+mod complex_tests {
+    use std::f64::consts::PI;
+
+    use num_complex::Complex64;
+
+    use super::{evaluate_complex, format_complex, ComplexFormat};
+    use crate::{parse_to_ast, AngleMode, Context, ParseError, Span};
+
+    fn complex(input: &str) -> Result<Complex64, ParseError> {
+        evaluate_complex(input, &Context::new())
+    }
+
+    fn rectangular(input: &str) -> String {
+        format_complex(complex(input).unwrap(), ComplexFormat::Rectangular)
+    }
+
+    #[test]
+    fn imaginary_unit() {
+        assert_eq!(Ok(Complex64::new(0., 2.)), complex("2√(-4)"));
+        assert_eq!(Ok(Complex64::new(-1., 0.)), complex("i^2"));
+        assert_eq!(Ok(Complex64::new(3., 4.)), complex("3+4j"));
+        assert_eq!(Ok(Complex64::new(0., 2.)), complex("√-4"));
+        assert_eq!(Ok(Complex64::new(0., -1.)), complex("1/i"));
+    }
+
+    #[test]
+    fn variables_shadow_units() {
+        let mut context = Context::new();
+        context.set_variable("i", 5.);
+        assert_eq!(Ok(Complex64::new(10., 0.)), evaluate_complex("2i", &context));
+        context.define_function("f", vec!["z".to_string()], parse_to_ast("z*j").unwrap());
+        assert_eq!(Ok(Complex64::new(-1., 0.)), evaluate_complex("f(j)", &context));
+    }
+
+    #[test]
+    fn complex_functions() {
+        assert_eq!(Ok(Complex64::new(3., 0.)), complex("re(3-4i)"));
+        assert_eq!(Ok(Complex64::new(-4., 0.)), complex("im(3-4i)"));
+        assert_eq!(Ok(Complex64::new(5., 0.)), complex("abs(3-4i)"));
+        assert_eq!(Ok(Complex64::new(3., 4.)), complex("conj(3-4i)"));
+        assert_eq!(Ok(Complex64::new(PI / 2., 0.)), complex("arg(i)"));
+        assert_eq!(Ok(Complex64::new(0., PI)), complex("ln(-1)"));
+        assert_eq!(Ok(Complex64::new(2., 0.)), complex("max(1, 2)"));
+        let mut context = Context::new();
+        context.set_angle_mode(AngleMode::Degrees);
+        assert_eq!(Ok(Complex64::new(-90., 0.)), evaluate_complex("arg(-2i)", &context));
+    }
+
+    #[test]
+    fn powers_and_roots() {
+        assert_eq!(Ok(Complex64::new(0., 2.)), complex("(1+i)^2"));
+        assert_eq!(Ok(Complex64::new(2., 0.)), complex("3√8"));
+        assert_eq!(Ok(Complex64::new(1024., 0.)), complex("2^10"));
+        let root = complex("3√(-8)").unwrap();
+        assert!((root - Complex64::new(1., 3f64.sqrt())).norm() < 1e-12);
+        let euler = complex("e^(i*π)").unwrap();
+        assert!((euler + 1.).norm() < 1e-15);
+    }
+
+    #[test]
+    fn rectangular_format() {
+        assert_eq!("3+4i", rectangular("3+4i"));
+        assert_eq!("3-4i", rectangular("3-4i"));
+        assert_eq!("-i", rectangular("-i"));
+        assert_eq!("0.5i", rectangular("i/2"));
+        assert_eq!("0", rectangular("i-i"));
+        assert_eq!("-2", rectangular("-2"));
+    }
+
+    #[test]
+    fn polar_format() {
+        let value = complex("1+i").unwrap();
+        let radians = format_complex(value, ComplexFormat::Polar(AngleMode::Radians));
+        assert_eq!(format!("{}∠{}", 2f64.sqrt(), PI / 4.), radians);
+        assert_eq!(format!("{}∠45°", 2f64.sqrt()), format_complex(value, ComplexFormat::Polar(AngleMode::Degrees)));
+        assert_eq!("1∠200ᵍ", format_complex(Complex64::new(-1., 0.), ComplexFormat::Polar(AngleMode::Gradians)));
+    }
+
+    // These are invalid operations
+    #[test]
+    fn invalid_operations() {
+        assert_eq!(Err(ParseError::DivisionByZero { span: Span::new(1, 2) }), complex("1/(i-i)"));
+        assert_eq!(Err(ParseError::DivisionByZero { span: Span::new(1, 2) }), complex("0^-1"));
+        assert!(matches!(complex("i!"), Err(ParseError::DomainError { .. })));
+        assert!(matches!(complex("floor(i)"), Err(ParseError::DomainError { .. })));
+        assert!(matches!(complex("ln(0)"), Err(ParseError::DomainError { .. })));
+        assert!(matches!(complex("re(1, 2)"), Err(ParseError::ArityMismatch { .. })));
+        assert!(matches!(complex("k"), Err(ParseError::UnknownVariable { .. })));
+    }
+}
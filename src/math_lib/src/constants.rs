@@ -0,0 +1,236 @@
+//! # Description
+//! This module provides the mathematical constants understood by the parser and the
+//! catalogue of named physical constants.
+
+use std::f64::consts;
+
+#[cfg(feature = "units")]
+use crate::context::Context;
+#[cfg(feature = "units")]
+use crate::units::{evaluate_quantity, Dimension, Quantity};
+
+/// A mathematical constant that can be written directly in an expression.
+///
+/// Constants are read by the tokenizer, either as their symbol or as their name, and
+/// evaluate to the closest `f64` value. Their names are reserved and cannot be used as
+/// variables.
+///
+/// # Examples
+/// ```
+/// use math_lib::{parse, Constant};
+///
+/// assert_eq!(Constant::from_name("tau"), Some(Constant::Tau));
+/// assert_eq!(parse("τ/2"), Ok(std::f64::consts::PI));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Constant {
+    Pi,  // Represents 'π' or 'pi'
+    E,   // Represents 'e'
+    Tau, // Represents 'τ' or 'tau'
+    Phi, // Represents 'φ' or 'phi'
+}
+
+impl Constant {
+    /// All constants, in the order they are listed in the documentation.
+    pub const ALL: [Constant; 4] = [Constant::Pi, Constant::E, Constant::Tau, Constant::Phi];
+
+    /// Returns the value of the constant.
+    pub fn value(self) -> f64 {
+        match self {
+            Constant::Pi => consts::PI,
+            Constant::E => consts::E,
+            Constant::Tau => consts::TAU,
+            // (1 + √5) / 2
+            Constant::Phi => 1.618_033_988_749_895,
+        }
+    }
+
+    /// Returns the symbol the constant is printed with.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Constant::Pi => "π",
+            Constant::E => "e",
+            Constant::Tau => "τ",
+            Constant::Phi => "φ",
+        }
+    }
+
+    /// Returns the constant written as `name`, which may be its symbol or its spelled-out name.
+    pub fn from_name(name: &str) -> Option<Constant> {
+        match name {
+            "π" | "pi" => Some(Constant::Pi),
+            "e" => Some(Constant::E),
+            "τ" | "tau" => Some(Constant::Tau),
+            "φ" | "phi" => Some(Constant::Phi),
+            _ => None,
+        }
+    }
+}
+
+/// A constant of the catalogue, with its value, unit and description.
+///
+/// Physical constants use their CODATA 2018 values. Once
+/// [`Context::set_named_constants`](crate::Context::set_named_constants) enables the
+/// catalogue, a constant can be written in an expression by its name, unless a variable or
+/// parameter of the same name hides it.
+/// The names do not clash with unit symbols, so the standard acceleration of gravity is
+/// `g_n` and the Planck constant `h_planck`, leaving `g` to grams and `h` to hours.
+///
+/// # Examples
+/// ```
+/// use math_lib::{evaluate_with, Context, NamedConstant};
+///
+/// let avogadro = NamedConstant::find("N_A").unwrap();
+/// assert_eq!((avogadro.value, avogadro.unit), (6.02214076e23, "1/mol"));
+/// let mut context = Context::new();
+/// context.set_named_constants(true);
+/// assert_eq!(evaluate_with("2 g_n", &context), Ok(19.6133));
+/// assert!(NamedConstant::ALL.iter().any(|constant| constant.description == "Speed of light in vacuum"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NamedConstant {
+    /// Name the constant is written as in expressions.
+    pub name: &'static str,
+    /// Value of the constant in its unit.
+    pub value: f64,
+    /// Unit of the value in SI units, like `J/K`, or empty if the constant is dimensionless.
+    pub unit: &'static str,
+    /// What the constant is.
+    pub description: &'static str,
+}
+
+impl NamedConstant {
+    /// All constants of the catalogue, the mathematical constants first.
+    pub const ALL: &'static [NamedConstant] = &[
+        NamedConstant::new("pi", consts::PI, "", "Ratio of a circle's circumference to its diameter"),
+        NamedConstant::new("e", consts::E, "", "Euler's number, the base of the natural logarithm"),
+        NamedConstant::new("tau", consts::TAU, "", "Ratio of a circle's circumference to its radius"),
+        NamedConstant::new("phi", 1.618_033_988_749_895, "", "Golden ratio"),
+        NamedConstant::new("c", 299_792_458., "m/s", "Speed of light in vacuum"),
+        NamedConstant::new("h_planck", 6.626_070_15e-34, "J*s", "Planck constant"),
+        NamedConstant::new("hbar", 1.054_571_817e-34, "J*s", "Reduced Planck constant"),
+        NamedConstant::new("G", 6.674_30e-11, "m^3/(kg*s^2)", "Newtonian constant of gravitation"),
+        NamedConstant::new("g_n", 9.806_65, "m/s^2", "Standard acceleration of gravity"),
+        NamedConstant::new("N_A", 6.022_140_76e23, "1/mol", "Avogadro constant"),
+        NamedConstant::new("k_B", 1.380_649e-23, "J/K", "Boltzmann constant"),
+        NamedConstant::new("R", 8.314_462_618, "J/(mol*K)", "Molar gas constant"),
+        NamedConstant::new("q_e", 1.602_176_634e-19, "C", "Elementary charge"),
+        NamedConstant::new("m_e", 9.109_383_701_5e-31, "kg", "Electron mass"),
+        NamedConstant::new("m_p", 1.672_621_923_69e-27, "kg", "Proton mass"),
+        NamedConstant::new("m_n", 1.674_927_498_04e-27, "kg", "Neutron mass"),
+        NamedConstant::new("eps_0", 8.854_187_812_8e-12, "F/m", "Vacuum electric permittivity"),
+        NamedConstant::new("mu_0", 1.256_637_062_12e-6, "N/A^2", "Vacuum magnetic permeability"),
+        NamedConstant::new("sigma", 5.670_374_419e-8, "W/(m^2*K^4)", "Stefan-Boltzmann constant"),
+        NamedConstant::new("alpha", 7.297_352_569_3e-3, "", "Fine-structure constant"),
+    ];
+
+    const fn new(name: &'static str, value: f64, unit: &'static str, description: &'static str) -> NamedConstant {
+        NamedConstant { name, value, unit, description }
+    }
+
+    /// Returns the constant of the catalogue called `name`, or `None` if there is none.
+    pub fn find(name: &str) -> Option<&'static NamedConstant> {
+        NamedConstant::ALL.iter().find(|constant| constant.name == name)
+    }
+
+    /// Returns the constant as a quantity with its dimension.
+    ///
+    /// # Examples
+    /// ```
+    /// use math_lib::NamedConstant;
+    ///
+    /// let light = NamedConstant::find("c").unwrap().quantity();
+    /// assert_eq!(light.to_string(), "299792458 m/s");
+    /// ```
+    #[cfg(feature = "units")]
+    pub fn quantity(&self) -> Quantity {
+        if self.unit.is_empty() {
+            return Quantity::new(self.value, Dimension::NONE);
+        }
+        let unit = evaluate_quantity(self.unit, &Context::new()).expect("units of the catalogue are valid");
+        Quantity::new(self.value * unit.si_value(), unit.dimension())
+    }
+}
+
+#[cfg(test)]
+mod constant_tests {
+    use super::{Constant, NamedConstant};
+
+    #[test]
+    fn golden_ratio() {
+        assert_eq!((1. + 5f64.sqrt()) / 2., Constant::Phi.value());
+    }
+
+    #[test]
+    fn symbols_are_names() {
+        for constant in Constant::ALL {
+            assert_eq!(Some(constant), Constant::from_name(constant.symbol()));
+        }
+    }
+
+    #[test]
+    fn catalogue_contains_mathematical_constants() {
+        for constant in Constant::ALL {
+            let named = NamedConstant::ALL.iter().find(|named| Constant::from_name(named.name) == Some(constant));
+            assert_eq!(Some(constant.value()), named.map(|named| named.value));
+        }
+    }
+
+    #[test]
+    fn catalogue_names_are_unique() {
+        for (index, constant) in NamedConstant::ALL.iter().enumerate() {
+            assert_eq!(Some(constant), NamedConstant::find(constant.name));
+            assert!(NamedConstant::ALL[index + 1..].iter().all(|other| other.name != constant.name));
+        }
+    }
+
+    #[test]
+    fn catalogue_in_expressions() {
+        use crate::{evaluate_with, Context};
+
+        let mut context = Context::new();
+        context.set_named_constants(true);
+        assert_eq!(Ok(299_792_458. * 2.), evaluate_with("2c", &context));
+        assert_eq!(Ok(1.380_649e-23 * 6.022_140_76e23), evaluate_with("k_B*N_A", &context));
+        context.set_variable("c", 3.);
+        assert_eq!(Ok(6.), evaluate_with("2c", &context));
+    }
+
+    #[test]
+    fn catalogue_is_opt_in() {
+        use crate::{evaluate_script, evaluate_with, Context, ParseError, Span};
+
+        let mut context = Context::new();
+        assert_eq!(
+            Err(ParseError::UnknownVariable { name: "c".to_string(), span: Span::new(0, 1) }),
+            evaluate_with("c", &context)
+        );
+        // A misspelled parameter is reported instead of being read as the gas constant
+        assert_eq!(
+            Err(ParseError::UnknownVariable { name: "R".to_string(), span: Span::new(12, 13) }),
+            evaluate_script("f(r) = 2*π*R; f(1)", &mut context)
+        );
+        assert_eq!(Ok(std::f64::consts::PI), evaluate_with("pi", &context));
+    }
+
+    #[cfg(feature = "units")]
+    #[test]
+    fn catalogue_units() {
+        use crate::{evaluate_quantity, Context};
+
+        let mut context = Context::new();
+        context.set_named_constants(true);
+        for constant in NamedConstant::ALL {
+            assert!(evaluate_quantity(&format!("1 {}", constant.name), &context).is_ok(), "{}", constant.name);
+            if !constant.unit.is_empty() {
+                // Names of constants must not hide units
+                let unit = evaluate_quantity(constant.unit, &Context::new()).unwrap();
+                assert_eq!(unit.dimension(), constant.quantity().dimension(), "{}", constant.name);
+            }
+        }
+        assert_eq!("19.6133 kg*m/s^2", evaluate_quantity("2 kg * g_n", &context).unwrap().to_string());
+        let planck = NamedConstant::find("h_planck").unwrap().quantity();
+        assert_eq!("kg*m^2/s", planck.dimension().to_string());
+        assert_eq!("s^4*A^2/(kg*m^3)", NamedConstant::find("eps_0").unwrap().quantity().dimension().to_string());
+    }
+}
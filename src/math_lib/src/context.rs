@@ -0,0 +1,207 @@
+//! # Description
+//! This module provides the context expressions are evaluated in.
+
+use std::collections::HashMap;
+
+use crate::ast::Expr;
+use crate::constants::NamedConstant;
+use crate::functions::{AngleMode, Arity, Function, FunctionRegistry};
+use crate::options::ParserOptions;
+
+/// Default limit of nested function calls, see [`Context::set_max_call_depth`].
+const DEFAULT_MAX_CALL_DEPTH: usize = 128;
+
+/// Values available to an expression while it is evaluated.
+///
+/// A context maps variable names to their values and holds the [`FunctionRegistry`] of
+/// functions that can be called, starting with the built-in functions. It also sets the
+/// [`AngleMode`] used by trigonometric functions, the [`ParserOptions`] expressions are
+/// parsed with and whether the [`NamedConstant`] catalogue is available.
+/// Variables and functions live in separate namespaces. Names follow the identifier rules
+/// of the parser: a letter or `_` followed by letters, digits or `_`.
+///
+/// # Examples
+/// ```
+/// use math_lib::{evaluate_with, Context};
+///
+/// let mut context = Context::new();
+/// context.set_variable("rate", 25.);
+/// context.set_variable("hours", 8.);
+/// context.set_variable("fee", 10.);
+/// assert_eq!(evaluate_with("rate*hours+fee", &context), Ok(210.));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Context {
+    variables: HashMap<String, f64>,
+    functions: FunctionRegistry,
+    angle_mode: AngleMode,
+    max_call_depth: usize,
+    parser_options: ParserOptions,
+    named_constants: bool,
+}
+
+impl Default for Context {
+    fn default() -> Context {
+        Context {
+            variables: HashMap::new(),
+            functions: FunctionRegistry::new(),
+            angle_mode: AngleMode::default(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            parser_options: ParserOptions::default(),
+            named_constants: false,
+        }
+    }
+}
+
+impl Context {
+    /// Creates a context without variables, containing the built-in functions.
+    pub fn new() -> Context {
+        Context::default()
+    }
+
+    /// Sets the value of a variable, replacing its previous value.
+    pub fn set_variable(&mut self, name: &str, value: f64) {
+        self.variables.insert(name.to_string(), value);
+    }
+
+    /// Returns the value of a variable, or `None` if it is not defined.
+    pub fn variable(&self, name: &str) -> Option<f64> {
+        self.variables.get(name).copied()
+    }
+
+    /// Returns the value `name` stands for in an expression, the variable of that name or
+    /// else the [`NamedConstant`] of the catalogue, if it is enabled.
+    pub(crate) fn value(&self, name: &str) -> Option<f64> {
+        self.variable(name).or_else(|| self.named_constant(name).map(|constant| constant.value))
+    }
+
+    /// Returns the [`NamedConstant`] called `name`, or `None` if there is none or the
+    /// catalogue is not enabled.
+    pub(crate) fn named_constant(&self, name: &str) -> Option<&'static NamedConstant> {
+        NamedConstant::find(name).filter(|_| self.named_constants)
+    }
+
+    /// Removes a variable and returns its value, or `None` if it was not defined.
+    pub fn remove_variable(&mut self, name: &str) -> Option<f64> {
+        self.variables.remove(name)
+    }
+
+    /// Returns an iterator over all defined variables and their values.
+    pub fn variables(&self) -> impl Iterator<Item = (&str, f64)> {
+        self.variables.iter().map(|(name, value)| (name.as_str(), *value))
+    }
+
+    /// Defines a function, replacing any previous function with the same name.
+    ///
+    /// When called, the body is evaluated with every parameter bound to its argument.
+    /// Parameters shadow variables of the context with the same name.
+    ///
+    /// # Examples
+    /// ```
+    /// use math_lib::{evaluate_with, parse_to_ast, Context};
+    ///
+    /// let mut context = Context::new();
+    /// let body = parse_to_ast("x^2+y").unwrap();
+    /// context.define_function("f", vec!["x".to_string(), "y".to_string()], body);
+    /// assert_eq!(evaluate_with("f(3, 4)", &context), Ok(13.));
+    /// ```
+    pub fn define_function(&mut self, name: &str, params: Vec<String>, body: Expr) {
+        self.functions.define(name, params, body);
+    }
+
+    /// Registers a Rust closure as a function, see [`FunctionRegistry::register_native`].
+    pub fn register_function<F>(&mut self, name: &str, arity: Arity, function: F)
+    where
+        F: Fn(&[f64]) -> Result<f64, String> + Send + Sync + 'static,
+    {
+        self.functions.register_native(name, arity, function);
+    }
+
+    /// Returns a function, or `None` if it is not defined.
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.get(name)
+    }
+
+    /// Removes a function and returns it, or `None` if it was not defined.
+    pub fn remove_function(&mut self, name: &str) -> Option<Function> {
+        self.functions.remove(name)
+    }
+
+    /// Returns the registry of functions that can be called.
+    pub fn functions(&self) -> &FunctionRegistry {
+        &self.functions
+    }
+
+    /// Returns the registry of functions that can be called, for registering new ones.
+    pub fn functions_mut(&mut self) -> &mut FunctionRegistry {
+        &mut self.functions
+    }
+
+    /// Returns the unit in which angles are written.
+    pub fn angle_mode(&self) -> AngleMode {
+        self.angle_mode
+    }
+
+    /// Sets the unit in which angles are written, radians by default.
+    ///
+    /// # Examples
+    /// ```
+    /// use math_lib::{evaluate_with, AngleMode, Context};
+    ///
+    /// let mut context = Context::new();
+    /// context.set_angle_mode(AngleMode::Degrees);
+    /// assert_eq!(evaluate_with("sin(30)+cos(180)", &context).map(|x| (x * 1e9).round()), Ok(-0.5e9));
+    /// assert_eq!(evaluate_with("asin(1)", &context), Ok(90.));
+    /// ```
+    pub fn set_angle_mode(&mut self, angle_mode: AngleMode) {
+        self.angle_mode = angle_mode;
+    }
+
+    /// Returns the limit of nested function calls.
+    pub fn max_call_depth(&self) -> usize {
+        self.max_call_depth
+    }
+
+    /// Sets the limit of nested function calls.
+    ///
+    /// Evaluation fails once more calls than this are active at the same time, which stops
+    /// runaway recursion such as `f(x) = f(x)`. The default is 128.
+    pub fn set_max_call_depth(&mut self, max_call_depth: usize) {
+        self.max_call_depth = max_call_depth;
+    }
+
+    /// Returns the options expressions evaluated in this context are parsed with.
+    pub fn parser_options(&self) -> &ParserOptions {
+        &self.parser_options
+    }
+
+    /// Sets the options expressions evaluated in this context are parsed with.
+    pub fn set_parser_options(&mut self, parser_options: ParserOptions) {
+        self.parser_options = parser_options;
+    }
+
+    /// Returns whether the constants of the [`NamedConstant`] catalogue can be written by
+    /// their names.
+    pub fn named_constants(&self) -> bool {
+        self.named_constants
+    }
+
+    /// Sets whether the constants of the [`NamedConstant`] catalogue can be written by
+    /// their names, which is disabled by default.
+    ///
+    /// Without the catalogue, names like `c` or `G` are unknown variables unless they are
+    /// defined, so a misspelled name is reported instead of evaluating to a constant.
+    ///
+    /// # Examples
+    /// ```
+    /// use math_lib::{evaluate_with, Context, ParseError};
+    ///
+    /// let mut context = Context::new();
+    /// assert!(matches!(evaluate_with("2c", &context), Err(ParseError::UnknownVariable { .. })));
+    /// context.set_named_constants(true);
+    /// assert_eq!(evaluate_with("2c", &context), Ok(599_584_916.));
+    /// ```
+    pub fn set_named_constants(&mut self, named_constants: bool) {
+        self.named_constants = named_constants;
+    }
+}
@@ -0,0 +1,512 @@
+//! # Description
+//! This module provides the evaluation of expressions with arbitrary-precision decimals.
+//! It is only compiled with the `decimal` feature.
+
+use std::num::NonZeroU64;
+use std::str::FromStr;
+
+use bigdecimal::num_bigint::{BigInt, Sign};
+use bigdecimal::{BigDecimal, RoundingMode, ToPrimitive, Zero};
+
+use crate::ast::{call_native, called_function, evaluate_chain, Expr};
+use crate::context::Context;
+use crate::error::{ParseError, Span};
+use crate::functions::{self, Implementation};
+use crate::integer::{is_bitwise, IntegerMode};
+use crate::parser::{parse_to_ast_with, Operator};
+use crate::special;
+
+/// Digits computed beyond the precision while approximating roots, so that the rounded
+/// result is correct.
+const GUARD_DIGITS: u64 = 10;
+
+/// Largest number of Newton steps taken to approximate a root.
+const MAX_ROOT_ITERATIONS: usize = 200;
+
+/// Largest root degree computed with decimals; higher degrees are computed with `f64`.
+const MAX_EXACT_ROOT_DEGREE: i64 = 300;
+
+/// Largest power of ten a number literal or a power may reach to be computed with decimals;
+/// larger and smaller values are computed with `f64`.
+const MAX_EXACT_EXPONENT: u64 = 10_000;
+
+/// Largest number whose factorial is multiplied out with decimals; the factorials of larger
+/// numbers are computed with `f64`.
+const MAX_EXACT_FACTORIAL: u64 = 10_000;
+
+/// Precision and rounding of decimal evaluation.
+///
+/// Every operation rounds its result to `precision` significant digits with `rounding`,
+/// so `1/3` has exactly `precision` digits while `0.1+0.2` is exactly `0.3`.
+///
+/// # Examples
+/// ```
+/// use math_lib::{evaluate_decimal, Context, DecimalOptions, RoundingMode};
+///
+/// let options = DecimalOptions { precision: 5, rounding: RoundingMode::Down };
+/// assert_eq!(evaluate_decimal("2/3", &Context::new(), &options).unwrap().to_string(), "0.66666");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecimalOptions {
+    /// Number of significant digits results are rounded to. A precision of 0 is read as 1.
+    pub precision: u64,
+    /// How results are rounded to the precision.
+    pub rounding: RoundingMode,
+}
+
+impl Default for DecimalOptions {
+    /// Returns 34 significant digits rounded half to even, the precision of IEEE 754 decimal128.
+    fn default() -> DecimalOptions {
+        DecimalOptions { precision: 34, rounding: RoundingMode::HalfEven }
+    }
+}
+
+impl DecimalOptions {
+    /// Returns the rounding context of these options, widened by `guard` digits.
+    fn rounding_context(&self, guard: u64) -> bigdecimal::Context {
+        let precision = NonZeroU64::new(self.precision.saturating_add(guard)).unwrap_or(NonZeroU64::MIN);
+        bigdecimal::Context::new(precision, self.rounding)
+    }
+}
+
+/// Parses and evaluates `input` with arbitrary-precision decimals.
+///
+/// The input is parsed with the parser options of `context`, see
+/// [`Expr::evaluate_decimal`] for how it is evaluated.
+///
+/// # Arguments
+///
+/// * `input` - A string slice containing the expression to evaluate
+/// * `context` - The context providing variables, functions and parser options
+/// * `options` - The precision and rounding of the evaluation
+///
+/// # Returns
+/// A Result containing the value of the expression or a ParseError if the expression
+/// cannot be parsed or evaluated.
+///
+/// # Examples
+/// ```
+/// use math_lib::{evaluate_decimal, parse, Context, DecimalOptions};
+///
+/// assert_eq!(parse("0.1+0.2").unwrap().to_string(), "0.30000000000000004");
+/// let sum = evaluate_decimal("0.1+0.2", &Context::new(), &DecimalOptions::default()).unwrap();
+/// assert_eq!(sum.to_string(), "0.3");
+/// ```
+pub fn evaluate_decimal(input: &str, context: &Context, options: &DecimalOptions) -> Result<BigDecimal, ParseError> {
+    parse_to_ast_with(input, context.parser_options())?.evaluate_decimal(context, options)
+}
+
+impl Expr {
+    /// Evaluates the expression with arbitrary-precision decimals, looking variables up in
+    /// `context`.
+    ///
+    /// `+`, `-`, `*`, `/` and `%`, powers with a whole exponent, roots with a whole degree
+    /// and factorials of whole numbers are computed with decimals, so they are exact unless
+    /// the result has more digits than the precision. Everything else, like functions and
+    /// other powers, is computed with `f64` and converted, which limits it to about 16
+    /// significant digits. The same holds for constants and the variables of `context`,
+    /// which are stored as `f64`. Number literals are read with all their digits, unless
+    /// their exponent is beyond ±10000.
+    ///
+    /// The result is stripped of trailing zeros.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - The context providing the values of variables and functions
+    /// * `options` - The precision and rounding of the evaluation
+    ///
+    /// # Returns
+    /// A Result containing the value of the expression or a ParseError if an error occurs
+    /// during evaluation or a value computed with `f64` is not finite.
+    pub fn evaluate_decimal(&self, context: &Context, options: &DecimalOptions) -> Result<BigDecimal, ParseError> {
+        let scope = DecimalScope { context, options, locals: &[], depth: 0 };
+        Ok(scope.evaluate(self)?.normalized())
+    }
+}
+
+/// Variables visible while evaluating an expression with decimals.
+struct DecimalScope<'a> {
+    context: &'a Context,
+    options: &'a DecimalOptions,
+    // Parameters of the function being called, empty outside of calls
+    locals: &'a [(&'a str, BigDecimal)],
+    // Number of function calls being evaluated
+    depth: usize,
+}
+
+impl DecimalScope<'_> {
+    /// Evaluates `expr`, rounding every intermediate result to the precision.
+    fn evaluate(&self, expr: &Expr) -> Result<BigDecimal, ParseError> {
+        match expr {
+            Expr::Number { value, literal, span } => {
+                // The literal keeps the digits an f64 cannot hold
+                let exact = literal.as_deref().and_then(|literal| BigDecimal::from_str(literal).ok());
+                match exact.filter(|decimal| magnitude(decimal).unsigned_abs() <= MAX_EXACT_EXPONENT) {
+                    Some(decimal) => Ok(self.round(decimal)),
+                    None => self.decimal_of(*value, *span),
+                }
+            }
+            Expr::Variable { name, span } => {
+                if let Some((_, value)) = self.locals.iter().find(|(local, _)| local == name) {
+                    return Ok(value.clone());
+                }
+                let value = self.context.value(name).ok_or_else(|| ParseError::UnknownVariable {
+                    name: name.clone(),
+                    span: *span,
+                })?;
+                self.decimal_of(value, *span)
+            }
+            Expr::Constant { constant, span } => self.decimal_of(constant.value(), *span),
+            Expr::Unary { op, operand, span } => self.apply_unary(*op, self.evaluate(operand)?, *span),
+            Expr::Binary { .. } => evaluate_chain(
+                expr,
+                |operand| self.evaluate(operand),
+                |left, op, right, add_on, span| {
+                    let mut right = self.evaluate(right)?;
+                    if add_on {
+                        right = self.apply_binary(Operator::Multiply, left.clone(), right, span)?;
+                    }
+                    self.apply_binary(op, left, right, span)
+                },
+            ),
+            Expr::Call { name, args, span } => {
+                let function = called_function(self.context, name, args.len(), *span)?;
+                match function.implementation() {
+                    Implementation::Native(native) => {
+                        let values = args.iter().map(|arg| self.evaluate(arg).map(|value| to_f64(&value)));
+                        let values = values.collect::<Result<Vec<_>, _>>()?;
+                        let result = call_native(function, native.as_ref(), values, self.context, *span)?;
+                        self.decimal_of(result, *span)
+                    }
+                    Implementation::User(function) => {
+                        if self.depth >= self.context.max_call_depth() {
+                            return Err(ParseError::RecursionLimit { name: name.clone(), span: *span });
+                        }
+                        let mut locals = Vec::with_capacity(args.len());
+                        for (param, arg) in function.params.iter().zip(args) {
+                            locals.push((param.as_str(), self.evaluate(arg)?));
+                        }
+                        // The body only sees its own parameters, not those of the caller
+                        let scope = DecimalScope { locals: &locals, depth: self.depth + 1, ..*self };
+                        scope.evaluate(&function.body)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns `value` rounded to the precision.
+    fn round(&self, value: BigDecimal) -> BigDecimal {
+        self.options.rounding_context(0).round_decimal(value)
+    }
+
+    /// Converts a value computed with `f64` to a decimal with the fewest digits that
+    /// convert back to the same `f64`, so `0.1` stays `0.1`.
+    fn decimal_of(&self, value: f64, span: Span) -> Result<BigDecimal, ParseError> {
+        let not_finite = || ParseError::DomainError { message: format!("{value} is not a finite number"), span };
+        if !value.is_finite() {
+            return Err(not_finite());
+        }
+        // Display of f64 never uses an exponent, so the text is always a plain decimal
+        let decimal = BigDecimal::from_str(&value.to_string()).map_err(|_| not_finite())?;
+        Ok(self.round(decimal))
+    }
+
+    /// Applies a unary operator to an already evaluated operand.
+    fn apply_unary(&self, op: Operator, operand: BigDecimal, span: Span) -> Result<BigDecimal, ParseError> {
+        match op {
+            Operator::Plus => Ok(operand),
+            Operator::Minus => Ok(-operand),
+            Operator::Percent => Ok(self.round(operand * BigDecimal::new(BigInt::from(1), 2))),
+            Operator::Root => self.root(BigDecimal::from(2), operand, span),
+            Operator::Factorial => self.factorial(operand, span),
+            Operator::BitNot => {
+                let value = IntegerMode::default().apply_unary(op, integer_of(&operand, span)?, span)?;
+                Ok(BigDecimal::from(value))
+            }
+            _ => Err(ParseError::InvalidOperator { op, span }),
+        }
+    }
+
+    /// Applies a binary operator to already evaluated operands.
+    fn apply_binary(&self, op: Operator, left: BigDecimal, right: BigDecimal, span: Span) -> Result<BigDecimal, ParseError> {
+        match op {
+            Operator::Plus => Ok(self.round(left + right)),
+            Operator::Minus => Ok(self.round(left - right)),
+            Operator::Multiply => Ok(self.round(left * right)),
+            Operator::Divide => {
+                if right.is_zero() {
+                    return Err(ParseError::DivisionByZero { span });
+                }
+                Ok(divide(&left, &right, &self.options.rounding_context(0)))
+            }
+            Operator::Percent => {
+                if right.is_zero() {
+                    return Err(ParseError::DomainError {
+                        message: "Cannot take the percentage of zero".to_string(),
+                        span,
+                    });
+                }
+                let ratio = divide(&left, &right, &self.options.rounding_context(GUARD_DIGITS));
+                Ok(self.round(ratio * BigDecimal::from(100)))
+            }
+            Operator::Modulo => {
+                if right.is_zero() {
+                    return Err(ParseError::DivisionByZero { span });
+                }
+                // The remainder takes the sign of the divisor
+                let remainder = &left % &right;
+                if !remainder.is_zero() && (remainder.sign() == Sign::Minus) != (right.sign() == Sign::Minus) {
+                    Ok(self.round(remainder + right))
+                } else {
+                    Ok(self.round(remainder))
+                }
+            }
+            Operator::Power => self.power(left, right, span),
+            Operator::Root => self.root(left, right, span),
+            // Bitwise operators work on 64-bit signed integers
+            op if is_bitwise(op) => {
+                let mode = IntegerMode::default();
+                let value = mode.apply_binary(op, integer_of(&left, span)?, integer_of(&right, span)?, span)?;
+                Ok(BigDecimal::from(value))
+            }
+            _ => Err(ParseError::InvalidOperator { op, span }),
+        }
+    }
+
+    /// Raises `base` to `exponent`, with decimals if the exponent is a whole number.
+    fn power(&self, base: BigDecimal, exponent: BigDecimal, span: Span) -> Result<BigDecimal, ParseError> {
+        let approximate = || self.decimal_of(to_f64(&base).powf(to_f64(&exponent)), span);
+        let Some(exponent_int) = exponent.is_integer().then(|| exponent.to_i64()).flatten() else {
+            return approximate();
+        };
+        // The digits before or after the point of the power grow with the exponent
+        let estimate = (magnitude(&base).unsigned_abs() + 1).checked_mul(exponent_int.unsigned_abs());
+        if estimate.is_none_or(|estimate| estimate > MAX_EXACT_EXPONENT) {
+            return approximate();
+        }
+        if exponent_int >= 0 {
+            return Ok(base.powi_with_context(exponent_int, &self.options.rounding_context(0)));
+        }
+        if base.is_zero() {
+            return Err(ParseError::DivisionByZero { span });
+        }
+        let power = base.powi_with_context(-exponent_int, &self.options.rounding_context(GUARD_DIGITS));
+        Ok(divide(&BigDecimal::from(1), &power, &self.options.rounding_context(0)))
+    }
+
+    /// Computes the `degree`-th root of `radicand`, with decimals if the degree is a whole
+    /// number.
+    fn root(&self, degree: BigDecimal, radicand: BigDecimal, span: Span) -> Result<BigDecimal, ParseError> {
+        // The f64 root checks the domain, so both representations reject the same operands
+        let approximation = functions::root(to_f64(&degree), to_f64(&radicand))
+            .map_err(|message| ParseError::DomainError { message, span })?;
+        let degree = degree.is_integer().then(|| degree.to_i64()).flatten();
+        let exact = degree
+            .filter(|degree| (1..=MAX_EXACT_ROOT_DEGREE).contains(degree) && !radicand.is_zero())
+            .and_then(|degree| nth_root(&radicand, degree, self.options));
+        match exact {
+            Some(root) => Ok(root),
+            None => self.decimal_of(approximation, span),
+        }
+    }
+
+    /// Computes `x!`, multiplying whole numbers out with decimals.
+    fn factorial(&self, x: BigDecimal, span: Span) -> Result<BigDecimal, ParseError> {
+        let n = x.is_integer().then(|| x.to_u64()).flatten();
+        match n {
+            Some(n) if n <= MAX_EXACT_FACTORIAL => {
+                let rounding = self.options.rounding_context(0);
+                Ok((2..=n).fold(BigDecimal::from(1), |product, i| rounding.round_decimal(product * BigDecimal::from(i))))
+            }
+            _ => {
+                let result = special::factorial(to_f64(&x)).map_err(|message| ParseError::DomainError { message, span })?;
+                self.decimal_of(result, span)
+            }
+        }
+    }
+}
+
+/// Converts a decimal to the nearest `f64`, for the operations computed with `f64`.
+fn to_f64(value: &BigDecimal) -> f64 {
+    value.to_f64().unwrap_or(f64::NAN)
+}
+
+/// Returns the power of ten of the leading digit of `value`, so 0.01 has a magnitude of -2.
+fn magnitude(value: &BigDecimal) -> i64 {
+    (value.digits() as i64).saturating_sub(value.fractional_digit_count()).saturating_sub(1)
+}
+
+/// Converts an operand of a bitwise operator to an integer, which it must be.
+fn integer_of(value: &BigDecimal, span: Span) -> Result<i128, ParseError> {
+    if !value.is_integer() {
+        return Err(ParseError::DomainError { message: format!("{value} is not a whole number"), span });
+    }
+    value.to_i128().filter(|integer| integer.unsigned_abs() < 1 << 64).ok_or_else(|| ParseError::DomainError {
+        message: format!("{value} does not fit into 64 bits"),
+        span,
+    })
+}
+
+/// Divides `left` by `right`, which must not be zero, rounding the quotient with `rounding`.
+///
+/// The quotient is computed to one digit more than the precision, and a remainder is kept
+/// as a trailing nonzero digit, so the quotient is rounded as if all its digits were known.
+fn divide(left: &BigDecimal, right: &BigDecimal, rounding: &bigdecimal::Context) -> BigDecimal {
+    let (numerator, numerator_scale) = left.as_bigint_and_exponent();
+    let (denominator, denominator_scale) = right.as_bigint_and_exponent();
+    let digits = |n: &BigInt| n.to_string().trim_start_matches('-').len() as i64;
+    let precision = rounding.precision().get() as i64;
+    let shift = (precision + 1 + digits(&denominator) - digits(&numerator)).max(0);
+    let shifted = numerator * BigInt::from(10).pow(shift as u32);
+    let mut quotient = &shifted / &denominator;
+    let mut scale = numerator_scale - denominator_scale + shift;
+    if !(shifted % &denominator).is_zero() {
+        let sign = if (left.sign() == Sign::Minus) == (right.sign() == Sign::Minus) { 1 } else { -1 };
+        quotient = quotient * 10 + sign;
+        scale += 1;
+    }
+    rounding.round_decimal(BigDecimal::new(quotient, scale))
+}
+
+/// Approximates the `degree`-th root of the positive `radicand` with Newton's method.
+///
+/// Returns `None` if the radicand is out of the range of `f64`, which the first guess is
+/// computed with.
+fn nth_root(radicand: &BigDecimal, degree: i64, options: &DecimalOptions) -> Option<BigDecimal> {
+    if degree == 1 {
+        return Some(options.rounding_context(0).round_decimal(radicand.clone()));
+    }
+    let working = options.rounding_context(GUARD_DIGITS);
+    let guess = to_f64(radicand).powf(1. / degree as f64);
+    if !guess.is_finite() || guess == 0. {
+        return None;
+    }
+    let mut root = BigDecimal::from_str(&guess.to_string()).ok()?;
+    let degree_decimal = BigDecimal::from(degree);
+    let previous_degree = BigDecimal::from(degree - 1);
+    for _ in 0..MAX_ROOT_ITERATIONS {
+        // x' = ((n - 1)x + a / x^(n - 1)) / n
+        let power = root.powi_with_context(degree - 1, &working);
+        let sum = working.round_decimal(&previous_degree * &root + divide(radicand, &power, &working));
+        let next = divide(&sum, &degree_decimal, &working);
+        if next == root {
+            break;
+        }
+        root = next;
+    }
+    Some(options.rounding_context(0).round_decimal(root.abs()))
+}
+
+#[cfg(test)]
+mod decimal_tests {
+    use bigdecimal::{BigDecimal, RoundingMode};
+
+    use super::{evaluate_decimal, DecimalOptions};
+    use crate::{Context, ParseError, Span};
+
+    fn decimal(input: &str) -> Result<String, ParseError> {
+        evaluate_decimal(input, &Context::new(), &DecimalOptions::default()).map(|value| value.to_string())
+    }
+
+    fn rounded(input: &str, precision: u64, rounding: RoundingMode) -> String {
+        let options = DecimalOptions { precision, rounding };
+        evaluate_decimal(input, &Context::new(), &options).unwrap().to_string()
+    }
+
+    #[test]
+    fn exact_arithmetic() {
+        assert_eq!(Ok("0.3".to_string()), decimal("0.1+0.2"));
+        assert_eq!(Ok("0.1".to_string()), decimal("0.3-0.2"));
+        assert_eq!(Ok("0.02".to_string()), decimal("0.1*0.2"));
+        assert_eq!(Ok("0.125".to_string()), decimal("1/8"));
+        assert_eq!(Ok("25".to_string()), decimal("1%4"));
+        assert_eq!(Ok("-3.3".to_string()), decimal("-(1.1+2.2)"));
+    }
+
+    #[test]
+    fn literals_keep_their_digits() {
+        assert_eq!(Ok("1E-20".to_string()), decimal("1.00000000000000000001-1"));
+        assert_eq!(Ok("12345678901234567890".to_string()), decimal("12345678901234567890"));
+        assert_eq!(Ok("1E-400".to_string()), decimal("1e-400"));
+        assert_eq!(Ok("18446744073709551615".to_string()), decimal("0xFFFFFFFFFFFFFFFF"));
+        assert_eq!(Ok("0.5".to_string()), decimal(".5"));
+        // Literals with a huge exponent are read as f64
+        assert!(matches!(decimal("1e1000000000 + 1e-1000000000"), Err(ParseError::DomainError { .. })));
+        assert!(matches!(decimal("1e1000000000 mod 7"), Err(ParseError::DomainError { .. })));
+        assert_eq!(Ok("1".to_string()), decimal("1 + 1e-1000000000"));
+        assert_eq!(Ok("1e+10000".to_string()), decimal("1e10000"));
+    }
+
+    #[test]
+    fn precision() {
+        assert_eq!(Ok("0.3333333333333333333333333333333333".to_string()), decimal("1/3"));
+        assert_eq!("0.667", rounded("2/3", 3, RoundingMode::HalfEven));
+        assert_eq!("0.666", rounded("2/3", 3, RoundingMode::Down));
+        assert_eq!("-0.67", rounded("-2/3", 2, RoundingMode::Floor));
+        assert_eq!("0.1", rounded("1/8", 1, RoundingMode::HalfEven));
+        assert_eq!("0.2", rounded("1/8", 1, RoundingMode::Up));
+    }
+
+    #[test]
+    fn powers_and_roots() {
+        assert_eq!(Ok("1.21".to_string()), decimal("1.1^2"));
+        assert_eq!(Ok("0.001".to_string()), decimal("10^-3"));
+        assert_eq!(Ok("1267650600228229401496703205376".to_string()), decimal("2^100"));
+        assert_eq!(Ok("1.1".to_string()), decimal("√1.21"));
+        assert_eq!(Ok("0.2".to_string()), decimal("3√0.008"));
+        assert_eq!(Ok("1.414213562373095048801688724209698".to_string()), decimal("√2"));
+        assert_eq!(Ok("2".to_string()), decimal("4^0.5"));
+        assert_eq!(Ok("1e+3000".to_string()), decimal("10^3000"));
+        // Powers too large for decimals are computed with f64
+        assert_eq!(Ok("0".to_string()), decimal("2^-9223372036854775808"));
+        assert_eq!(Ok("0".to_string()), decimal("0.1^9223372036854775807"));
+        assert!(matches!(decimal("10^9223372036854775807"), Err(ParseError::DomainError { .. })));
+        assert!(matches!(decimal("(10^5000)^5000"), Err(ParseError::DomainError { .. })));
+    }
+
+    #[test]
+    fn factorials() {
+        assert_eq!(Ok("2432902008176640000".to_string()), decimal("20!"));
+        assert_eq!(
+            "30414093201713378043612608166064768844377641568960512000000000000",
+            rounded("50!", 100, RoundingMode::HalfEven)
+        );
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        assert_eq!(Ok("15".to_string()), decimal("0xFF & 0x0F"));
+        assert_eq!(Ok("-256".to_string()), decimal("~0xFF"));
+        assert_eq!(Ok("9007199254740993".to_string()), decimal("2^53 | 1"));
+        assert!(matches!(decimal("0.5 << 1"), Err(ParseError::DomainError { .. })));
+        assert!(matches!(decimal("2^64 | 0"), Err(ParseError::DomainError { .. })));
+    }
+
+    #[test]
+    fn modulo() {
+        assert_eq!(Ok("0.1".to_string()), decimal("0.7 mod 0.3"));
+        assert_eq!(Ok("0.2".to_string()), decimal("-0.7 mod 0.3"));
+        assert_eq!(Ok("-0.2".to_string()), decimal("0.7 mod -0.3"));
+        assert!(matches!(decimal("1 mod 0"), Err(ParseError::DivisionByZero { .. })));
+    }
+
+    #[test]
+    fn functions_and_variables() {
+        let mut context = Context::new();
+        context.set_variable("x", 0.1);
+        context.define_function("f", vec!["a".to_string()], crate::parse_to_ast("a*3").unwrap());
+        let value = evaluate_decimal("f(x)+max(0.1, 0.2)", &context, &DecimalOptions::default());
+        assert_eq!(Ok("0.5".parse::<BigDecimal>().unwrap()), value);
+    }
+
+    // These are invalid operations
+    #[test]
+    fn invalid_operations() {
+        assert_eq!(Err(ParseError::DivisionByZero { span: Span::new(3, 4) }), decimal("0.1/0"));
+        assert_eq!(Err(ParseError::DivisionByZero { span: Span::new(1, 2) }), decimal("0^-1"));
+        assert!(matches!(decimal("√-4"), Err(ParseError::DomainError { .. })));
+        assert!(matches!(decimal("(-3)!"), Err(ParseError::DomainError { .. })));
+        assert!(matches!(decimal("(-8)^(1/3)"), Err(ParseError::DomainError { .. })));
+    }
+}
@@ -0,0 +1,150 @@
+//! # Description
+//! This module provides the error type returned when parsing or evaluating an expression.
+
+use std::error::Error;
+use std::fmt;
+
+use crate::functions::Arity;
+use crate::parser::Operator;
+
+/// A range of bytes in the input string.
+///
+/// `start` is inclusive and `end` is exclusive, so the offending text can be obtained with
+/// `&input[span.start..span.end]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// Creates a span covering the bytes `start..end`.
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    /// Creates the smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+/// Error returned by the expression parser.
+///
+/// Every variant carries the [`Span`] of the input that caused it, so callers can point
+/// at the offending character instead of matching on the error message.
+///
+/// # Examples
+/// ```
+/// use math_lib::{parse, ParseError, Span};
+///
+/// let error = parse("1/0").unwrap_err();
+/// assert_eq!(error, ParseError::DivisionByZero { span: Span::new(1, 2) });
+/// assert_eq!(error.to_string(), "Cannot divide by zero");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// The input contains a character that is not part of the grammar.
+    UnexpectedChar { ch: char, span: Span },
+    /// A number literal could not be read, for example `1.5.5`.
+    MalformedNumber { span: Span },
+    /// A number literal could be meant in another locale, for example `1,5` when `,` groups
+    /// digits. See [`NumberFormat`](crate::NumberFormat).
+    AmbiguousNumber { span: Span },
+    /// A parenthesis has no matching counterpart.
+    UnbalancedParen { span: Span },
+    /// The tokens do not form a valid expression, for example `1+` or `()`.
+    InvalidExpression { span: Span },
+    /// An operator was used in a position where it cannot be evaluated.
+    InvalidOperator { op: Operator, span: Span },
+    /// The right operand of a division is zero.
+    DivisionByZero { span: Span },
+    /// An operation was applied outside of its domain, for example the root of a negative number.
+    DomainError { message: String, span: Span },
+    /// A called function does not exist.
+    UnknownFunction { name: String, span: Span },
+    /// Two quantities with different dimensions were combined, for example `3 m + 2 s`.
+    /// The units are written in SI base units.
+    IncompatibleUnits { left: String, right: String, span: Span },
+    /// A variable is not defined in the context.
+    UnknownVariable { name: String, span: Span },
+    /// A function was called with the wrong number of arguments.
+    ArityMismatch { name: String, expected: Arity, found: usize, span: Span },
+    /// Too many function calls were nested, usually because of endless recursion.
+    RecursionLimit { name: String, span: Span },
+    /// Parentheses, operators or function calls are nested too deeply, or too many operators
+    /// are written in a row, for the expression to be evaluated.
+    NestingLimit { span: Span },
+}
+
+impl ParseError {
+    /// Returns the part of the input the error refers to.
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedChar { span, .. }
+            | ParseError::MalformedNumber { span }
+            | ParseError::AmbiguousNumber { span }
+            | ParseError::UnbalancedParen { span }
+            | ParseError::InvalidExpression { span }
+            | ParseError::InvalidOperator { span, .. }
+            | ParseError::DivisionByZero { span }
+            | ParseError::DomainError { span, .. }
+            | ParseError::UnknownFunction { span, .. }
+            | ParseError::UnknownVariable { span, .. }
+            | ParseError::IncompatibleUnits { span, .. }
+            | ParseError::ArityMismatch { span, .. }
+            | ParseError::RecursionLimit { span, .. }
+            | ParseError::NestingLimit { span } => *span,
+        }
+    }
+
+    /// Returns the error with its span replaced by `span`.
+    pub(crate) fn with_span(mut self, span: Span) -> ParseError {
+        match &mut self {
+            ParseError::UnexpectedChar { span: old, .. }
+            | ParseError::MalformedNumber { span: old }
+            | ParseError::AmbiguousNumber { span: old }
+            | ParseError::UnbalancedParen { span: old }
+            | ParseError::InvalidExpression { span: old }
+            | ParseError::InvalidOperator { span: old, .. }
+            | ParseError::DivisionByZero { span: old }
+            | ParseError::DomainError { span: old, .. }
+            | ParseError::UnknownFunction { span: old, .. }
+            | ParseError::UnknownVariable { span: old, .. }
+            | ParseError::IncompatibleUnits { span: old, .. }
+            | ParseError::ArityMismatch { span: old, .. }
+            | ParseError::RecursionLimit { span: old, .. }
+            | ParseError::NestingLimit { span: old } => *old = span,
+        }
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedChar { ch, .. } => write!(f, "Invalid character in input: {ch}"),
+            ParseError::MalformedNumber { .. } => write!(f, "Failed to parse number"),
+            ParseError::AmbiguousNumber { .. } => {
+                write!(f, "Ambiguous number, group the digits in threes and use the decimal separator of the locale")
+            }
+            ParseError::UnbalancedParen { .. } => write!(f, "Unbalanced parenthesis"),
+            ParseError::InvalidExpression { .. } => write!(f, "Invalid expression"),
+            ParseError::InvalidOperator { op, .. } => write!(f, "{op:?} is an invalid operator"),
+            ParseError::DivisionByZero { .. } => write!(f, "Cannot divide by zero"),
+            ParseError::DomainError { message, .. } => write!(f, "{message}"),
+            ParseError::UnknownFunction { name, .. } => write!(f, "Unknown function: {name}"),
+            ParseError::UnknownVariable { name, .. } => write!(f, "Unknown variable: {name}"),
+            ParseError::IncompatibleUnits { left, right, .. } => write!(f, "Incompatible units: {left} and {right}"),
+            ParseError::ArityMismatch { name, expected, found, .. } => {
+                write!(f, "Function {name} expects {expected} argument(s), found {found}")
+            }
+            ParseError::RecursionLimit { name, .. } => {
+                write!(f, "Maximum recursion depth exceeded when calling {name}")
+            }
+            ParseError::NestingLimit { .. } => write!(f, "Maximum nesting depth exceeded"),
+        }
+    }
+}
+
+impl Error for ParseError {}
@@ -0,0 +1,546 @@
+//! # Description
+//! This module provides the registry of functions that can be called from expressions.
+
+use std::collections::HashMap;
+use std::f64::consts::FRAC_PI_2;
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+use crate::ast::Expr;
+use crate::special;
+
+/// Signature of a function implemented in Rust.
+///
+/// The function receives the evaluated arguments, whose count already matches its
+/// [`Arity`], and returns the result or a message describing why it cannot be computed.
+pub type NativeFunction = dyn Fn(&[f64]) -> Result<f64, String> + Send + Sync;
+
+/// Number of arguments a function accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    /// Exactly this many arguments.
+    Fixed(usize),
+    /// At least `min` arguments.
+    Variadic { min: usize },
+}
+
+impl Arity {
+    /// Returns whether a call with `count` arguments is valid.
+    pub fn accepts(self, count: usize) -> bool {
+        match self {
+            Arity::Fixed(expected) => count == expected,
+            Arity::Variadic { min } => count >= min,
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arity::Fixed(expected) => write!(f, "{expected}"),
+            Arity::Variadic { min } => write!(f, "at least {min}"),
+        }
+    }
+}
+
+/// Unit in which angles are written in expressions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AngleMode {
+    /// A full turn is `2π`.
+    #[default]
+    Radians,
+    /// A full turn is `360`.
+    Degrees,
+    /// A full turn is `400`.
+    Gradians,
+}
+
+impl AngleMode {
+    /// Returns the size of a right angle in this unit.
+    fn right_angle(self) -> f64 {
+        match self {
+            AngleMode::Radians => FRAC_PI_2,
+            AngleMode::Degrees => 90.,
+            AngleMode::Gradians => 100.,
+        }
+    }
+
+    /// Converts an angle written in this unit to radians.
+    ///
+    /// Whole multiples of a right angle become exact multiples of `π/2`, which the
+    /// built-in trigonometric functions evaluate exactly.
+    pub fn to_radians(self, angle: f64) -> f64 {
+        match right_angles(angle, self.right_angle()) {
+            Some(count) => count * FRAC_PI_2,
+            None => angle / self.right_angle() * FRAC_PI_2,
+        }
+    }
+
+    /// Converts an angle in radians to this unit.
+    pub fn from_radians(self, angle: f64) -> f64 {
+        match right_angles(angle, FRAC_PI_2) {
+            Some(count) => count * self.right_angle(),
+            None => angle / FRAC_PI_2 * self.right_angle(),
+        }
+    }
+}
+
+/// Returns how many right angles `angle` is, if it is a whole multiple of `right_angle`.
+fn right_angles(angle: f64, right_angle: f64) -> Option<f64> {
+    let count = angle / right_angle;
+    (count.fract() == 0. && count * right_angle == angle).then_some(count)
+}
+
+/// Which values of a function are angles, converted according to the [`AngleMode`] of
+/// the evaluation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AngleConversion {
+    /// No value is an angle.
+    #[default]
+    None,
+    /// All arguments are angles; the function receives them in radians.
+    Arguments,
+    /// The result is an angle; the function returns it in radians.
+    Result,
+}
+
+/// A function defined in the expression language, for example `f(x, y) = x^2 + y`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserFunction {
+    /// Names of the parameters, in the order the arguments are passed.
+    pub params: Vec<String>,
+    /// Expression computing the result from the parameters.
+    pub body: Expr,
+}
+
+/// How a function computes its result.
+#[derive(Clone)]
+pub enum Implementation {
+    /// A Rust closure.
+    Native(Arc<NativeFunction>),
+    /// A function defined in the expression language.
+    User(UserFunction),
+}
+
+impl fmt::Debug for Implementation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Implementation::Native(_) => write!(f, "Native"),
+            Implementation::User(function) => f.debug_tuple("User").field(function).finish(),
+        }
+    }
+}
+
+/// A function stored in a [`FunctionRegistry`].
+#[derive(Debug, Clone)]
+pub struct Function {
+    name: String,
+    arity: Arity,
+    angles: AngleConversion,
+    implementation: Implementation,
+}
+
+impl Function {
+    /// Returns the name the function is called by.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the number of arguments the function accepts.
+    pub fn arity(&self) -> Arity {
+        self.arity
+    }
+
+    /// Returns which values of the function are angles.
+    pub fn angle_conversion(&self) -> AngleConversion {
+        self.angles
+    }
+
+    /// Returns how the function computes its result.
+    pub fn implementation(&self) -> &Implementation {
+        &self.implementation
+    }
+}
+
+/// Functions available to expressions, looked up by name.
+///
+/// [`FunctionRegistry::new`] contains the built-in functions: `abs`, `sqrt`, `root(n, x)`,
+/// `floor`, `ceil`, `round`, the variadic `min`, `max`, `sum` and `avg`, the trigonometric
+/// functions `sin`, `cos`, `tan`, `asin`, `acos`, `atan` and `atan2(y, x)`, the
+/// hyperbolic functions `sinh`, `cosh`, `tanh`, `asinh`, `acosh` and `atanh`, the
+/// exponential and logarithms `exp`, `ln`, `log10`, `log2` and `log(b, x)`, and the gamma
+/// function `gamma`, its logarithm `lgamma` and the beta function `beta(a, b)`.
+///
+/// # Examples
+/// ```
+/// use math_lib::{evaluate_with, Arity, Context};
+///
+/// let mut context = Context::new();
+/// context.functions_mut().register_native("hypot", Arity::Fixed(2), |args| Ok(args[0].hypot(args[1])));
+/// assert_eq!(evaluate_with("hypot(3, 4) + max(1, 7, 2)", &context), Ok(12.));
+/// ```
+#[derive(Debug, Clone)]
+pub struct FunctionRegistry {
+    // Shared between clones until one of them is changed
+    functions: Arc<HashMap<String, Function>>,
+}
+
+impl Default for FunctionRegistry {
+    fn default() -> FunctionRegistry {
+        FunctionRegistry::new()
+    }
+}
+
+impl FunctionRegistry {
+    /// Creates a registry containing the built-in functions.
+    ///
+    /// The built-in functions are registered once and shared by every registry until it is
+    /// changed, so creating a registry does not allocate.
+    pub fn new() -> FunctionRegistry {
+        FunctionRegistry::builtins().clone()
+    }
+
+    /// Returns the registry of the built-in functions, which is built once.
+    fn builtins() -> &'static FunctionRegistry {
+        static BUILTINS: OnceLock<FunctionRegistry> = OnceLock::new();
+        BUILTINS.get_or_init(|| {
+            let mut registry = FunctionRegistry::empty();
+            registry.register_native("abs", Arity::Fixed(1), |args| Ok(args[0].abs()));
+            registry.register_native("sqrt", Arity::Fixed(1), |args| root(2., args[0]));
+            registry.register_native("root", Arity::Fixed(2), |args| root(args[0], args[1]));
+            registry.register_native("floor", Arity::Fixed(1), |args| Ok(args[0].floor()));
+            registry.register_native("ceil", Arity::Fixed(1), |args| Ok(args[0].ceil()));
+            registry.register_native("round", Arity::Fixed(1), |args| Ok(args[0].round()));
+            registry.register_native("min", Arity::Variadic { min: 1 }, |args| {
+                Ok(args.iter().copied().fold(f64::INFINITY, f64::min))
+            });
+            registry.register_native("max", Arity::Variadic { min: 1 }, |args| {
+                Ok(args.iter().copied().fold(f64::NEG_INFINITY, f64::max))
+            });
+            registry.register_native("sum", Arity::Variadic { min: 1 }, |args| Ok(args.iter().sum()));
+            registry.register_native("avg", Arity::Variadic { min: 1 }, |args| {
+                Ok(args.iter().sum::<f64>() / args.len() as f64)
+            });
+
+            let one = Arity::Fixed(1);
+            registry.register_angular("sin", one, AngleConversion::Arguments, |args| Ok(sin(args[0])));
+            registry.register_angular("cos", one, AngleConversion::Arguments, |args| Ok(cos(args[0])));
+            registry.register_angular("tan", one, AngleConversion::Arguments, |args| tan(args[0]));
+            registry.register_angular("asin", one, AngleConversion::Result, |args| {
+                if !(-1. ..=1.).contains(&args[0]) {
+                    return Err("Cannot take asin of a number outside of [-1, 1]".to_string());
+                }
+                Ok(args[0].asin())
+            });
+            registry.register_angular("acos", one, AngleConversion::Result, |args| {
+                if !(-1. ..=1.).contains(&args[0]) {
+                    return Err("Cannot take acos of a number outside of [-1, 1]".to_string());
+                }
+                Ok(args[0].acos())
+            });
+            registry.register_angular("atan", one, AngleConversion::Result, |args| Ok(args[0].atan()));
+            registry.register_angular("atan2", Arity::Fixed(2), AngleConversion::Result, |args| {
+                Ok(args[0].atan2(args[1]))
+            });
+
+            registry.register_native("sinh", one, |args| Ok(args[0].sinh()));
+            registry.register_native("cosh", one, |args| Ok(args[0].cosh()));
+            registry.register_native("tanh", one, |args| Ok(args[0].tanh()));
+            registry.register_native("asinh", one, |args| Ok(args[0].asinh()));
+            registry.register_native("acosh", one, |args| {
+                if args[0] < 1. {
+                    return Err("Cannot take acosh of a number less than 1".to_string());
+                }
+                Ok(args[0].acosh())
+            });
+            registry.register_native("atanh", one, |args| {
+                if args[0] <= -1. || args[0] >= 1. {
+                    return Err("Cannot take atanh of a number outside of (-1, 1)".to_string());
+                }
+                Ok(args[0].atanh())
+            });
+
+            registry.register_native("exp", one, |args| Ok(args[0].exp()));
+            registry.register_native("ln", one, |args| Ok(logarithm_argument(args[0])?.ln()));
+            registry.register_native("log10", one, |args| Ok(logarithm_argument(args[0])?.log10()));
+            registry.register_native("log2", one, |args| Ok(logarithm_argument(args[0])?.log2()));
+            registry.register_native("log", Arity::Fixed(2), |args| logarithm(args[0], args[1]));
+
+            registry.register_native("gamma", one, |args| special::gamma(args[0]));
+            registry.register_native("lgamma", one, |args| special::lgamma(args[0]));
+            registry.register_native("beta", Arity::Fixed(2), |args| special::beta(args[0], args[1]));
+            registry
+        })
+    }
+
+    /// Creates a registry without any functions.
+    pub fn empty() -> FunctionRegistry {
+        FunctionRegistry { functions: Arc::new(HashMap::new()) }
+    }
+
+    /// Registers a Rust closure as a function, replacing any function with the same name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name the function is called by
+    /// * `arity` - The number of arguments the function accepts
+    /// * `function` - The closure computing the result from the evaluated arguments
+    pub fn register_native<F>(&mut self, name: &str, arity: Arity, function: F)
+    where
+        F: Fn(&[f64]) -> Result<f64, String> + Send + Sync + 'static,
+    {
+        self.register_angular(name, arity, AngleConversion::None, function);
+    }
+
+    /// Registers a Rust closure working with angles in radians as a function, replacing any
+    /// function with the same name.
+    ///
+    /// Depending on `angles`, the arguments or the result are converted between radians and
+    /// the [`AngleMode`] of the evaluation.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name the function is called by
+    /// * `arity` - The number of arguments the function accepts
+    /// * `angles` - Which values of the function are angles
+    /// * `function` - The closure computing the result from the evaluated arguments
+    ///
+    /// # Examples
+    /// ```
+    /// use math_lib::{evaluate_with, AngleConversion, AngleMode, Arity, Context};
+    ///
+    /// let mut context = Context::new();
+    /// context.set_angle_mode(AngleMode::Degrees);
+    /// context.functions_mut().register_angular("cot", Arity::Fixed(1), AngleConversion::Arguments, |args| {
+    ///     Ok(1. / args[0].tan())
+    /// });
+    /// assert!((evaluate_with("cot(45)", &context).unwrap() - 1.).abs() < 1e-12);
+    /// ```
+    pub fn register_angular<F>(&mut self, name: &str, arity: Arity, angles: AngleConversion, function: F)
+    where
+        F: Fn(&[f64]) -> Result<f64, String> + Send + Sync + 'static,
+    {
+        self.insert(name, arity, angles, Implementation::Native(Arc::new(function)));
+    }
+
+    /// Defines a function in the expression language, replacing any function with the
+    /// same name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name the function is called by
+    /// * `params` - The names of the parameters
+    /// * `body` - The expression computing the result from the parameters
+    pub fn define(&mut self, name: &str, params: Vec<String>, body: Expr) {
+        let arity = Arity::Fixed(params.len());
+        self.insert(name, arity, AngleConversion::None, Implementation::User(UserFunction { params, body }));
+    }
+
+    fn insert(&mut self, name: &str, arity: Arity, angles: AngleConversion, implementation: Implementation) {
+        let function = Function { name: name.to_string(), arity, angles, implementation };
+        Arc::make_mut(&mut self.functions).insert(name.to_string(), function);
+    }
+
+    /// Returns the function with the given name, or `None` if it is not registered.
+    pub fn get(&self, name: &str) -> Option<&Function> {
+        self.functions.get(name)
+    }
+
+    /// Removes a function and returns it, or `None` if it was not registered.
+    pub fn remove(&mut self, name: &str) -> Option<Function> {
+        if !self.functions.contains_key(name) {
+            return None;
+        }
+        Arc::make_mut(&mut self.functions).remove(name)
+    }
+
+    /// Returns an iterator over all registered functions in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &Function> {
+        self.functions.values()
+    }
+}
+
+/// Computes the sine of an angle in radians, exactly for multiples of a right angle.
+fn sin(angle: f64) -> f64 {
+    match right_angles(angle, FRAC_PI_2) {
+        Some(count) => [0., 1., 0., -1.][count.rem_euclid(4.) as usize],
+        None => angle.sin(),
+    }
+}
+
+/// Computes the cosine of an angle in radians, exactly for multiples of a right angle.
+fn cos(angle: f64) -> f64 {
+    match right_angles(angle, FRAC_PI_2) {
+        Some(count) => [1., 0., -1., 0.][count.rem_euclid(4.) as usize],
+        None => angle.cos(),
+    }
+}
+
+/// Computes the tangent of an angle in radians, exactly for multiples of a right angle.
+fn tan(angle: f64) -> Result<f64, String> {
+    match right_angles(angle, FRAC_PI_2) {
+        Some(count) if count.rem_euclid(2.) == 1. => {
+            Err("Cannot take tan of an odd multiple of a right angle".to_string())
+        }
+        Some(_) => Ok(0.),
+        None => Ok(angle.tan()),
+    }
+}
+
+/// Checks that `argument` is in the domain of logarithms and returns it.
+fn logarithm_argument(argument: f64) -> Result<f64, String> {
+    if argument <= 0. {
+        return Err("Cannot take the logarithm of a non-positive number".to_string());
+    }
+    Ok(argument)
+}
+
+/// Computes the logarithm of `argument` to `base`, exactly when `argument` is a whole
+/// power of `base`.
+fn logarithm(base: f64, argument: f64) -> Result<f64, String> {
+    if base <= 0. || base == 1. {
+        return Err("Cannot take the logarithm to a non-positive base or base 1".to_string());
+    }
+    let result = logarithm_argument(argument)?.ln() / base.ln();
+    let exponent = result.round();
+    if base.powf(exponent) == argument {
+        return Ok(exponent);
+    }
+    Ok(result)
+}
+
+/// Computes the `degree`-th root of `radicand`, as the `√` operator does.
+pub(crate) fn root(degree: f64, radicand: f64) -> Result<f64, String> {
+    if degree.abs() < f64::EPSILON {
+        return Err("Cannot take the 0th root".to_string());
+    }
+    if radicand < 0. {
+        return Err("Cannot take the root of a negative number".to_string());
+    }
+    Ok(radicand.powf(1. / degree))
+}
+
+#[cfg(test)]
+mod registry_tests {
+    use std::f64::consts::PI;
+    use std::sync::Arc;
+
+    use super::{AngleConversion, AngleMode, Arity, FunctionRegistry, Implementation};
+
+    fn call(registry: &FunctionRegistry, name: &str, args: &[f64]) -> Result<f64, String> {
+        match registry.get(name).map(|function| function.implementation()) {
+            Some(Implementation::Native(function)) => function(args),
+            _ => panic!("{name} is not a native function"),
+        }
+    }
+
+    #[test]
+    fn arity() {
+        assert!(Arity::Fixed(2).accepts(2));
+        assert!(!Arity::Fixed(2).accepts(3));
+        assert!(Arity::Variadic { min: 1 }.accepts(5));
+        assert!(!Arity::Variadic { min: 1 }.accepts(0));
+        assert_eq!("at least 1", Arity::Variadic { min: 1 }.to_string());
+    }
+
+    #[test]
+    fn builtins() {
+        let registry = FunctionRegistry::new();
+        assert_eq!(Ok(3.), call(&registry, "sqrt", &[9.]));
+        assert_eq!(Ok(2.), call(&registry, "root", &[3., 8.]));
+        assert_eq!(Ok(-4.), call(&registry, "min", &[3., -4., 8.]));
+        assert_eq!(Ok(5.), call(&registry, "avg", &[3., 7.]));
+        assert_eq!(Arity::Variadic { min: 1 }, registry.get("max").unwrap().arity());
+    }
+
+    #[test]
+    fn empty_registry() {
+        assert!(FunctionRegistry::empty().get("abs").is_none());
+        assert_eq!(0, FunctionRegistry::empty().iter().count());
+    }
+
+    #[test]
+    fn register_and_remove() {
+        let mut registry = FunctionRegistry::empty();
+        registry.register_native("double", Arity::Fixed(1), |args| Ok(2. * args[0]));
+        assert_eq!(Ok(8.), call(&registry, "double", &[4.]));
+        assert_eq!("double", registry.remove("double").unwrap().name());
+        assert!(registry.get("double").is_none());
+    }
+
+    #[test]
+    fn shared_builtins() {
+        let (first, mut second) = (FunctionRegistry::new(), FunctionRegistry::new());
+        assert!(Arc::ptr_eq(&first.functions, &second.functions));
+        second.register_native("abs", Arity::Fixed(1), |_| Ok(0.));
+        second.remove("sqrt");
+        assert!(!Arc::ptr_eq(&first.functions, &second.functions));
+        assert_eq!(Ok(2.), call(&FunctionRegistry::new(), "abs", &[-2.]));
+        assert!(FunctionRegistry::new().get("sqrt").is_some());
+        assert_eq!(Ok(0.), call(&second, "abs", &[-2.]));
+    }
+
+    #[test]
+    fn angle_conversion() {
+        assert_eq!(PI, AngleMode::Degrees.to_radians(180.));
+        assert_eq!(PI, AngleMode::Gradians.to_radians(200.));
+        assert_eq!(1., AngleMode::Radians.to_radians(1.));
+        assert_eq!(90., AngleMode::Degrees.from_radians(PI / 2.));
+        assert_eq!(-50., AngleMode::Gradians.from_radians(-PI / 4.));
+        assert!((AngleMode::Degrees.from_radians(1.) - 57.29577951308232).abs() < 1e-12);
+    }
+
+    #[test]
+    fn exact_right_angles() {
+        let registry = FunctionRegistry::new();
+        assert_eq!(Ok(0.), call(&registry, "sin", &[PI]));
+        assert_eq!(Ok(-1.), call(&registry, "cos", &[-PI]));
+        assert_eq!(Ok(0.), call(&registry, "tan", &[2. * PI]));
+        assert_eq!(AngleConversion::Result, registry.get("atan2").unwrap().angle_conversion());
+        assert_eq!(AngleConversion::None, registry.get("sinh").unwrap().angle_conversion());
+    }
+
+    #[test]
+    fn undefined_tangent() {
+        assert_eq!(
+            Err("Cannot take tan of an odd multiple of a right angle".to_string()),
+            call(&FunctionRegistry::new(), "tan", &[-PI / 2.])
+        );
+    }
+
+    #[test]
+    fn logarithms() {
+        let registry = FunctionRegistry::new();
+        assert_eq!(Ok(3.), call(&registry, "log", &[10., 1000.]));
+        assert_eq!(Ok(-2.), call(&registry, "log", &[3., 1. / 9.]));
+        assert_eq!(Ok(0.5), call(&registry, "log", &[4., 2.]));
+        assert_eq!(Ok(10.), call(&registry, "log2", &[1024.]));
+        assert_eq!(Ok(1.), call(&registry, "ln", &[std::f64::consts::E]));
+    }
+
+    #[test]
+    fn logarithm_outside_of_domain() {
+        let registry = FunctionRegistry::new();
+        assert_eq!(
+            Err("Cannot take the logarithm of a non-positive number".to_string()),
+            call(&registry, "ln", &[0.])
+        );
+        assert_eq!(
+            Err("Cannot take the logarithm of a non-positive number".to_string()),
+            call(&registry, "log10", &[-10.])
+        );
+        assert_eq!(
+            Err("Cannot take the logarithm to a non-positive base or base 1".to_string()),
+            call(&registry, "log", &[1., 5.])
+        );
+        assert!(call(&registry, "log", &[-2., 4.]).is_err());
+    }
+
+    #[test]
+    fn negative_square_root() {
+        assert_eq!(
+            Err("Cannot take the root of a negative number".to_string()),
+            call(&FunctionRegistry::new(), "sqrt", &[-1.])
+        );
+    }
+}
@@ -0,0 +1,561 @@
+//! # Description
+//! This module provides the evaluation of expressions with fixed-width integers, like the
+//! registers of a processor.
+
+use crate::ast::{call_native, called_function, evaluate_chain, Expr};
+use crate::context::Context;
+use crate::error::{ParseError, Span};
+use crate::functions::Implementation;
+use crate::parser::{parse_to_ast_with, Operator};
+
+/// Magnitude every operand must be below, 2^64.
+const LIMIT: f64 = 18_446_744_073_709_551_616.;
+
+/// Number of bits of the integers of an [`IntegerMode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntegerWidth {
+    Bits8,
+    Bits16,
+    Bits32,
+    #[default]
+    Bits64,
+}
+
+impl IntegerWidth {
+    /// Returns the number of bits.
+    pub fn bits(self) -> u32 {
+        match self {
+            IntegerWidth::Bits8 => 8,
+            IntegerWidth::Bits16 => 16,
+            IntegerWidth::Bits32 => 32,
+            IntegerWidth::Bits64 => 64,
+        }
+    }
+}
+
+/// What happens to a result that does not fit into the integers of an [`IntegerMode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// The bits that do not fit are dropped, so `127+1` is `-128` with 8 signed bits.
+    #[default]
+    Wrap,
+    /// The result is clamped to the nearest integer that fits, so `127+1` is `127` with
+    /// 8 signed bits.
+    Saturate,
+}
+
+/// Width, signedness and overflow behavior of the integers expressions are evaluated with,
+/// see [`Expr::evaluate_integer`].
+///
+/// The default are 64-bit signed integers that wrap, which is also how the bitwise
+/// operators treat their operands outside of integer mode.
+///
+/// # Examples
+/// ```
+/// use math_lib::{IntegerMode, IntegerWidth, Overflow};
+///
+/// let register = IntegerMode { width: IntegerWidth::Bits8, signed: false, overflow: Overflow::Wrap };
+/// assert_eq!((register.min(), register.max()), (0, 255));
+/// assert_eq!(IntegerMode::default().max(), i64::MAX as i128);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntegerMode {
+    /// Number of bits of every result.
+    pub width: IntegerWidth,
+    /// Whether the integers are in two's complement or unsigned.
+    pub signed: bool,
+    /// What happens to results that do not fit.
+    pub overflow: Overflow,
+}
+
+impl Default for IntegerMode {
+    fn default() -> IntegerMode {
+        IntegerMode { width: IntegerWidth::default(), signed: true, overflow: Overflow::default() }
+    }
+}
+
+impl IntegerMode {
+    /// Returns the smallest integer of the mode.
+    pub fn min(&self) -> i128 {
+        if self.signed {
+            -(1 << (self.width.bits() - 1))
+        } else {
+            0
+        }
+    }
+
+    /// Returns the largest integer of the mode.
+    pub fn max(&self) -> i128 {
+        if self.signed {
+            (1 << (self.width.bits() - 1)) - 1
+        } else {
+            (1 << self.width.bits()) - 1
+        }
+    }
+
+    /// Drops the bits of `value` that do not fit, keeping its bit pattern.
+    fn wrap(&self, value: i128) -> i128 {
+        let value = value.rem_euclid(1 << self.width.bits());
+        if value > self.max() {
+            value - (1 << self.width.bits())
+        } else {
+            value
+        }
+    }
+
+    /// Brings `value` into the range of the mode according to its overflow behavior.
+    fn fit(&self, value: i128) -> i128 {
+        match self.overflow {
+            Overflow::Wrap => self.wrap(value),
+            Overflow::Saturate => value.clamp(self.min(), self.max()),
+        }
+    }
+
+    /// Fits a result that is `exact` unless it does not fit into `i128`, in which case
+    /// `wrapping` is the result modulo 2^128 and `negative` its sign.
+    fn fit_overflowing(&self, exact: Option<i128>, wrapping: i128, negative: bool) -> i128 {
+        match (exact, self.overflow) {
+            (Some(value), _) => self.fit(value),
+            (None, Overflow::Wrap) => self.wrap(wrapping),
+            (None, Overflow::Saturate) if negative => self.min(),
+            (None, Overflow::Saturate) => self.max(),
+        }
+    }
+
+    /// Returns the number of bits `amount` shifts by, which must be less than the width.
+    fn shift_amount(&self, amount: i128, span: Span) -> Result<u32, ParseError> {
+        let bits = self.width.bits();
+        u32::try_from(amount).ok().filter(|amount| *amount < bits).ok_or_else(|| ParseError::DomainError {
+            message: format!("Cannot shift a {bits}-bit integer by {amount} bits"),
+            span,
+        })
+    }
+
+    /// Applies a unary operator to an already evaluated operand.
+    pub(crate) fn apply_unary(&self, op: Operator, operand: i128, span: Span) -> Result<i128, ParseError> {
+        match op {
+            Operator::Plus => Ok(self.fit(operand)),
+            Operator::Minus => Ok(self.fit(-operand)),
+            Operator::Percent => Ok(self.fit(operand / 100)),
+            Operator::BitNot => Ok(self.wrap(!self.wrap(operand))),
+            Operator::Root => Ok(self.fit(root(operand, 2, span)?)),
+            Operator::Factorial => self.factorial(operand, span),
+            _ => Err(ParseError::InvalidOperator { op, span }),
+        }
+    }
+
+    /// Applies a binary operator to already evaluated operands.
+    ///
+    /// Division rounds towards zero. Shifts drop the bits shifted out whatever the overflow
+    /// behavior, and `>>` shifts in the sign bit of signed integers.
+    pub(crate) fn apply_binary(&self, op: Operator, left: i128, right: i128, span: Span) -> Result<i128, ParseError> {
+        let negative = (left < 0) != (right < 0);
+        match op {
+            Operator::Plus => Ok(self.fit(left + right)),
+            Operator::Minus => Ok(self.fit(left - right)),
+            Operator::Multiply => Ok(self.fit_overflowing(left.checked_mul(right), left.wrapping_mul(right), negative)),
+            Operator::Divide => {
+                if right == 0 {
+                    return Err(ParseError::DivisionByZero { span });
+                }
+                Ok(self.fit(left / right))
+            }
+            Operator::Percent => {
+                if right == 0 {
+                    return Err(ParseError::DomainError {
+                        message: "Cannot take the percentage of zero".to_string(),
+                        span,
+                    });
+                }
+                Ok(self.fit(left * 100 / right))
+            }
+            Operator::Modulo => {
+                if right == 0 {
+                    return Err(ParseError::DivisionByZero { span });
+                }
+                // The remainder takes the sign of the divisor
+                let remainder = left % right;
+                if remainder != 0 && (remainder < 0) != (right < 0) {
+                    Ok(self.fit(remainder + right))
+                } else {
+                    Ok(self.fit(remainder))
+                }
+            }
+            Operator::Power => self.power(left, right, span),
+            Operator::Root => {
+                if left <= 0 {
+                    return Err(ParseError::DomainError { message: format!("Cannot take the {left}th root"), span });
+                }
+                // Degrees beyond u32 give the same root, only their parity matters
+                let degree = u32::try_from(left).unwrap_or(u32::MAX - (left % 2 == 0) as u32);
+                Ok(self.fit(root(right, degree, span)?))
+            }
+            Operator::BitAnd => Ok(self.wrap(self.wrap(left) & self.wrap(right))),
+            Operator::BitOr => Ok(self.wrap(self.wrap(left) | self.wrap(right))),
+            Operator::BitXor => Ok(self.wrap(self.wrap(left) ^ self.wrap(right))),
+            Operator::ShiftLeft => Ok(self.wrap(self.wrap(left) << self.shift_amount(right, span)?)),
+            Operator::ShiftRight => Ok(self.wrap(self.wrap(left) >> self.shift_amount(right, span)?)),
+            _ => Err(ParseError::InvalidOperator { op, span }),
+        }
+    }
+
+    fn power(&self, base: i128, exponent: i128, span: Span) -> Result<i128, ParseError> {
+        if exponent < 0 {
+            return Err(ParseError::DomainError {
+                message: "Cannot raise an integer to a negative power".to_string(),
+                span,
+            });
+        }
+        let exact = u32::try_from(exponent).ok().and_then(|exponent| base.checked_pow(exponent));
+        let mut wrapping: i128 = 1;
+        if exact.is_none() {
+            let (mut base, mut exponent) = (base, exponent);
+            while exponent > 0 {
+                if exponent & 1 == 1 {
+                    wrapping = wrapping.wrapping_mul(base);
+                }
+                base = base.wrapping_mul(base);
+                exponent >>= 1;
+            }
+        }
+        Ok(self.fit_overflowing(exact, wrapping, base < 0 && exponent % 2 == 1))
+    }
+
+    fn factorial(&self, operand: i128, span: Span) -> Result<i128, ParseError> {
+        if operand < 0 {
+            return Err(ParseError::DomainError {
+                message: "Cannot take factorial of a negative number".to_string(),
+                span,
+            });
+        }
+        let mut exact = Some(1i128);
+        let mut wrapping: i128 = 1;
+        for factor in 2..=operand {
+            exact = exact.and_then(|exact| exact.checked_mul(factor));
+            wrapping = wrapping.wrapping_mul(factor);
+            // Once the product is too large, wrapping only needs the bits that are left and
+            // saturating is done
+            if exact.is_none() && (wrapping == 0 || self.overflow == Overflow::Saturate) {
+                break;
+            }
+        }
+        Ok(self.fit_overflowing(exact, wrapping, false))
+    }
+}
+
+/// Returns the `degree`th root of `radicand` rounded towards zero.
+fn root(radicand: i128, degree: u32, span: Span) -> Result<i128, ParseError> {
+    if radicand < 0 && degree.is_multiple_of(2) {
+        return Err(ParseError::DomainError {
+            message: "Cannot take an even root of a negative number".to_string(),
+            span,
+        });
+    }
+    let magnitude = radicand.abs();
+    let fits = |root: i128| root.checked_pow(degree).is_some_and(|power| power <= magnitude);
+    // Correct the floating point estimate, which may be off by one
+    let mut root = (magnitude as f64).powf(1. / f64::from(degree)).round() as i128;
+    while root > 0 && !fits(root) {
+        root -= 1;
+    }
+    while fits(root + 1) {
+        root += 1;
+    }
+    Ok(root * radicand.signum())
+}
+
+/// Returns whether `op` works on the bits of integers.
+pub(crate) fn is_bitwise(op: Operator) -> bool {
+    matches!(
+        op,
+        Operator::BitAnd | Operator::BitOr | Operator::BitXor | Operator::BitNot | Operator::ShiftLeft | Operator::ShiftRight
+    )
+}
+
+/// Converts `value` to an integer, which it must be, with a magnitude below 2^64.
+pub(crate) fn integer_of(value: f64, span: Span) -> Result<i128, ParseError> {
+    if value.fract() != 0. || value.is_nan() {
+        return Err(ParseError::DomainError { message: format!("{value} is not a whole number"), span });
+    }
+    if value.abs() >= LIMIT {
+        return Err(ParseError::DomainError { message: format!("{value} does not fit into 64 bits"), span });
+    }
+    Ok(value as i128)
+}
+
+/// Parses and evaluates `input` with the integers of `mode`.
+///
+/// The input is parsed with the parser options of `context`, see
+/// [`Expr::evaluate_integer`] for how it is evaluated.
+///
+/// # Arguments
+///
+/// * `input` - A string slice containing the expression to evaluate
+/// * `context` - The context providing variables, functions and parser options
+/// * `mode` - The width, signedness and overflow behavior of the integers
+///
+/// # Returns
+/// A Result containing the value of the expression or a ParseError if the expression
+/// cannot be parsed or evaluated.
+///
+/// # Examples
+/// ```
+/// use math_lib::{evaluate_integer, Context, IntegerMode, IntegerWidth, Overflow};
+///
+/// let byte = IntegerMode { width: IntegerWidth::Bits8, signed: false, overflow: Overflow::Wrap };
+/// assert_eq!(evaluate_integer("0xF0 | 0x0F", &Context::new(), &byte), Ok(255));
+/// assert_eq!(evaluate_integer("0xFF + 1", &Context::new(), &byte), Ok(0));
+/// assert_eq!(evaluate_integer("~0x0F", &Context::new(), &byte), Ok(0xF0));
+/// ```
+pub fn evaluate_integer(input: &str, context: &Context, mode: &IntegerMode) -> Result<i128, ParseError> {
+    parse_to_ast_with(input, context.parser_options())?.evaluate_integer(context, mode)
+}
+
+impl Expr {
+    /// Evaluates the expression with the integers of `mode`, looking variables up in
+    /// `context`.
+    ///
+    /// Numbers and variables must be whole and are taken as written, while the result of
+    /// every operator and of the whole expression is brought into the range of `mode`. So
+    /// `-128` is the smallest 8-bit signed integer in either overflow behavior, and `0xFF`
+    /// is `-1` in it unless it saturates. Division rounds towards zero, roots round down
+    /// and the bitwise operators work on the two's complement of the operands.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - The context providing the values of variables and functions
+    /// * `mode` - The width, signedness and overflow behavior of the integers
+    ///
+    /// # Returns
+    /// A Result containing the value of the expression or a ParseError if an error occurs
+    /// during evaluation.
+    pub fn evaluate_integer(&self, context: &Context, mode: &IntegerMode) -> Result<i128, ParseError> {
+        let value = IntegerScope { context, mode, locals: &[], depth: 0 }.evaluate(self)?;
+        Ok(mode.fit(value))
+    }
+}
+
+/// Variables visible while evaluating an expression with integers.
+struct IntegerScope<'a> {
+    context: &'a Context,
+    mode: &'a IntegerMode,
+    // Parameters of the function being called, empty outside of calls
+    locals: &'a [(&'a str, i128)],
+    // Number of function calls being evaluated
+    depth: usize,
+}
+
+impl IntegerScope<'_> {
+    fn evaluate(&self, expr: &Expr) -> Result<i128, ParseError> {
+        match expr {
+            // The literal keeps the digits of integers an f64 cannot hold
+            Expr::Number { value, literal, span } => {
+                match literal.as_deref().and_then(|literal| literal.parse::<i128>().ok()) {
+                    Some(integer) if integer.unsigned_abs() < 1 << 64 => Ok(integer),
+                    _ => integer_of(*value, *span),
+                }
+            }
+            Expr::Variable { name, span } => {
+                if let Some((_, value)) = self.locals.iter().find(|(local, _)| local == name) {
+                    return Ok(*value);
+                }
+                let value = self.context.value(name).ok_or_else(|| ParseError::UnknownVariable {
+                    name: name.clone(),
+                    span: *span,
+                })?;
+                integer_of(value, *span)
+            }
+            Expr::Constant { constant, span } => integer_of(constant.value(), *span),
+            Expr::Unary { op, operand, span } => self.mode.apply_unary(*op, self.evaluate(operand)?, *span),
+            Expr::Binary { .. } => evaluate_chain(
+                expr,
+                |operand| self.evaluate(operand),
+                |left, op, right, add_on, span| {
+                    let right = match right {
+                        // The percentage is divided by a hundred after multiplying, so that
+                        // percentages below 100 are not truncated to zero. Splitting `left`
+                        // keeps the product of two 64-bit operands within i128.
+                        Expr::Unary { op: Operator::Percent, operand, .. } if add_on => {
+                            let percent = self.evaluate(operand)?;
+                            self.mode.fit(left / 100 * percent + left % 100 * percent / 100)
+                        }
+                        right => self.evaluate(right)?,
+                    };
+                    self.mode.apply_binary(op, left, right, span)
+                },
+            ),
+            Expr::Call { name, args, span } => self.call(name, args, *span),
+        }
+    }
+
+    fn call(&self, name: &str, args: &[Expr], span: Span) -> Result<i128, ParseError> {
+        let function = called_function(self.context, name, args.len(), span)?;
+        match function.implementation() {
+            Implementation::Native(native) => {
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    values.push(self.evaluate(arg)? as f64);
+                }
+                let value = call_native(function, native.as_ref(), values, self.context, span)?;
+                Ok(self.mode.fit(integer_of(value, span)?))
+            }
+            Implementation::User(function) => {
+                if self.depth >= self.context.max_call_depth() {
+                    return Err(ParseError::RecursionLimit { name: name.to_string(), span });
+                }
+                let mut locals = Vec::with_capacity(args.len());
+                for (param, arg) in function.params.iter().zip(args) {
+                    locals.push((param.as_str(), self.evaluate(arg)?));
+                }
+                // The body only sees its own parameters, not those of the caller
+                let scope = IntegerScope { context: self.context, mode: self.mode, locals: &locals, depth: self.depth + 1 };
+                Ok(self.mode.fit(scope.evaluate(&function.body)?))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod integer_tests {
+    use super::{evaluate_integer, IntegerMode, IntegerWidth, Overflow};
+    use crate::{parse, parse_to_ast, Context, ParseError, ParserOptions, PercentMode, Span};
+
+    fn mode(bits: u32, signed: bool, overflow: Overflow) -> IntegerMode {
+        let width = match bits {
+            8 => IntegerWidth::Bits8,
+            16 => IntegerWidth::Bits16,
+            32 => IntegerWidth::Bits32,
+            _ => IntegerWidth::Bits64,
+        };
+        IntegerMode { width, signed, overflow }
+    }
+
+    fn integer(input: &str, mode: IntegerMode) -> Result<i128, ParseError> {
+        evaluate_integer(input, &Context::new(), &mode)
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        assert_eq!(Ok(0x0F as f64), parse("0xFF & 0x0F"));
+        assert_eq!(Ok(0xFF as f64), parse("0xF0 | 0x0F"));
+        assert_eq!(Ok(0xF0 as f64), parse("0xFF xor 0x0F"));
+        assert_eq!(Ok(-1.), parse("~0"));
+        assert_eq!(Ok(1024.), parse("1 << 10"));
+        assert_eq!(Ok(-4.), parse("-16 >> 2"));
+        assert_eq!(Ok(i64::MIN as f64), parse("1 << 63"));
+    }
+
+    #[test]
+    fn modulo() {
+        let i64_mode = IntegerMode::default();
+        assert_eq!(Ok(2), integer("17 mod 5", i64_mode));
+        assert_eq!(Ok(2), integer("-7 mod 3", i64_mode));
+        assert_eq!(Ok(-2), integer("7 mod -3", i64_mode));
+        assert_eq!(Err(ParseError::DivisionByZero { span: Span::new(2, 5) }), integer("1 mod 0", i64_mode));
+    }
+
+    #[test]
+    fn bitwise_precedence() {
+        assert_eq!("((1+1)<<4)|1", parse_to_ast("1+1 << 4 | 1").unwrap().to_string());
+        assert_eq!("((1&2) xor 3)|4", parse_to_ast("1&2 xor 3|4").unwrap().to_string());
+        assert_eq!(Ok(33.), parse("1+1 << 4 | 1"));
+        assert_eq!(Ok(7.), parse("4 | 2 xor 1 & 3"));
+        assert_eq!(Ok(-2.), parse("~1"));
+        assert_eq!(Ok(0.), parse("~-1"));
+    }
+
+    #[test]
+    fn wrapping() {
+        assert_eq!(Ok(-128), integer("127+1", mode(8, true, Overflow::Wrap)));
+        assert_eq!(Ok(0), integer("0xFF+1", mode(8, false, Overflow::Wrap)));
+        assert_eq!(Ok(255), integer("0-1", mode(8, false, Overflow::Wrap)));
+        assert_eq!(Ok(-1), integer("0xFFFF", mode(16, true, Overflow::Wrap)));
+        assert_eq!(Ok(-128), integer("-128", mode(8, true, Overflow::Wrap)));
+        assert_eq!(Ok(-128), integer("-128/-1", mode(8, true, Overflow::Wrap)));
+        assert_eq!(Ok(0), integer("2^32", mode(32, false, Overflow::Wrap)));
+        assert_eq!(Ok(0), integer("100!", mode(64, true, Overflow::Wrap)));
+        assert_eq!(Ok(u64::MAX as i128), integer("0xFFFFFFFFFFFFFFFF * 0xFFFFFFFFFFFFFFFF - 2", mode(64, false, Overflow::Wrap)));
+    }
+
+    #[test]
+    fn saturating() {
+        assert_eq!(Ok(127), integer("127+1", mode(8, true, Overflow::Saturate)));
+        assert_eq!(Ok(-128), integer("-128", mode(8, true, Overflow::Saturate)));
+        assert_eq!(Ok(-128), integer("-100-100", mode(8, true, Overflow::Saturate)));
+        assert_eq!(Ok(0), integer("3-5", mode(16, false, Overflow::Saturate)));
+        assert_eq!(Ok(u32::MAX as i128), integer("2^40", mode(32, false, Overflow::Saturate)));
+        assert_eq!(Ok(i64::MIN as i128), integer("(-2)^99", mode(64, true, Overflow::Saturate)));
+        assert_eq!(Ok(i64::MAX as i128), integer("30!", mode(64, true, Overflow::Saturate)));
+        // Shifts drop the bits shifted out even when saturating
+        assert_eq!(Ok(0xFE), integer("0xFF << 1", mode(8, false, Overflow::Saturate)));
+    }
+
+    #[test]
+    fn registers() {
+        let byte = mode(8, false, Overflow::Wrap);
+        assert_eq!(Ok(0xF0), integer("~0x0F", byte));
+        assert_eq!(Ok(0x80), integer("1 << 7", byte));
+        assert_eq!(Ok(0x40), integer("0x80 >> 1", byte));
+        let signed_byte = mode(8, true, Overflow::Wrap);
+        assert_eq!(Ok(-64), integer("0x80 >> 1", signed_byte));
+        assert_eq!(Ok(-128), integer("1 << 7", signed_byte));
+        assert_eq!(Ok(0x04), integer("0x1234 & 0x0F0F >> 8", mode(16, false, Overflow::Wrap)));
+    }
+
+    #[test]
+    fn integer_arithmetic() {
+        let mode = IntegerMode::default();
+        assert_eq!(Ok(3), integer("7/2", mode));
+        assert_eq!(Ok(-3), integer("-7/2", mode));
+        assert_eq!(Ok(3), integer("√15", mode));
+        assert_eq!(Ok(4), integer("√16", mode));
+        assert_eq!(Ok(-2), integer("3√(-8)", mode));
+        assert_eq!(Ok(10), integer("3√1030", mode));
+        assert_eq!(Ok(120), integer("5!", mode));
+        assert_eq!(Ok(25), integer("1 % 4", mode));
+        assert_eq!(Ok(7), integer("max(3, 7)", mode));
+        let mut context = Context::new();
+        context.set_variable("mask", 0xF0 as f64);
+        context.define_function("low", vec!["x".to_string()], parse_to_ast("x & ~mask").unwrap());
+        assert_eq!(Ok(0x0A), evaluate_integer("low(0xFA)", &context, &mode));
+    }
+
+    #[test]
+    fn add_on_percentages() {
+        let i64_mode = IntegerMode::default();
+        let mut context = Context::new();
+        context.set_parser_options(ParserOptions { percent: PercentMode::AddOn, ..ParserOptions::default() });
+        assert_eq!(Ok(220), evaluate_integer("200+10%", &context, &i64_mode));
+        assert_eq!(Ok(100), evaluate_integer("200-50%", &context, &i64_mode));
+        assert_eq!(Ok(198), evaluate_integer("200-10%+10%", &context, &i64_mode));
+        assert_eq!(Ok(-9), evaluate_integer("-7+33%", &context, &i64_mode));
+        assert_eq!(Ok(255), evaluate_integer("200+10%+20%", &context, &mode(8, false, Overflow::Saturate)));
+        let unsigned = mode(64, false, Overflow::Wrap);
+        assert_eq!(Ok(u64::MAX as i128 / 2 + 1), evaluate_integer("0xFFFFFFFFFFFFFFFF-50%", &context, &unsigned));
+    }
+
+    // These are invalid operations
+    #[test]
+    fn invalid_operations() {
+        let mode = IntegerMode::default();
+        assert_eq!(Err(ParseError::DivisionByZero { span: Span::new(1, 2) }), integer("1/0", mode));
+        assert!(matches!(integer("1.5", mode), Err(ParseError::DomainError { .. })));
+        assert!(matches!(integer("π", mode), Err(ParseError::DomainError { .. })));
+        assert!(matches!(integer("2^-1", mode), Err(ParseError::DomainError { .. })));
+        assert!(matches!(integer("√-4", mode), Err(ParseError::DomainError { .. })));
+        assert!(matches!(integer("1 << 64", mode), Err(ParseError::DomainError { .. })));
+        assert!(matches!(integer("1 << 8", super::IntegerMode { width: IntegerWidth::Bits8, ..mode }), Err(ParseError::DomainError { .. })));
+        assert!(matches!(integer("sqrt(2)", mode), Err(ParseError::DomainError { .. })));
+        assert!(matches!(parse("1.5 & 1"), Err(ParseError::DomainError { .. })));
+        assert!(matches!(integer("18446744073709551616", mode), Err(ParseError::DomainError { .. })));
+    }
+
+    #[test]
+    fn operands_below_two_to_the_64() {
+        assert_eq!(Ok(u64::MAX as i128), integer("0xFFFFFFFFFFFFFFFF", mode(64, false, Overflow::Wrap)));
+        assert!(matches!(parse("2^64 | 0"), Err(ParseError::DomainError { .. })));
+        assert!(matches!(parse("-(2^64) & 1"), Err(ParseError::DomainError { .. })));
+        // The literal is rounded up to 2^64 as an f64
+        assert!(matches!(parse("0xFFFFFFFFFFFFFFFF | 0"), Err(ParseError::DomainError { .. })));
+    }
+}
@@ -1,2 +1,45 @@
+mod ast;
+#[cfg(feature = "complex")]
+mod complex;
+mod constants;
+mod context;
+#[cfg(feature = "decimal")]
+mod decimal;
+mod error;
+mod functions;
+mod integer;
+mod operators;
+mod options;
 mod parser;
-pub use parser::parse;
+mod radix;
+#[cfg(feature = "rational")]
+mod rational;
+mod special;
+#[cfg(feature = "units")]
+mod units;
+pub use ast::{Expr, Statement};
+#[cfg(feature = "complex")]
+pub use complex::{evaluate_complex, format_complex, ComplexFormat};
+pub use constants::{Constant, NamedConstant};
+pub use context::Context;
+#[cfg(feature = "decimal")]
+pub use decimal::{evaluate_decimal, DecimalOptions};
+pub use error::{ParseError, Span};
+pub use functions::{
+    AngleConversion, AngleMode, Arity, Function, FunctionRegistry, Implementation, NativeFunction, UserFunction,
+};
+pub use integer::{evaluate_integer, IntegerMode, IntegerWidth, Overflow};
+pub use options::{ImplicitMultiplication, NumberFormat, ParserOptions, PercentMode};
+pub use parser::{evaluate_script, evaluate_with, parse, parse_script, parse_to_ast, parse_to_ast_with, Operator};
+pub use radix::{format_radix, RadixFormat};
+#[cfg(feature = "rational")]
+pub use rational::{evaluate_rational, RationalValue};
+#[cfg(feature = "units")]
+pub use units::{evaluate_quantity, Dimension, Quantity};
+
+#[cfg(feature = "decimal")]
+pub use bigdecimal::{BigDecimal, RoundingMode};
+#[cfg(feature = "complex")]
+pub use num_complex::Complex64;
+#[cfg(feature = "rational")]
+pub use num_rational::BigRational;
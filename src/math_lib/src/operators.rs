@@ -0,0 +1,228 @@
+//! # Description
+//! This module provides the registry of operators understood by the parser.
+
+use std::sync::OnceLock;
+
+use crate::options::PercentMode;
+use crate::parser::Operator;
+
+/// Binding power of implicit multiplication in [`ImplicitMultiplication::Tight`] mode,
+/// between `*` and prefix `-`.
+///
+/// [`ImplicitMultiplication::Tight`]: crate::ImplicitMultiplication::Tight
+pub(crate) const TIGHT_IMPLICIT_MULTIPLICATION: u8 = 25;
+
+/// How a chain of operators with the same binding power groups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Associativity {
+    /// `a-b-c` is `(a-b)-c`.
+    Left,
+    /// `a^b^c` is `a^(b^c)`.
+    Right,
+}
+
+/// Position of an operator relative to its operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Fixity {
+    /// Written before its only operand, like `-x`.
+    Prefix,
+    /// Written between its two operands, like `x+y`.
+    Infix,
+    /// Written after its only operand, like `x!`.
+    Postfix,
+}
+
+/// An operator together with the rules the parser applies to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct OperatorEntry {
+    pub(crate) symbol: &'static str,
+    pub(crate) operator: Operator,
+    pub(crate) fixity: Fixity,
+    pub(crate) binding_power: u8,
+    pub(crate) associativity: Associativity,
+}
+
+impl OperatorEntry {
+    /// Returns the binding power the operand on the right of the operator is parsed with.
+    ///
+    /// Operators that bind less tightly than this end the operand. A left-associative
+    /// operator therefore stops at the next operator with the same binding power.
+    pub(crate) fn right_binding_power(&self) -> u8 {
+        match self.associativity {
+            Associativity::Left => self.binding_power + 1,
+            Associativity::Right => self.binding_power,
+        }
+    }
+}
+
+/// Registry of the prefix, infix and postfix operators understood by the parser.
+///
+/// Every entry maps a symbol to an [`Operator`] and sets how tightly it binds and how it
+/// groups. The same symbol may be registered with different fixities, like `-` in `-x`
+/// and `x-y`; the parser tells them apart by whether an operand precedes the symbol.
+///
+/// The binding powers of [`OperatorRegistry::standard`] are ten times the levels
+/// documented on [`Operator`], which leaves room for operators between them. The operators
+/// binding looser than level 1 use the powers from 5 to 9.
+#[derive(Debug, Clone)]
+pub(crate) struct OperatorRegistry {
+    entries: Vec<OperatorEntry>,
+}
+
+impl OperatorRegistry {
+    /// Creates a registry without any operators.
+    pub(crate) fn empty() -> OperatorRegistry {
+        OperatorRegistry { entries: Vec::new() }
+    }
+
+    /// Returns the registry of the operators documented on [`Operator`].
+    ///
+    /// The registry is built once and shared by every parse.
+    pub(crate) fn standard() -> &'static OperatorRegistry {
+        static STANDARD: OnceLock<OperatorRegistry> = OnceLock::new();
+        STANDARD.get_or_init(|| {
+            let mut registry = OperatorRegistry::empty();
+            registry.register(Fixity::Infix, "+", Operator::Plus, 10, Associativity::Left);
+            registry.register(Fixity::Infix, "-", Operator::Minus, 10, Associativity::Left);
+            registry.register(Fixity::Infix, "*", Operator::Multiply, 20, Associativity::Left);
+            registry.register(Fixity::Infix, "/", Operator::Divide, 20, Associativity::Left);
+            registry.register(Fixity::Infix, "%", Operator::Percent, 20, Associativity::Left);
+            registry.register(Fixity::Infix, "mod", Operator::Modulo, 20, Associativity::Left);
+            registry.register(Fixity::Prefix, "+", Operator::Plus, 30, Associativity::Right);
+            registry.register(Fixity::Prefix, "-", Operator::Minus, 30, Associativity::Right);
+            registry.register(Fixity::Prefix, "~", Operator::BitNot, 30, Associativity::Right);
+            registry.register(Fixity::Infix, "^", Operator::Power, 40, Associativity::Right);
+            registry.register(Fixity::Infix, "√", Operator::Root, 40, Associativity::Right);
+            registry.register(Fixity::Prefix, "√", Operator::Root, 40, Associativity::Right);
+            registry.register(Fixity::Postfix, "!", Operator::Factorial, 50, Associativity::Left);
+            registry.register(Fixity::Infix, "|", Operator::BitOr, 6, Associativity::Left);
+            registry.register(Fixity::Infix, "xor", Operator::BitXor, 7, Associativity::Left);
+            registry.register(Fixity::Infix, "&", Operator::BitAnd, 8, Associativity::Left);
+            registry.register(Fixity::Infix, "<<", Operator::ShiftLeft, 9, Associativity::Left);
+            registry.register(Fixity::Infix, ">>", Operator::ShiftRight, 9, Associativity::Left);
+            #[cfg(feature = "units")]
+            registry.register(Fixity::Infix, "to", Operator::Convert, 5, Associativity::Left);
+            registry
+        })
+    }
+
+    /// Returns the registry for expressions parsed with `percent`, which is the standard
+    /// registry with `%` as a postfix operator on level 5 unless `percent` is the ratio.
+    pub(crate) fn with_percent(percent: PercentMode) -> &'static OperatorRegistry {
+        static POSTFIX_PERCENT: OnceLock<OperatorRegistry> = OnceLock::new();
+        match percent {
+            PercentMode::Ratio => OperatorRegistry::standard(),
+            PercentMode::Postfix | PercentMode::AddOn => POSTFIX_PERCENT.get_or_init(|| {
+                let mut registry = OperatorRegistry::standard().clone();
+                registry.unregister(Fixity::Infix, Operator::Percent);
+                registry.register(Fixity::Postfix, "%", Operator::Percent, 50, Associativity::Left);
+                registry
+            }),
+        }
+    }
+
+    /// Registers an operator, replacing any operator with the same symbol and fixity.
+    ///
+    /// # Arguments
+    /// * `fixity` - Position of the operator relative to its operands
+    /// * `symbol` - Text the operator is written with
+    /// * `operator` - Operator the symbol is read as
+    /// * `binding_power` - How tightly the operator binds, higher binds tighter
+    /// * `associativity` - How a chain of operators with the same binding power groups
+    pub(crate) fn register(
+        &mut self,
+        fixity: Fixity,
+        symbol: &'static str,
+        operator: Operator,
+        binding_power: u8,
+        associativity: Associativity,
+    ) {
+        self.entries.retain(|entry| entry.fixity != fixity || entry.symbol != symbol);
+        self.entries.push(OperatorEntry { symbol, operator, fixity, binding_power, associativity });
+    }
+
+    /// Removes `operator` with the given fixity, if it is registered.
+    pub(crate) fn unregister(&mut self, fixity: Fixity, operator: Operator) {
+        self.entries.retain(|entry| entry.fixity != fixity || entry.operator != operator);
+    }
+
+    /// Returns the entry of `operator` with the given fixity, or `None` if it is not
+    /// registered with that fixity.
+    pub(crate) fn get(&self, fixity: Fixity, operator: Operator) -> Option<&OperatorEntry> {
+        self.entries.iter().find(|entry| entry.fixity == fixity && entry.operator == operator)
+    }
+
+    /// Returns the operator whose symbol starts `input`, preferring the longest symbol,
+    /// together with the length of the symbol in bytes.
+    ///
+    /// A symbol ending in a letter, like `to`, must end a word, so it does not match the
+    /// start of `total`.
+    pub(crate) fn symbol_at(&self, input: &str) -> Option<(Operator, usize)> {
+        let ends_word = |symbol: &str| {
+            let continues = |c: char| c.is_alphanumeric() || c == '_';
+            !symbol.ends_with(continues) || !input[symbol.len()..].starts_with(continues)
+        };
+        self.entries
+            .iter()
+            .filter(|entry| input.starts_with(entry.symbol) && ends_word(entry.symbol))
+            .max_by_key(|entry| entry.symbol.len())
+            .map(|entry| (entry.operator, entry.symbol.len()))
+    }
+}
+
+#[cfg(test)]
+mod operator_tests {
+    use super::{Associativity, Fixity, OperatorRegistry, PercentMode};
+    use crate::parser::Operator;
+
+    #[test]
+    fn fixities_share_symbols() {
+        let registry = OperatorRegistry::standard();
+        assert_eq!(10, registry.get(Fixity::Infix, Operator::Minus).unwrap().binding_power);
+        assert_eq!(30, registry.get(Fixity::Prefix, Operator::Minus).unwrap().binding_power);
+        assert!(registry.get(Fixity::Postfix, Operator::Minus).is_none());
+    }
+
+    #[test]
+    fn right_binding_power() {
+        let registry = OperatorRegistry::standard();
+        assert_eq!(11, registry.get(Fixity::Infix, Operator::Plus).unwrap().right_binding_power());
+        assert_eq!(40, registry.get(Fixity::Infix, Operator::Power).unwrap().right_binding_power());
+    }
+
+    #[test]
+    fn longest_symbol_wins() {
+        let mut registry = OperatorRegistry::empty();
+        registry.register(Fixity::Infix, "*", Operator::Multiply, 20, Associativity::Left);
+        registry.register(Fixity::Infix, "**", Operator::Power, 40, Associativity::Right);
+        assert_eq!(Some((Operator::Power, 2)), registry.symbol_at("**2"));
+        assert_eq!(Some((Operator::Multiply, 1)), registry.symbol_at("*2"));
+        assert_eq!(None, registry.symbol_at("2"));
+    }
+
+    #[test]
+    fn word_symbols_end_words() {
+        let mut registry = OperatorRegistry::empty();
+        registry.register(Fixity::Infix, "mod", Operator::Percent, 20, Associativity::Left);
+        assert_eq!(Some((Operator::Percent, 3)), registry.symbol_at("mod 3"));
+        assert_eq!(Some((Operator::Percent, 3)), registry.symbol_at("mod(3)"));
+        assert_eq!(None, registry.symbol_at("modulus"));
+        assert_eq!(None, registry.symbol_at("mod_2"));
+    }
+
+    #[test]
+    fn postfix_percent() {
+        let registry = OperatorRegistry::with_percent(PercentMode::Postfix);
+        assert_eq!(50, registry.get(Fixity::Postfix, Operator::Percent).unwrap().binding_power);
+        assert!(registry.get(Fixity::Infix, Operator::Percent).is_none());
+        assert!(OperatorRegistry::standard().get(Fixity::Postfix, Operator::Percent).is_none());
+    }
+
+    #[test]
+    fn register_replaces_same_fixity() {
+        let mut registry = OperatorRegistry::empty();
+        registry.register(Fixity::Infix, "^", Operator::Power, 40, Associativity::Left);
+        registry.register(Fixity::Infix, "^", Operator::Power, 40, Associativity::Right);
+        assert_eq!(Associativity::Right, registry.get(Fixity::Infix, Operator::Power).unwrap().associativity);
+    }
+}
@@ -0,0 +1,157 @@
+//! # Description
+//! This module provides the options that change how expressions are parsed.
+
+/// How two operands written next to each other, like `2x` or `(1+2)(3+4)`, are read.
+///
+/// An operand starting with a name, a constant, a call or `(` can follow another operand
+/// without an operator in between, and the two are multiplied. An operand starting with a
+/// number cannot, so `2 3` stays an error instead of being read as `6`. A name directly
+/// followed by `(` is always a call, so `f(2)` calls `f` while `2(3)` multiplies.
+///
+/// The modes differ in how tightly the implicit multiplication binds, which decides the
+/// meaning of `1/2x`.
+///
+/// # Examples
+/// ```
+/// use math_lib::{parse_to_ast_with, ImplicitMultiplication, ParserOptions};
+///
+/// let tight = ParserOptions { implicit_multiplication: ImplicitMultiplication::Tight, ..ParserOptions::default() };
+/// assert_eq!(parse_to_ast_with("1/2π", &ParserOptions::default()).unwrap().to_string(), "(1/2)*π");
+/// assert_eq!(parse_to_ast_with("1/2π", &tight).unwrap().to_string(), "1/(2*π)");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImplicitMultiplication {
+    /// Operands must be separated by an operator, `2x` is an error.
+    Disabled,
+    /// Implicit multiplication binds like `*`, so `1/2x` is `(1/2)*x`.
+    #[default]
+    SamePrecedence,
+    /// Implicit multiplication binds tighter than `*`, `/` and `%` but looser than prefix
+    /// `-` and `^`, so `1/2x` is `1/(2*x)` and `2x^2` is `2*(x^2)`.
+    Tight,
+}
+
+/// Separators a number literal is written with, for reading numbers in a given locale.
+///
+/// The integer part may be split into groups of three digits by one of the grouping
+/// separators, like `1,000,000` or `1 000 000`. A number using grouping separators must
+/// group every digit before the decimal separator, use a single kind of grouping separator
+/// and have no grouping separator after the decimal separator. A grouping separator only
+/// belongs to a number if a digit follows it, so `1 + 2` is still a sum.
+///
+/// Input that could be meant in another locale is rejected with
+/// [`ParseError::AmbiguousNumber`](crate::ParseError::AmbiguousNumber) instead of being
+/// misread. This covers `1,5` in [`NumberFormat::ENGLISH`], which might be meant as one and
+/// a half, and a `.` or `,` that is neither the decimal nor a grouping separator.
+///
+/// Inside a function call, `,` separates the arguments. If `,` is a grouping separator and
+/// followed by exactly three digits, like in `max(1,000, 2)`, the input is rejected as
+/// ambiguous instead, and is written as `max(1000, 2)` or `max(1, 000, 2)` depending on
+/// what is meant. Likewise, if `,` is the decimal separator, a `,` followed by a digit in a
+/// call like `max(2,5, 3)` is ambiguous, and is written as `max((2,5), 3)` or `max(2, 5, 3)`.
+///
+/// # Examples
+/// ```
+/// use math_lib::{parse_to_ast_with, NumberFormat, ParseError, ParserOptions, Span};
+///
+/// let english = ParserOptions { number_format: Some(NumberFormat::ENGLISH), ..ParserOptions::default() };
+/// let german = ParserOptions { number_format: Some(NumberFormat::GERMAN), ..ParserOptions::default() };
+/// assert_eq!(parse_to_ast_with("1,000,000.5", &english).unwrap().evaluate(), Ok(1000000.5));
+/// assert_eq!(parse_to_ast_with("1.000.000,5", &german).unwrap().evaluate(), Ok(1000000.5));
+/// assert_eq!(parse_to_ast_with("1,5", &english).unwrap_err(), ParseError::AmbiguousNumber { span: Span::new(0, 3) });
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberFormat {
+    /// Character between the integer part and the fraction.
+    pub decimal_separator: char,
+    /// Characters that may separate the groups of three digits of the integer part.
+    pub grouping_separators: &'static [char],
+}
+
+impl NumberFormat {
+    /// Decimal point, with digits grouped by `,`, space, `_` or `'`, like `1,000.5`.
+    pub const ENGLISH: NumberFormat = NumberFormat { decimal_separator: '.', grouping_separators: &[',', ' ', '_', '\''] };
+    /// Decimal comma, with digits grouped by `.`, space, `_` or `'`, like `1.000,5`.
+    pub const GERMAN: NumberFormat = NumberFormat { decimal_separator: ',', grouping_separators: &['.', ' ', '_', '\''] };
+    /// Decimal comma, with digits grouped by space or `_`, like `1 000,5`.
+    pub const FRENCH: NumberFormat = NumberFormat { decimal_separator: ',', grouping_separators: &[' ', '_'] };
+    /// Decimal point, with digits grouped by `'`, like `1'000.5`.
+    pub const SWISS: NumberFormat = NumberFormat { decimal_separator: '.', grouping_separators: &['\''] };
+}
+
+/// Meaning of `%` in an expression.
+///
+/// # Examples
+/// ```
+/// use math_lib::{parse_to_ast_with, ParserOptions, PercentMode};
+///
+/// let evaluate = |input: &str, percent: PercentMode| {
+///     parse_to_ast_with(input, &ParserOptions { percent, ..ParserOptions::default() }).unwrap().evaluate()
+/// };
+/// assert_eq!(evaluate("1%4", PercentMode::Ratio), Ok(25.));
+/// assert_eq!(evaluate("200*10%", PercentMode::Postfix), Ok(20.));
+/// assert_eq!(evaluate("200+10%", PercentMode::Postfix), Ok(200.1));
+/// assert_eq!(evaluate("200+10%", PercentMode::AddOn), Ok(220.));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PercentMode {
+    /// `%` is an infix operator giving how many percent its left operand is of its right
+    /// one, so `1%4` is `25`.
+    #[default]
+    Ratio,
+    /// `%` is a postfix operator dividing by a hundred, so `10%` is `0.1`.
+    Postfix,
+    /// Like [`PercentMode::Postfix`], except that a percentage added to or subtracted from
+    /// a value is a percentage of that value, like on a pocket calculator. So `200+10%` is
+    /// `220` and `200-10%` is `180`, while `200*10%` is still `20`.
+    AddOn,
+}
+
+/// Options that change how expressions are parsed.
+///
+/// Functions taking a [`Context`](crate::Context) parse with the options set on it, the
+/// other functions use the defaults.
+///
+/// # Examples
+/// ```
+/// use math_lib::{evaluate_with, Context, ImplicitMultiplication, ParserOptions};
+///
+/// let mut context = Context::new();
+/// context.set_variable("x", 4.);
+/// assert_eq!(evaluate_with("3(x+1)", &context), Ok(15.));
+///
+/// context.set_parser_options(ParserOptions {
+///     implicit_multiplication: ImplicitMultiplication::Disabled,
+///     ..ParserOptions::default()
+/// });
+/// assert!(evaluate_with("3(x+1)", &context).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParserOptions {
+    /// How operands written next to each other are read.
+    pub implicit_multiplication: ImplicitMultiplication,
+    /// Whether a number may end with an SI prefix, for engineering.
+    ///
+    /// The prefixes `f`, `p`, `n`, `µ` (or `μ` or `u`), `m`, `k`, `M`, `G` and `T` scale the
+    /// number by the power of ten they stand for, so `4.7k` is `4700` and `10µ` is `0.00001`.
+    /// A prefix must directly follow the digits and must not be followed by a letter, digit
+    /// or `_`, so `2min` is still `2` times `min`. Off by default.
+    ///
+    /// # Examples
+    /// ```
+    /// use math_lib::{parse_to_ast_with, ParserOptions};
+    ///
+    /// let engineering = ParserOptions { si_suffixes: true, ..ParserOptions::default() };
+    /// assert_eq!(parse_to_ast_with("4.7k*2.2M", &engineering).unwrap().evaluate(), Ok(1.034e10));
+    /// // Without SI suffixes, `k` is a variable
+    /// assert_eq!(parse_to_ast_with("4.7k", &ParserOptions::default()).unwrap().to_string(), "4.7*k");
+    /// ```
+    pub si_suffixes: bool,
+    /// Separators number literals are written with.
+    ///
+    /// Without a format, both `.` and `,` are read as the decimal separator and digits
+    /// cannot be grouped, so `1,5` is one and a half. This is the default.
+    pub number_format: Option<NumberFormat>,
+    /// Meaning of `%`, the ratio in percent by default.
+    pub percent: PercentMode,
+}
@@ -1,24 +1,89 @@
 //! # Description
 //! This module provides functions for parsing mathematical expressions.
 
-
-/* 
-* to_postfix() author: Richard Gajdosik <gajdo33@vutbr.cz> 2024 VUT FIT
-* Sources: https://www.fit.vutbr.cz/study/courses/IFJ/private/prednesy/Ifj08-en.pdf
-*          https://github.com/RichardGajdosik/VUTFIT_IFJ_2021_Projekt/blob/master/src/expressions.c
-*/
-
-// Operator enum representing possible operators in the expressions
-#[derive(Debug, Clone, Copy, PartialEq)]
+use crate::ast::{Expr, Statement};
+use crate::constants::Constant;
+use crate::context::Context;
+use crate::error::{ParseError, Span};
+use crate::operators::{Fixity, OperatorRegistry, TIGHT_IMPLICIT_MULTIPLICATION};
+use crate::options::{ImplicitMultiplication, NumberFormat, ParserOptions, PercentMode};
+
+/// Largest number of levels expressions may be nested, by parentheses, calls, prefix and
+/// postfix operators or the right operands of infix operators. Parsing and evaluating
+/// recurse once per level, so deeper input is rejected instead of overflowing the stack.
+/// Infix operators in a row, like in `1+2+3`, are evaluated in a loop and add no level.
+const MAX_NESTING_DEPTH: usize = 256;
+
+/// Largest height of an expression tree. Printing, cloning and dropping a tree recurse
+/// once per level, including every infix operator in a row, so a row like `1+2+3` may have
+/// at most this many operands.
+const MAX_TREE_HEIGHT: usize = 1000;
+
+/// Operator that can appear in an expression.
+///
+/// # Precedence
+/// Operators on a higher level bind tighter than operators on a lower level, so they are
+/// applied first. Operators on the same level group according to their associativity.
+///
+/// | Level | Operators             | Form            | Associativity | Example                  |
+/// |-------|-----------------------|-----------------|---------------|--------------------------|
+/// | 1     | `+` `-`               | infix           | left          | `1-2+3` is `(1-2)+3`     |
+/// | 2     | `*` `/` `%` `mod`     | infix           | left          | `8/4*2` is `(8/4)*2`     |
+/// | 3     | `+` `-` `~`           | prefix          | right         | `-2*3` is `(-2)*3`       |
+/// | 4     | `^` `√`               | infix or prefix | right         | `2^3^2` is `2^(3^2)`     |
+/// | 5     | `!`                   | postfix         | left          | `2^3!` is `2^(3!)`       |
+///
+/// By default `%` gives the ratio of its operands in percent. With the other
+/// [`PercentMode`]s of the [`ParserOptions`], `%` is a postfix operator on level 5 instead,
+/// so `2^10%` is `2^(10%)`. The remainder `a mod b` has the sign of `b`, so `-7 mod 3` is
+/// `2` and `7 mod -3` is `-2`.
+///
+/// The bitwise operators bind looser than level 1, like in C. From the tightest they are
+/// the shifts `<<` and `>>`, then `&`, then `xor` and finally `|`, all infix and left
+/// associative, so `1+1 << 4 | 1` is `((1+1) << 4) | 1`. They work on whole numbers, see
+/// [`IntegerMode`](crate::IntegerMode).
+///
+/// With the `units` feature, the conversion `to` is an infix operator below all of them,
+/// so `5 km/h to m/s` converts the whole of `5 km/h`.
+///
+/// A prefix operator applies to everything on its own or a higher level to its right, so
+/// `-2^2` is `-(2^2)`, `√2^4` is `√(2^4)` and `2^-1*4` is `(2^(-1))*4`. Only `√` exists
+/// both as a prefix and an infix operator on the same level: `√16` is the square root and
+/// `3√8` the cube root of 8.
+/// Parentheses and the arguments of a function call are parsed on their own, so
+/// `(1+2)*3` is `9` and `max(1, 2)^2` is `4`.
+///
+/// Operands written next to each other, like `2π` or `(1+2)(3+4)`, are multiplied on
+/// level 2 by default. See [`ImplicitMultiplication`] for binding them tighter, on a level
+/// between 2 and 3, or not at all.
+///
+/// # Examples
+/// ```
+/// use math_lib::parse;
+///
+/// assert_eq!(parse("2^3^2"), Ok(512.));
+/// assert_eq!(parse("-2^2"), Ok(-4.));
+/// assert_eq!(parse("2*3!"), Ok(12.));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Operator {
     Plus,       // Represents '+'
     Minus,      // Represents '-'
     Multiply,   // Represents '*'
     Divide,     // Represents '/'
     Percent,    // Represents '%'
+    Modulo,     // Represents 'mod'
     Power,      // Represents '^'
     Root,       // Represents '√'
     Factorial,  // Represents '!'
+    BitAnd,     // Represents '&'
+    BitOr,      // Represents '|'
+    BitXor,     // Represents 'xor'
+    BitNot,     // Represents '~'
+    ShiftLeft,  // Represents '<<'
+    ShiftRight, // Represents '>>'
+    #[cfg(feature = "units")]
+    Convert,    // Represents 'to'
     OpenParen,  // Represents '('
     CloseParen, // Represents ')'
     EndOfInput, // Represents '$'
@@ -27,416 +92,1026 @@ pub enum Operator {
 // Token enum representing either a value or an operator
 #[derive(Debug, Clone, PartialEq)]
 enum Token {
-    Operand(f64),       // For numerical values
-    Operator(Operator), // For operators including parentheses and end of input
-}
-
-impl Token {
-    fn precedence_index(&self) -> usize {
-        match self {
-            Token::Operator(Operator::Multiply) => 0,
-            Token::Operator(Operator::Divide) => 1,
-            Token::Operator(Operator::Percent) => 2,
-            Token::Operator(Operator::Plus) => 3,
-            Token::Operator(Operator::Minus) => 4,
-            Token::Operator(Operator::OpenParen) => 5,
-            Token::Operator(Operator::CloseParen) => 6,
-            Token::Operator(Operator::EndOfInput) => 7,
-            Token::Operand(_) => 8,
-            Token::Operator(Operator::Power) => 9,
-            Token::Operator(Operator::Root) => 10,
-            Token::Operator(Operator::Factorial) => 11,
-        }
-    }
-}
-
-/// Evaluates a postfix expression.
-/// 
-/// This function takes a vector of Tokens in postfix order and evaluates the expression
-/// to produce a single numerical result. It handles binary operations like addition,
-/// subtraction, multiplication, and division, as well as unary operations and functions
-/// like factorial and square root.
-/// 
-/// # Arguments
-/// 
-/// * `tokens` - A vector of Tokens in postfix notation
-/// 
-/// # Returns
-/// A Result containing the evaluated result as f64 or an error string if the expression
-/// contains invalid operations or if an arithmetic error occurs (like division by zero).
-///
-/// # Examples
-/// ```
-/// let postfix = vec![
-///     Token::Operand(3.),
-///     Token::Operand(1.),
-///     Token::Operator(Operator::Plus),
-///     Token::Operand(2.),
-///     Token::Operator(Operator::Multiply)
-/// ];
-/// let result = evaluate_expression(postfix).unwrap();
-/// assert_eq!(result, 8.);
-/// ```
-fn evaluate_expression(tokens: Vec<Token>) -> Result<f64, String> {
-    let mut stack: Vec<f64> = Vec::new();
+    Operand(f64),          // For numerical values
+    Identifier(String),    // For variable names
+    Constant(Constant),    // For mathematical constants like 'π'
+    Operator(Operator),    // For operators including parentheses and end of input
+    Assign,                // For '=' in assignments
+    Separator,             // For ';' between statements
+    Function(String),      // For a function name directly followed by '(', which it includes
+    Comma,                 // For ',' between the arguments of a function
+}
 
-    for token in tokens {
-        match token {
-            Token::Operand(num) => {
-                stack.push(num);
-            }
-            Token::Operator(op) =>
-
-            
-            match op {
-                // EDGE CASES
-                // Handle unary minus separately when there is only one operand available
-                Operator::Minus if stack.len() == 1 => {
-                    let num = stack.pop().unwrap();
-                    stack.push(-num);
-                },
-
-                Operator::Root if stack.len() == 1 => {
-                    let num = stack.pop().unwrap();
-                    if num < 0. {
-                        return Err("Cannot take the root of a negative number".to_string());
-                    }
-                    let answer = num.powf(1. / 2.);
-                    stack.push(answer);
-                },
-                
-                _ => {
-                    if stack.len() < 2 {
-                        return Err("Invalid expression".to_string());
-                    }
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
-
-                    //println!("Left operand: {}, Right operand: {}", left, right);
-
-                    let answer = match op {
-                        Operator::Plus => {
-                            left + right
-                        }
-                        Operator::Minus => {
-                            left - right
-                        }
-                        Operator::Multiply => {
-                            left * right
-                        }
-                        Operator::Divide => {
-                            if right.abs() < f64::EPSILON {
-                                return Err("Cannot divide by zero".to_string());
-                            }
-                            left / right
-                        }
-                        Operator::Percent => {
-                            if right.abs() < f64::EPSILON {
-                                return Err("Cannot take the percentage of zero".to_string());
-                            }
-                            left / right * 100.0
-                        }
-                        Operator::Power => {
-                            left.powf(right)
-                        }
-                        Operator::Root => {
-                            if left.abs() < f64::EPSILON {
-                                return Err("Cannot take the 0th root".to_string());
-                            }
-                            if right < 0. {
-                                return Err("Cannot take the root of a negative number".to_string());
-                            }
-                            right.powf(1. / left)
-                        }
-                        Operator::Factorial => {
-                            if left < 0. {
-                                return Err("Cannot take factorial of a negative number".to_string());
-                            }
-                            let mut res = 1.0;
-                            (2..=(left as i64)).for_each(|i| res *= i as f64);
-                            res
-                        }
-                        _ => {
-                            return Err(format!("{op:?} is an invalid operator"));
-                        }
-                    };
-                    stack.push(answer);
-                }
-            }
-        }
-    }
+// Token together with the part of the input it was read from
+#[derive(Debug, Clone, PartialEq)]
+struct SpannedToken {
+    token: Token,
+    span: Span,
+    // For an operand read from the input, its exact value written as a decimal like `1.5e3`
+    literal: Option<String>,
+}
 
-    if stack.len() != 1 {
-        return Err("Invalid expression".to_string());
+impl SpannedToken {
+    fn new(token: Token, span: Span) -> SpannedToken {
+        SpannedToken { token, span, literal: None }
     }
 
-    Ok(stack.pop().unwrap())
+    fn operand(value: f64, literal: String, span: Span) -> SpannedToken {
+        SpannedToken { token: Token::Operand(value), span, literal: Some(literal) }
+    }
 }
 
 fn process_current_number(
     current_number: &mut String,
-    output_queue: &mut Vec<Token>,
-) -> Result<(), String> {
+    number_start: usize,
+    number_end: usize,
+    output_queue: &mut Vec<SpannedToken>,
+) -> Result<(), ParseError> {
     if !current_number.is_empty() {
+        let span = Span::new(number_start, number_end);
         match current_number.parse::<f64>() {
             Ok(num) => {
-                output_queue.push(Token::Operand(num));
+                output_queue.push(SpannedToken::operand(num, current_number.clone(), span));
                 current_number.clear();
             }
-            Err(_) => return Err("Failed to parse number".to_string()),
+            Err(_) => return Err(ParseError::MalformedNumber { span }),
         }
     }
     Ok(())
 }
 
+/// Reads the digits and separators of the number literal at the start of `input`.
+///
+/// Without a `format`, both `.` and `,` are decimal separators. With a format, grouping
+/// separators are dropped after checking that they split the integer part into groups of
+/// three digits. A `,` inside a function call separates arguments and ends the number. It
+/// is ambiguous if it could also belong to the number, which is the case if `,` groups
+/// digits in `format` and exactly three digits follow, or if `,` is the decimal separator
+/// of `format` and a digit follows.
+///
+/// # Arguments
+/// * `input` - Input starting with the first character of the number
+/// * `start` - Position of `input` in the whole input, for the span of errors
+/// * `format` - Separators of the number, or `None` for the legacy format
+/// * `in_call` - Whether the number is an argument of a function call
+///
+/// # Returns
+/// * `Ok((String, usize))` - The number with `.` as the decimal separator and without
+///   grouping separators, and its length in bytes in `input`
+/// * `Err(ParseError)` - If the number is ambiguous in `format`, or in a call could also be
+///   read as two arguments
+fn read_number(
+    input: &str,
+    start: usize,
+    format: Option<&NumberFormat>,
+    in_call: bool,
+) -> Result<(String, usize), ParseError> {
+    let mut number = String::new();
+    let mut length = 0;
+    // Digits read since the last separator, the grouping separator in use and whether the
+    // decimal separator has been read
+    let mut group = 0;
+    let mut grouping: Option<char> = None;
+    let mut fraction = false;
+    let mut ambiguous = false;
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        let digit_follows = chars.peek().is_some_and(char::is_ascii_digit);
+        let separates_arguments = c == ',' && in_call;
+        // `max(1,000, 2)` might be meant with 1000 as well as with the arguments 1 and 0, and
+        // `max(2,5, 3)` with 2.5 if `,` is the decimal separator
+        let groups_digits = format.is_some_and(|format| format.grouping_separators.contains(&','));
+        let decimal_comma = format.is_some_and(|format| format.decimal_separator == ',');
+        if separates_arguments && !fraction {
+            let digits = input[length + 1..].bytes().take_while(u8::is_ascii_digit).count();
+            if (groups_digits && digits == 3) || (decimal_comma && digits > 0) {
+                return Err(ParseError::AmbiguousNumber { span: Span::new(start, start + length + 1 + digits) });
+            }
+        }
+        match format {
+            _ if c.is_ascii_digit() => {
+                number.push(c);
+                group += 1;
+            }
+            None if (c == '.' || c == ',') && !separates_arguments => number.push('.'),
+            Some(format) if c == format.decimal_separator && !separates_arguments => {
+                ambiguous |= grouping.is_some() && group != 3;
+                number.push(if fraction { c } else { '.' });
+                fraction = true;
+            }
+            Some(format) if format.grouping_separators.contains(&c) && digit_follows && !separates_arguments => {
+                ambiguous |= fraction
+                    || grouping.is_some_and(|separator| separator != c)
+                    || group == 0
+                    || group > 3
+                    || (grouping.is_some() && group != 3);
+                grouping = Some(c);
+                group = 0;
+            }
+            // A separator of another locale
+            Some(_) if (c == '.' || c == ',') && digit_follows && !separates_arguments => {
+                ambiguous = true;
+                number.push(c);
+            }
+            _ => break,
+        }
+        length += c.len_utf8();
+    }
+    ambiguous |= grouping.is_some() && !fraction && group != 3;
+    if ambiguous {
+        return Err(ParseError::AmbiguousNumber { span: Span::new(start, start + length) });
+    }
+    Ok((number, length))
+}
+
+/// Returns the length in bytes of the exponent at the start of `input`, like `e3` or
+/// `E-9`, or 0 if `input` does not start with one.
+fn exponent_length(input: &str) -> usize {
+    let bytes = input.as_bytes();
+    if !matches!(bytes.first(), Some(b'e' | b'E')) {
+        return 0;
+    }
+    let sign = usize::from(matches!(bytes.get(1), Some(b'+' | b'-')));
+    let digits = bytes[1 + sign..].iter().take_while(|byte| byte.is_ascii_digit()).count();
+    if digits == 0 {
+        0
+    } else {
+        1 + sign + digits
+    }
+}
+
+/// Returns the length in bytes and the power of ten of the SI prefix at the start of
+/// `input`, unless the prefix is the start of a longer name.
+fn si_suffix(input: &str) -> Option<(usize, i32)> {
+    let mut chars = input.chars();
+    let prefix = chars.next()?;
+    let power = match prefix {
+        'f' => -15,
+        'p' => -12,
+        'n' => -9,
+        'µ' | 'μ' | 'u' => -6,
+        'm' => -3,
+        'k' => 3,
+        'M' => 6,
+        'G' => 9,
+        'T' => 12,
+        _ => return None,
+    };
+    if chars.next().is_some_and(|next| next.is_alphanumeric() || next == '_') {
+        return None;
+    }
+    Some((prefix.len_utf8(), power))
+}
+
+/// Reads the integer literal with a radix prefix at the start of `input`, like `0xFF`,
+/// `0o17` or `0b1010`.
+///
+/// The digits may be grouped with `_`, like `0b1111_0000`. The literal runs to the end of
+/// the word, so `0b102` is malformed instead of `0b10` followed by `2`.
+///
+/// # Arguments
+/// * `input` - Input starting with the first character of the number
+/// * `start` - Position of `input` in the whole input, for the span of errors
+///
+/// # Returns
+/// * `None` - If `input` does not start with a prefix followed by a digit of its base
+/// * `Some(Ok(SpannedToken))` - The operand, with its exact value unless it is too large
+///   for a `u128`
+/// * `Some(Err(ParseError))` - If the literal contains characters that are not digits
+fn radix_literal(input: &str, start: usize) -> Option<Result<SpannedToken, ParseError>> {
+    let radix = match input.get(..2)? {
+        "0b" | "0B" => 2,
+        "0o" | "0O" => 8,
+        "0x" | "0X" => 16,
+        _ => return None,
+    };
+    let digits = &input[2..];
+    digits.chars().next()?.to_digit(radix)?;
+    let length = 2 + digits.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(digits.len());
+    let span = Span::new(start, start + length);
+    let malformed = ParseError::MalformedNumber { span };
+    let mut value = 0.;
+    let mut exact = Some(0u128);
+    let mut previous = None;
+    for c in input[2..length].chars() {
+        match c.to_digit(radix) {
+            Some(digit) => {
+                value = value * f64::from(radix) + f64::from(digit);
+                exact = exact.and_then(|exact| exact.checked_mul(radix.into())?.checked_add(digit.into()));
+            }
+            // A separator must follow a digit, and the last digit must not be followed by one
+            None if c == '_' && previous != Some('_') => {}
+            None => return Some(Err(malformed)),
+        }
+        previous = Some(c);
+    }
+    if previous == Some('_') {
+        return Some(Err(malformed));
+    }
+    Some(Ok(match exact {
+        Some(exact) => SpannedToken::operand(value, exact.to_string(), span),
+        None => SpannedToken::new(Token::Operand(value), span),
+    }))
+}
+
+/// Input rewritten to the spelling the tokenizer reads, with the origin of every byte.
+///
+/// Text pasted from documents often uses typographic symbols instead of the ASCII ones.
+/// These are replaced as follows:
+///
+/// | Input                         | Replacement       |
+/// |-------------------------------|-------------------|
+/// | `×`, `·`, `⋅`                 | `*`               |
+/// | `÷`                           | `/`               |
+/// | `−` (minus sign), `–` (dash)  | `-`               |
+/// | Superscripts like `²` or `⁻¹` | `^2` or `^-1`     |
+/// | Unicode spaces                | ` `               |
+/// | `’`                           | `'`               |
+///
+/// A run of superscript digits and signs is a single exponent, so `x²³` is `x^23`.
+///
+/// # Examples
+/// ```ignore
+/// let normalized = Normalized::new("2×3²");
+/// assert_eq!(normalized.text, "2*3^2");
+/// assert_eq!(normalized.original_span(Span::new(3, 5)), Span::new(3, 5));
+/// ```
+struct Normalized {
+    text: String,
+    // Span of the input character every byte of `text` was produced from, followed by the
+    // empty span at the end of the input
+    origins: Vec<Span>,
+}
+
+impl Normalized {
+    /// Normalizes `input`.
+    fn new(input: &str) -> Normalized {
+        let mut normalized = Normalized { text: String::with_capacity(input.len()), origins: Vec::new() };
+        let mut in_superscript = false;
+        for (index, c) in input.char_indices() {
+            let origin = Span::new(index, index + c.len_utf8());
+            let superscript = superscript(c);
+            if superscript.is_some() && !in_superscript {
+                normalized.push('^', origin);
+            }
+            in_superscript = superscript.is_some();
+            let replacement = match (c, superscript) {
+                (_, Some(mapped)) => mapped,
+                ('×' | '·' | '⋅', None) => '*',
+                ('÷', None) => '/',
+                ('−' | '–', None) => '-',
+                ('’', None) => '\'',
+                (c, None) if c.is_whitespace() => ' ',
+                (c, None) => c,
+            };
+            normalized.push(replacement, origin);
+        }
+        normalized.origins.push(Span::new(input.len(), input.len()));
+        normalized
+    }
+
+    /// Appends `c`, which was produced from the input character at `origin`.
+    fn push(&mut self, c: char, origin: Span) {
+        self.text.push(c);
+        self.origins.extend(std::iter::repeat_n(origin, c.len_utf8()));
+    }
+
+    /// Returns the span of the input that the span `span` of the normalized text was produced from.
+    fn original_span(&self, span: Span) -> Span {
+        let start = self.origins[span.start].start;
+        if span.end <= span.start {
+            return Span::new(start, start);
+        }
+        Span::new(start, self.origins[span.end - 1].end)
+    }
+}
+
+/// Returns the ASCII character a superscript digit or sign stands for, or `None` if `c` is
+/// not a superscript.
+fn superscript(c: char) -> Option<char> {
+    match c {
+        '⁰' => Some('0'),
+        '¹' => Some('1'),
+        '²' => Some('2'),
+        '³' => Some('3'),
+        '⁴'..='⁹' => char::from_digit(c as u32 - '⁰' as u32, 10),
+        '⁺' => Some('+'),
+        '⁻' => Some('-'),
+        _ => None,
+    }
+}
+
 /// Tokenizes a string input into a vector of Tokens.
-/// 
+///
 /// This function scans a string representing a mathematical expression and converts
-/// it into a sequence of tokens. Each token represents either an operator or an operand.
-/// The function handles numbers, operators, and parentheses, converting them into
-/// their respective Token representations.
-/// 
+/// it into a sequence of tokens. Each token represents either an operator or an operand
+/// and remembers the byte span of the input it was read from.
+/// The function handles numbers, identifiers, operators, and parentheses, converting them
+/// into their respective Token representations. Operators are read with the symbols
+/// registered in the standard [`OperatorRegistry`], preferring the longest symbol. An identifier starts with a letter or `_`
+/// and continues with letters, digits or `_`; directly followed by `(` it names a function.
+/// The names and symbols of [`Constant`]s are read as constants.
+/// Inside the parentheses of a function call a comma separates arguments, elsewhere it is
+/// a decimal point. Whitespace between tokens is ignored.
+///
+/// The input is first rewritten by [`Normalized`], so typographic operators and
+/// superscripts are read like their ASCII spelling. The spans of the tokens and of errors
+/// still refer to the original input.
+/// A number may end with an exponent, so `6.022e23` and `1.5E-9` are single numbers, while
+/// an `e` not followed by digits is the constant, as in `2e`. With
+/// [`ParserOptions::si_suffixes`] set, a number may instead end with an SI prefix, as in `4.7k`.
+/// Integers may also be written in base 2, 8 or 16 with the prefixes `0b`, `0o` and `0x`, see
+/// [`radix_literal`].
+///
 /// # Arguments
-/// 
+///
 /// * `input` - A string slice representing the mathematical expression to tokenize
-/// 
+/// * `options` - The options changing how numbers are read
+///
 /// # Returns
-/// A Result containing a vector of Tokens if successful, or an error message if the input
+/// A Result containing a vector of SpannedTokens if successful, or a ParseError if the input
 /// contains invalid characters or improperly formatted numbers.
 /// # Examples
-/// ```
-/// let input = "3+4*2/(1-5)";
-/// let tokens = tokenize(input).unwrap();
+/// ```ignore
+/// let input = "3+4";
+/// let tokens = tokenize(input, &ParserOptions::default()).unwrap();
 /// assert_eq!(tokens, vec![
-///     Token::Operand(3.),
-///     Token::Operator(Operator::Plus),
-///     Token::Operand(4.),
-///     Token::Operator(Operator::Multiply),
-///     Token::Operand(2.),
-///     Token::Operator(Operator::Divide),
-///     Token::Operator(Operator::OpenParen),
-///     Token::Operand(1.),
-///     Token::Operator(Operator::Minus),
-///     Token::Operand(5.),
-///     Token::Operator(Operator::CloseParen)
+///     SpannedToken::operand(3., "3".to_string(), Span::new(0, 1)),
+///     SpannedToken::new(Token::Operator(Operator::Plus), Span::new(1, 2)),
+///     SpannedToken::operand(4., "4".to_string(), Span::new(2, 3)),
+///     SpannedToken::new(Token::Operator(Operator::EndOfInput), Span::new(3, 3)),
 /// ]);
 /// ```
-fn tokenize(input: &str) -> Result<Vec<Token>, String> {
-    let mut input_queue: Vec<Token> = Vec::new();
-    let mut current_number = String::new();
+fn tokenize(input: &str, options: &ParserOptions) -> Result<Vec<SpannedToken>, ParseError> {
+    let normalized = Normalized::new(input);
+    let tokens = tokenize_normalized(&normalized.text, options).map_err(|error| {
+        let span = normalized.original_span(error.span());
+        error.with_span(span)
+    })?;
+    Ok(tokens
+        .into_iter()
+        .map(|token| SpannedToken { span: normalized.original_span(token.span), ..token })
+        .collect())
+}
 
-    let mut input_chars = input.chars().peekable();
-    while let Some(c) = input_chars.next() {
+/// Tokenizes input that has already been rewritten by [`Normalized`], see [`tokenize`].
+fn tokenize_normalized(input: &str, options: &ParserOptions) -> Result<Vec<SpannedToken>, ParseError> {
+    let operators = OperatorRegistry::standard();
+    let mut input_queue: Vec<SpannedToken> = Vec::new();
+    // For every open parenthesis, whether it belongs to a function call
+    let mut open_parens: Vec<bool> = Vec::new();
+
+    let mut input_chars = input.char_indices().peekable();
+    while let Some((index, c)) = input_chars.next() {
+        let span = Span::new(index, index + c.len_utf8());
+        let in_call = open_parens.last() == Some(&true);
+        if let Some((op, length)) = operators.symbol_at(&input[index..]) {
+            let span = Span::new(index, index + length);
+            input_queue.push(SpannedToken::new(Token::Operator(op), span));
+            // Skip the remaining characters of a longer symbol
+            while input_chars.next_if(|&(next_index, _)| next_index < span.end).is_some() {}
+            continue;
+        }
         match c {
-            '+' => {
-                let op = Operator::Plus;
-                input_queue.push(Token::Operator(op));
-            }
-            '-' => {
-                let op = Operator::Minus;
-                input_queue.push(Token::Operator(op));
-            }
-            '*' => {
-                let op = Operator::Multiply;
-                input_queue.push(Token::Operator(op));
-            }
-            '/' => {
-                let op = Operator::Divide;
-                input_queue.push(Token::Operator(op));
-            }
-            '%' => {
-                let op = Operator::Percent;
-                input_queue.push(Token::Operator(op));
-            }
             '(' => {
                 let op = Operator::OpenParen;
-                input_queue.push(Token::Operator(op));
+                input_queue.push(SpannedToken::new(Token::Operator(op), span));
+                open_parens.push(false);
             }
             ')' => {
                 let op = Operator::CloseParen;
-                input_queue.push(Token::Operator(op));
+                input_queue.push(SpannedToken::new(Token::Operator(op), span));
+                open_parens.pop();
             }
-            '^' => {
-                let op = Operator::Power;
-                input_queue.push(Token::Operator(op));
+            ',' if in_call => {
+                input_queue.push(SpannedToken::new(Token::Comma, span));
+            }
+            '0'..='9' | ',' | '.' => {
+                if let Some(literal) = radix_literal(&input[index..], index) {
+                    let token = literal?;
+                    while input_chars.next_if(|&(next_index, _)| next_index < token.span.end).is_some() {}
+                    input_queue.push(token);
+                    continue;
+                }
+                let format = options.number_format.as_ref();
+                let (mut number, length) = read_number(&input[index..], index, format, in_call)?;
+                // A separator that does not belong to a number is read on its own and fails to parse
+                let length = if number.is_empty() {
+                    number.push(c);
+                    c.len_utf8()
+                } else {
+                    length
+                };
+                // An exponent or SI suffix written directly after the digits belongs to the number
+                let rest = &input[index + length..];
+                let mut end = index + length;
+                let exponent = exponent_length(rest);
+                if exponent > 0 {
+                    number.push_str(&rest[..exponent]);
+                    end += exponent;
+                } else if let Some((length, power)) = si_suffix(rest).filter(|_| options.si_suffixes) {
+                    number.push_str(&format!("e{power}"));
+                    end += length;
+                }
+                while input_chars.next_if(|&(next_index, _)| next_index < end).is_some() {}
+                process_current_number(&mut number, index, end, &mut input_queue)?;
             }
-            '√' => {
-                let op = Operator::Root;
-                input_queue.push(Token::Operator(op));
+            '=' => {
+                input_queue.push(SpannedToken::new(Token::Assign, span));
             }
-            '!' => {
-                let op = Operator::Factorial;
-                input_queue.push(Token::Operator(op));
-                // We implicitly add a second operand so that the factorial can be evaluated
-                input_queue.push(Token::Operand(1.));
+            ';' => {
+                input_queue.push(SpannedToken::new(Token::Separator, span));
             }
-            '0'..='9' | ',' | '.' => {
-                // If the character is a comma, replace it with a decimal point
-                let character = if c == ',' { '.' } else { c };
-                // Accumulate digit and decimal point characters into current_number
-                current_number.push(character);
-                // Check next character to decide if we should continue accumulating or process the number
-                if let Some(&next_char) = input_chars.peek() {
-                    if !next_char.is_digit(10) && next_char != ',' && next_char != '.' {
-                        process_current_number(&mut current_number, &mut input_queue)?;
+            c if c.is_whitespace() => {}
+            c if c.is_alphabetic() || c == '_' => {
+                // Accumulate the whole identifier
+                let mut name = c.to_string();
+                let mut end = span.end;
+                while let Some(&(next_index, next_char)) = input_chars.peek() {
+                    if !next_char.is_alphanumeric() && next_char != '_' {
+                        break;
                     }
+                    name.push(next_char);
+                    end = next_index + next_char.len_utf8();
+                    input_chars.next();
+                }
+                let name_span = Span::new(index, end);
+                if let Some((_, '(')) = input_chars.peek() {
+                    input_chars.next();
+                    input_queue.push(SpannedToken::new(Token::Function(name), name_span));
+                    open_parens.push(true);
+                } else if let Some(constant) = Constant::from_name(&name) {
+                    input_queue.push(SpannedToken::new(Token::Constant(constant), name_span));
                 } else {
-                    // If this is the last character, ensure the number is processed
-                    process_current_number(&mut current_number, &mut input_queue)?;
+                    input_queue.push(SpannedToken::new(Token::Identifier(name), name_span));
                 }
             }
-            _ => return Err(format!("Invalid character in input: {}", c)),
+            _ => return Err(ParseError::UnexpectedChar { ch: c, span }),
         }
     }
 
-    input_queue.push(Token::Operator(Operator::EndOfInput));
+    let end_of_input = Span::new(input.len(), input.len());
+    input_queue.push(SpannedToken::new(Token::Operator(Operator::EndOfInput), end_of_input));
     Ok(input_queue)
 }
 
-/// Converts a given infix expression to postfix notation.
-/// 
-/// The function uses a precedence table to resolve the order of operations
-/// and manage operator precedence. This ensures that the resulting postfix
-/// expression is correctly ordered for subsequent evaluation.
-/// 
-/// # Arguments
-/// 
-/// * `input_queue` - A vector of Tokens representing the infix expression
-/// 
-/// # Returns
-/// A Result containing the postfix notation as a vector of Tokens or an error message
-/// if the expression is invalid.
-/// 
+/// Parser building an expression tree from the tokens of a single expression.
+///
+/// The parser reads an operand and then extends it with every infix or postfix operator
+/// that binds at least as tightly as the operator the operand belongs to (Pratt parsing).
+/// How tightly operators bind and how they group is looked up in an [`OperatorRegistry`],
+/// so the grammar follows the precedence documented on [`Operator`].
+///
+/// A function name opens its argument list like a parenthesis, and every argument is parsed
+/// as an expression of its own. Operands written next to each other are multiplied as set
+/// by [`ParserOptions::implicit_multiplication`].
+///
 /// # Examples
+/// ```ignore
+/// let tokens = tokenize("3-1*f(2,4)", &ParserOptions::default()).unwrap();
+/// let expr = Parser::new(tokens, OperatorRegistry::standard(), &ParserOptions::default()).parse().unwrap();
+/// assert_eq!(expr.to_string(), "3-(1*f(2, 4))");
 /// ```
-/// let infix = vec![
-///     Token::Operand(3.),
-///     Token::Operator(Operator::Minus),
-///     Token::Operand(1.),
-///     Token::Operator(Operator::Multiply),
-///     Token::Operand(2.)
-/// ];
-/// let postfix = to_postfix(infix).unwrap();
-/// assert_eq!(postfix, vec![
-///     Token::Operand(3.),
-///     Token::Operand(1.),
-///     Token::Operator(Operator::Minus),
-///     Token::Operand(2.),
-///     Token::Operator(Operator::Multiply)
-/// ]);
-/// ```
-fn to_postfix(input_queue: Vec<Token>) -> Result<Vec<Token>, String> {
-    // We define the precedence table as a 2D array
-    let precedence_table: Vec<Vec<char>> = vec![
-        //    *    /    %    +    -    (    )    $    i    ^    √    !
-        vec!['>', '>', '>', '>', '>', '<', '>', '>', '<', '<', '<', '<'], // *
-        vec!['>', '>', '>', '>', '>', '<', '>', '>', '<', '<', '<', '<'], // /
-        vec!['>', '>', '>', '>', '>', '<', '>', '>', '<', '<', '<', '<'], // %
-        vec!['<', '<', '<', '>', '>', '<', '>', '>', '<', '<', '<', '<'], // +
-        vec!['<', '<', '<', '>', '>', '<', '>', '>', '<', '<', '<', '<'], // -
-        vec!['<', '<', '<', '<', '<', '<', '=', 'c', '<', '<', '<', '<'], // (
-        vec!['>', '>', '>', '>', '>', 'c', '>', '>', 'c', '>', '>', '>'], // )
-        vec!['<', '<', '<', '<', '<', '<', 'c', 's', '<', '<', '<', '<'], // $
-        vec!['>', '>', '>', '>', '>', 'c', '>', '>', 'c', '>', '>', '>'], // i
-        vec!['>', '>', '>', '>', '>', '<', '>', '>', '<', '>', '>', '<'], // ^
-        vec!['>', '>', '>', '>', '>', '<', '>', '>', '<', '>', '>', '<'], // √
-        vec!['>', '>', '>', '>', '>', '<', '>', '>', '<', '>', '>', '>'], // !
-    ];
+struct Parser<'a> {
+    tokens: Vec<SpannedToken>,
+    position: usize,
+    operators: &'a OperatorRegistry,
+    options: &'a ParserOptions,
+    // Spans of the parentheses and function calls that are open at the current position
+    open_parens: Vec<Span>,
+    // Number of expressions being parsed, one inside the other
+    depth: usize,
+    // Height of the tree returned by the last call of `expression` or `operand`
+    height: usize,
+    // Levels of recursion needed to evaluate that tree, see `evaluate_chain`
+    nesting: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Creates a parser for `tokens`, which must end with EndOfInput.
+    fn new(tokens: Vec<SpannedToken>, operators: &'a OperatorRegistry, options: &'a ParserOptions) -> Parser<'a> {
+        Parser { tokens, position: 0, operators, options, open_parens: Vec::new(), depth: 0, height: 0, nesting: 0 }
+    }
+
+    /// Returns the next token without consuming it, or EndOfInput once all tokens are consumed.
+    fn peek(&self) -> &SpannedToken {
+        &self.tokens[self.position.min(self.tokens.len() - 1)]
+    }
+
+    /// Consumes the next token and returns it.
+    fn advance(&mut self) -> SpannedToken {
+        let token = self.peek().clone();
+        self.position += 1;
+        token
+    }
+
+    /// Returns `true` if all tokens but EndOfInput are consumed.
+    fn at_end(&self) -> bool {
+        self.peek().token == Token::Operator(Operator::EndOfInput)
+    }
+
+    /// Returns the error for input ending where an operand of the operator at `span` is
+    /// expected, which points at the innermost open parenthesis if there is one.
+    fn missing_operand(&self, span: Span) -> ParseError {
+        match self.open_parens.last() {
+            Some(&open) => ParseError::UnbalancedParen { span: open },
+            None => ParseError::InvalidExpression { span },
+        }
+    }
+
+    /// Returns the height and nesting of a node at `span` whose children have at most the
+    /// given `height` and `nesting`, or an error if either exceeds its limit.
+    fn levels(&self, height: usize, nesting: usize, span: Span) -> Result<(usize, usize), ParseError> {
+        if height >= MAX_TREE_HEIGHT || nesting >= MAX_NESTING_DEPTH {
+            return Err(ParseError::NestingLimit { span });
+        }
+        Ok((height + 1, nesting + 1))
+    }
 
-    let mut input_queue = input_queue;
-    input_queue.reverse();
-    let mut output_queue: Vec<Token> = Vec::new();
-    let mut stack: Vec<Token> = vec![Token::Operator(Operator::EndOfInput)];
+    /// Returns the height and nesting of a binary node at `span` with the operands `left`,
+    /// whose levels are given, and `right`, the tree parsed last.
+    fn binary_levels(
+        &self,
+        left: &Expr,
+        height: usize,
+        nesting: usize,
+        span: Span,
+    ) -> Result<(usize, usize), ParseError> {
+        // Evaluating a binary left operand does not recurse, see `evaluate_chain`
+        let nesting = if matches!(left, Expr::Binary { .. }) { nesting - 1 } else { nesting };
+        self.levels(height.max(self.height), nesting.max(self.nesting), span)
+    }
 
-    while let Some(token) = input_queue.last().cloned() {
-        //println!("Current stack: {:?}", stack);
+    /// Returns the binding power of implicit multiplication, or `None` if it is disabled.
+    fn implicit_binding_power(&self) -> Option<u8> {
+        match self.options.implicit_multiplication {
+            ImplicitMultiplication::Disabled => None,
+            ImplicitMultiplication::SamePrecedence => {
+                self.operators.get(Fixity::Infix, Operator::Multiply).map(|entry| entry.binding_power)
+            }
+            ImplicitMultiplication::Tight => Some(TIGHT_IMPLICIT_MULTIPLICATION),
+        }
+    }
 
-        // Pop top of vector stack
-        let top = stack.last().cloned();
+    /// Parses all tokens as a single expression.
+    ///
+    /// # Returns
+    /// A Result containing the root of the expression tree or a ParseError pointing at the
+    /// first token that does not fit into the expression.
+    fn parse(mut self) -> Result<Expr, ParseError> {
+        let expr = self.expression(0)?;
+        let SpannedToken { token, span, .. } = self.advance();
+        match token {
+            Token::Operator(Operator::EndOfInput) => Ok(expr),
+            // ')' without any '(' to match
+            Token::Operator(Operator::CloseParen) => Err(ParseError::UnbalancedParen { span }),
+            _ => Err(ParseError::InvalidExpression { span }),
+        }
+    }
 
-        let index_first = match top {
-            Some(token) => token.precedence_index(),
-            None => {
-                //println!("Empty stack");
-                0
+    /// Parses an expression made of operators binding at least as tightly as `min_binding_power`.
+    fn expression(&mut self, min_binding_power: u8) -> Result<Expr, ParseError> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING_DEPTH {
+            return Err(ParseError::NestingLimit { span: self.peek().span });
+        }
+        let operators = self.operators;
+        let mut left = self.operand()?;
+        let (mut height, mut nesting) = (self.height, self.nesting);
+
+        loop {
+            let next = self.peek();
+            // An operand directly following another operand is multiplied by it
+            if matches!(
+                next.token,
+                Token::Identifier(_) | Token::Constant(_) | Token::Function(_) | Token::Operator(Operator::OpenParen)
+            ) {
+                let Some(binding_power) = self.implicit_binding_power() else { break };
+                if binding_power < min_binding_power {
+                    break;
+                }
+                let span = Span::new(next.span.start, next.span.start);
+                let right = self.expression(binding_power + 1)?;
+                (height, nesting) = self.binary_levels(&left, height, nesting, span)?;
+                left = Expr::Binary {
+                    op: Operator::Multiply,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                    add_on: false,
+                    span,
+                };
+                continue;
             }
-        };
-        let index_second = token.precedence_index();
 
-        let precedence = precedence_table[index_first][index_second];
+            let SpannedToken { token: Token::Operator(op), span, .. } = *next else { break };
+            if let Some(entry) = operators.get(Fixity::Postfix, op) {
+                if entry.binding_power < min_binding_power {
+                    break;
+                }
+                self.advance();
+                (height, nesting) = self.levels(height, nesting, span)?;
+                left = Expr::Unary { op, operand: Box::new(left), span };
+            } else if let Some(entry) = operators.get(Fixity::Infix, op) {
+                if entry.binding_power < min_binding_power {
+                    break;
+                }
+                self.advance();
+                let right = self.operand_of(span, entry.right_binding_power())?;
+                // An add-on percentage is a percentage of the value it is added to, so
+                // `a+b%` is evaluated as `a+a*b%`
+                let add_on = self.options.percent == PercentMode::AddOn
+                    && matches!(op, Operator::Plus | Operator::Minus)
+                    && matches!(right, Expr::Unary { op: Operator::Percent, .. });
+                (height, nesting) = self.binary_levels(&left, height, nesting, span)?;
+                left = Expr::Binary { op, left: Box::new(left), right: Box::new(right), add_on, span };
+            } else {
+                break;
+            }
+        }
+
+        self.depth -= 1;
+        (self.height, self.nesting) = (height, nesting);
+        Ok(left)
+    }
+
+    /// Parses the operand to the right of the operator at `span`.
+    ///
+    /// If the input ends instead, the error points at the operator missing its operand or
+    /// at the parenthesis left open.
+    fn operand_of(&mut self, span: Span, binding_power: u8) -> Result<Expr, ParseError> {
+        if self.at_end() {
+            return Err(self.missing_operand(span));
+        }
+        self.expression(binding_power)
+    }
 
-        match precedence {
-            '<' => {
-                // If input operator has lower precedence, push it onto the stack
-                //println!("Pushing top of Input_queue to stack");
-                let token_to_push_to_stack = input_queue.pop().unwrap();
-                stack.push(token_to_push_to_stack);
+    /// Parses a number, variable, constant, call, parenthesized expression or a prefix
+    /// operator together with its operand.
+    fn operand(&mut self) -> Result<Expr, ParseError> {
+        let SpannedToken { token, span, literal } = self.advance();
+        (self.height, self.nesting) = (1, 1);
+        match token {
+            Token::Operand(value) => Ok(Expr::Number { value, literal, span }),
+            Token::Identifier(name) => Ok(Expr::Variable { name, span }),
+            Token::Constant(constant) => Ok(Expr::Constant { constant, span }),
+            Token::Function(name) => self.call(name, span),
+            Token::Operator(Operator::OpenParen) => {
+                self.open_parens.push(span);
+                let expr = self.operand_of(span, 0)?;
+                self.close(span)?;
+                Ok(expr)
             }
-            '>' => {
-                // If input operator has higher precedence, pop operator from stack onto the Output_queue
-                //println!("Popping token from stack, pushing it to the output queue.");
-                let token_to_push_to_queue = stack.pop().expect("Expected token on the stack");
-                output_queue.push(token_to_push_to_queue);
+            Token::Operator(op) => match self.operators.get(Fixity::Prefix, op) {
+                Some(entry) => {
+                    let operand = self.operand_of(span, entry.right_binding_power())?;
+                    (self.height, self.nesting) = self.levels(self.height, self.nesting, span)?;
+                    Ok(Expr::Unary { op, operand: Box::new(operand), span })
+                }
+                None => Err(ParseError::InvalidExpression { span }),
+            },
+            Token::Assign | Token::Separator | Token::Comma => Err(ParseError::InvalidExpression { span }),
+        }
+    }
+
+    /// Consumes the ')' closing the '(' at `open`.
+    fn close(&mut self, open: Span) -> Result<(), ParseError> {
+        let SpannedToken { token, span, .. } = self.advance();
+        match token {
+            Token::Operator(Operator::CloseParen) => {
+                self.open_parens.pop();
+                Ok(())
             }
-            '=' => {
-                // We pop from stack and Input_queue
-                stack.pop().expect("Expected token '(' on the stack");
-                input_queue.pop().expect("Expected ')' on the stack");
+            // Input ended while the '(' was still open
+            Token::Operator(Operator::EndOfInput) => Err(ParseError::UnbalancedParen { span: open }),
+            _ => Err(ParseError::InvalidExpression { span }),
+        }
+    }
+
+    /// Parses the arguments of a call of `name` up to the closing ')'. The '(' is part of the
+    /// function token at `span`.
+    fn call(&mut self, name: String, span: Span) -> Result<Expr, ParseError> {
+        let mut args = Vec::new();
+        if self.peek().token == Token::Operator(Operator::CloseParen) {
+            self.advance();
+            return Ok(Expr::Call { name, args, span });
+        }
+
+        self.open_parens.push(span);
+        let (mut height, mut nesting) = (0, 0);
+        loop {
+            args.push(self.operand_of(span, 0)?);
+            (height, nesting) = (height.max(self.height), nesting.max(self.nesting));
+            match self.peek().token {
+                Token::Comma => {
+                    self.advance();
+                }
+                _ => {
+                    self.close(span)?;
+                    (self.height, self.nesting) = self.levels(height, nesting, span)?;
+                    return Ok(Expr::Call { name, args, span });
+                }
             }
-            's' => {
-                // Special case where we matched $ with $ we end here!
-                // println!("End of input and end of precedence stack");
-                return Ok(output_queue);
+        }
+    }
+}
+
+/// Parses the tokens of a single expression ending with EndOfInput.
+///
+/// # Returns
+/// A Result containing the root of the expression tree or a ParseError if the tokens
+/// are empty, contain an assignment or statement separator, or do not form an expression.
+fn build_expression(tokens: Vec<SpannedToken>, options: &ParserOptions) -> Result<Expr, ParseError> {
+    if let Some(token) = tokens.iter().find(|token| matches!(token.token, Token::Assign | Token::Separator)) {
+        return Err(ParseError::InvalidExpression { span: token.span });
+    }
+    if let [end_of_input] = tokens.as_slice() {
+        return Err(ParseError::InvalidExpression { span: end_of_input.span });
+    }
+    Parser::new(tokens, OperatorRegistry::with_percent(options.percent), options).parse()
+}
+
+/// Parses the tokens of a single statement ending with EndOfInput.
+///
+/// A statement starting with an identifier followed by `=` is an assignment, a statement
+/// starting with a function and containing `=` is a function definition, and any other
+/// statement is an expression.
+fn build_statement(tokens: Vec<SpannedToken>, options: &ParserOptions) -> Result<Statement, ParseError> {
+    match tokens.as_slice() {
+        [SpannedToken { token: Token::Identifier(name), span, .. }, SpannedToken { token: Token::Assign, .. }, ..] => {
+            let name = name.clone();
+            let span = *span;
+            let value = build_expression(tokens[2..].to_vec(), options)?;
+            Ok(Statement::Assign { name, value, span })
+        }
+        [SpannedToken { token: Token::Function(name), span, .. }, rest @ ..]
+            if rest.iter().any(|token| token.token == Token::Assign) =>
+        {
+            build_definition(name.clone(), *span, rest, options)
+        }
+        _ => Ok(Statement::Expression(build_expression(tokens, options)?)),
+    }
+}
+
+/// Parses a function definition `name(parameters) = body`.
+///
+/// # Arguments
+///
+/// * `name` - The name of the defined function
+/// * `span` - The span of the function name
+/// * `tokens` - The tokens following the function name, ending with EndOfInput
+/// * `options` - The options the body is parsed with
+///
+/// # Returns
+/// A Result containing the definition or a ParseError pointing at the first token that
+/// is not a parameter, comma, closing parenthesis or `=` where one is expected, or at a
+/// repeated parameter.
+fn build_definition(
+    name: String,
+    span: Span,
+    tokens: &[SpannedToken],
+    options: &ParserOptions,
+) -> Result<Statement, ParseError> {
+    let mut tokens = tokens.iter();
+    let mut next_token = || tokens.next().ok_or(ParseError::InvalidExpression { span });
+    let mut params: Vec<String> = Vec::new();
+
+    // Parameters are identifiers separated by commas and closed by ')'
+    let mut token = next_token()?;
+    if token.token != Token::Operator(Operator::CloseParen) {
+        loop {
+            match &token.token {
+                Token::Identifier(param) if !params.contains(param) => params.push(param.clone()),
+                _ => return Err(ParseError::InvalidExpression { span: token.span }),
             }
-            'c' => {
-                // Error
-                return Err("Invalid expression".to_string());
+            token = next_token()?;
+            match token.token {
+                Token::Comma => token = next_token()?,
+                Token::Operator(Operator::CloseParen) => break,
+                _ => return Err(ParseError::InvalidExpression { span: token.span }),
             }
-            _ => {
-                return Err("Invalid expression".to_string());
+        }
+    }
+
+    let token = next_token()?;
+    if token.token != Token::Assign {
+        return Err(ParseError::InvalidExpression { span: token.span });
+    }
+    let body = build_expression(tokens.cloned().collect(), options)?;
+    Ok(Statement::Define { name, params, body, span })
+}
+
+/// Splits the tokens of a script at statement separators and parses each statement.
+///
+/// Empty statements, for example after a trailing `;`, are skipped.
+fn build_statements(tokens: Vec<SpannedToken>, options: &ParserOptions) -> Result<Vec<Statement>, ParseError> {
+    let mut statements = Vec::new();
+    let mut current: Vec<SpannedToken> = Vec::new();
+
+    for token in tokens {
+        match token.token {
+            Token::Separator | Token::Operator(Operator::EndOfInput) => {
+                if !current.is_empty() {
+                    // Each statement gets its own end of input at the position of the separator
+                    let end_of_statement = Span::new(token.span.start, token.span.start);
+                    current.push(SpannedToken::new(Token::Operator(Operator::EndOfInput), end_of_statement));
+                    statements.push(build_statement(std::mem::take(&mut current), options)?);
+                }
             }
+            _ => current.push(token),
         }
     }
 
-    Ok(output_queue)
+    Ok(statements)
+}
+
+/// Parses a string expression into an expression tree.
+///
+/// The input is tokenized and its operators are grouped by their precedence into an
+/// [`Expr`]. The tree can be evaluated repeatedly with [`Expr::evaluate`], inspected, printed
+/// or transformed without tokenizing the input again.
+///
+/// # Arguments
+///
+/// * `input` - The string slice to parse
+///
+/// # Returns
+/// A Result containing the root of the expression tree or a ParseError if the expression
+/// is invalid. Spans inside the tree and the error point into `input`.
+/// # Examples
+/// ```
+/// use math_lib::{parse_to_ast, Expr, Operator};
+///
+/// let expr = parse_to_ast("1+2*3").unwrap();
+/// match &expr {
+///     Expr::Binary { op, right, .. } => {
+///         assert_eq!(*op, Operator::Plus);
+///         assert_eq!(right.to_string(), "2*3");
+///     }
+///     _ => unreachable!(),
+/// }
+/// assert_eq!(expr.evaluate(), Ok(7.));
+/// ```
+pub fn parse_to_ast(input: &str) -> Result<Expr, ParseError> {
+    parse_to_ast_with(input, &ParserOptions::default())
+}
+
+/// Parses a string expression into an expression tree with the given options.
+///
+/// # Arguments
+///
+/// * `input` - The string slice to parse
+/// * `options` - The options changing how the input is read
+///
+/// # Returns
+/// A Result containing the root of the expression tree or a ParseError if the expression
+/// is invalid under `options`.
+/// # Examples
+/// ```
+/// use math_lib::{parse_to_ast_with, ImplicitMultiplication, ParserOptions};
+///
+/// let options = ParserOptions { implicit_multiplication: ImplicitMultiplication::Tight, ..ParserOptions::default() };
+/// assert_eq!(parse_to_ast_with("1/2(3+1)", &options).unwrap().evaluate(), Ok(0.125));
+/// ```
+pub fn parse_to_ast_with(input: &str, options: &ParserOptions) -> Result<Expr, ParseError> {
+    let tokens = tokenize(input, options)?;
+    build_expression(tokens, options)
 }
 
 /// Parses a string expression and evaluates it to a number.
-/// 
-/// This function is the high-level interface to the expression parsing system. It first tokenizes
-/// the input string, converts the tokens from infix to postfix notation, and then evaluates the
-/// resulting postfix expression.
-/// 
+///
+/// This function is the high-level interface to the expression parsing system. It parses
+/// the input string into an expression tree with [`parse_to_ast`] and then evaluates it.
+///
 /// # Arguments
-/// 
+///
 /// * `input` - The string slice to parse and evaluate
-/// 
+///
 /// # Returns
-/// A Result containing the numerical result of the expression or an error message if the
-/// expression is invalid or an error occurs during evaluation.
+/// A Result containing the numerical result of the expression or a ParseError if the
+/// expression is invalid or an error occurs during evaluation. The error's span points
+/// into `input`.
 /// # Examples
 /// ```
+/// use math_lib::parse;
+///
 /// let result = parse("3+4*2/(1-5)^2").unwrap();
 /// assert_eq!(result, 3.5);
 /// ```
-pub fn parse(input: &str) -> Result<f64, String> {
-    let tokens = tokenize(input)?;
-    let postfix_result = to_postfix(tokens)?;
-    let result = evaluate_expression(postfix_result)?;
-    Ok(result)
+pub fn parse(input: &str) -> Result<f64, ParseError> {
+    parse_to_ast(input)?.evaluate()
 }
 
-#[cfg(test)]
-mod tokenize_tests {
-    use super::{tokenize, Token, Operator};
+/// Parses a string expression and evaluates it with the variables defined in `context`.
+///
+/// The input is parsed with the [`ParserOptions`] set on `context`.
+///
+/// # Arguments
+///
+/// * `input` - The string slice to parse and evaluate
+/// * `context` - The context providing the values of variables
+///
+/// # Returns
+/// A Result containing the numerical result of the expression or a ParseError if the
+/// expression is invalid, uses a variable missing from the context, or an error occurs
+/// during evaluation.
+/// # Examples
+/// ```
+/// use math_lib::{evaluate_with, Context, ParseError, Span};
+///
+/// let mut context = Context::new();
+/// context.set_variable("x", 4.);
+/// assert_eq!(evaluate_with("x^2+1", &context), Ok(17.));
+/// assert_eq!(
+///     evaluate_with("x+y", &context),
+///     Err(ParseError::UnknownVariable { name: "y".to_string(), span: Span::new(2, 3) })
+/// );
+/// ```
+pub fn evaluate_with(input: &str, context: &Context) -> Result<f64, ParseError> {
+    parse_to_ast_with(input, context.parser_options())?.evaluate_with(context)
+}
 
-    fn token_eoi() -> Token { Token::Operator(Operator::EndOfInput) }
+/// Parses a script of statements separated by `;`.
+///
+/// Every statement is an assignment `name = expression`, a function definition
+/// `name(param, ...) = expression` or an expression.
+///
+/// # Arguments
+///
+/// * `input` - The string slice containing the script
+///
+/// # Returns
+/// A Result containing the statements in the order they appear or a ParseError if any of
+/// them is invalid.
+/// # Examples
+/// ```
+/// use math_lib::parse_script;
+///
+/// let statements = parse_script("a = 3; b = a^2; b + 1").unwrap();
+/// assert_eq!(statements.len(), 3);
+/// assert_eq!(statements[1].to_string(), "b = a^2");
+/// ```
+pub fn parse_script(input: &str) -> Result<Vec<Statement>, ParseError> {
+    let options = ParserOptions::default();
+    build_statements(tokenize(input, &options)?, &options)
+}
 
-    #[test]
-    fn single_number() {
-        assert_eq!(
+/// Parses and executes a script of statements separated by `;`.
+///
+/// Statements are executed in order and assignments and function definitions update
+/// `context`, so later statements and the caller see the new bindings. If any statement
+/// fails, `context` is left unchanged. The script is parsed with the [`ParserOptions`] set
+/// on `context`.
+///
+/// # Arguments
+///
+/// * `input` - The string slice containing the script
+/// * `context` - The context providing and receiving the values of variables
+///
+/// # Returns
+/// A Result containing the value of the last statement that has one or a ParseError if
+/// the script is invalid, has no statement with a value, or one of its statements cannot
+/// be evaluated.
+/// # Examples
+/// ```
+/// use math_lib::{evaluate_script, Context};
+///
+/// let mut context = Context::new();
+/// assert_eq!(evaluate_script("a = 3; b = a^2; b + 1", &mut context), Ok(10.));
+/// assert_eq!(context.variable("b"), Some(9.));
+///
+/// assert_eq!(evaluate_script("f(x, y) = x^2 + y; f(3, 4)", &mut context), Ok(13.));
+/// assert!(context.function("f").is_some());
+/// ```
+pub fn evaluate_script(input: &str, context: &mut Context) -> Result<f64, ParseError> {
+    let options = context.parser_options();
+    let statements = build_statements(tokenize(input, options)?, options)?;
+
+    let mut scope = context.clone();
+    let mut result = None;
+    for statement in &statements {
+        result = statement.execute(&mut scope)?.or(result);
+    }
+    let result = result.ok_or(ParseError::InvalidExpression { span: Span::new(input.len(), input.len()) })?;
+    *context = scope;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tokenize_tests {
+    use super::{tokenize, Constant, Token, Operator, ParseError, ParserOptions, Span, SpannedToken};
+
+    fn token_eoi() -> Token { Token::Operator(Operator::EndOfInput) }
+
+    // Tokenizes the input and drops the spans
+    fn tokens(input: &str) -> Result<Vec<Token>, ParseError> {
+        tokenize(input, &ParserOptions::default()).map(|tokens| tokens.into_iter().map(|token| token.token).collect())
+    }
+
+    #[test]
+    fn single_number() {
+        assert_eq!(
             Ok(vec![Token::Operand(1.), token_eoi()]),
-            tokenize("1")
+            tokens("1")
         );
     }
 
@@ -444,7 +1119,7 @@ mod tokenize_tests {
     fn number_with_decimal() {
         assert_eq!(
             Ok(vec![Token::Operand(1.5), token_eoi()]),
-            tokenize("1.5")
+            tokens("1.5")
         );
     }
 
@@ -452,7 +1127,7 @@ mod tokenize_tests {
     fn number_with_comma() {
         assert_eq!(
             Ok(vec![Token::Operand(1.5), token_eoi()]),
-            tokenize("1,5")
+            tokens("1,5")
         );
     }
 
@@ -471,7 +1146,6 @@ mod tokenize_tests {
                 Token::Operator(Operator::OpenParen),
                 Token::Operand(3.),
                 Token::Operator(Operator::Factorial),
-                Token::Operand(1.), // Implicit operand for factorial
                 Token::Operator(Operator::CloseParen),
                 Token::Operator(Operator::Minus),
                 Token::Operand(5.),
@@ -483,7 +1157,7 @@ mod tokenize_tests {
                 Token::Operator(Operator::CloseParen),
                 token_eoi()
             ]),
-            tokenize("(3-1)*2+(3!)-5√(8^5)"),
+            tokens("(3-1)*2+(3!)-5√(8^5)"),
         );
     }
 
@@ -493,10 +1167,9 @@ mod tokenize_tests {
             Ok(vec![
                 Token::Operand(5.),
                 Token::Operator(Operator::Factorial),
-                Token::Operand(1.), // Implicit operand for factorial
                 token_eoi(),
             ]),
-            tokenize("5!")
+            tokens("5!")
         );
     }
 
@@ -504,64 +1177,196 @@ mod tokenize_tests {
     #[test]
     fn with_multiple_decimals() {
         assert_eq!(
-            Err("Failed to parse number".to_string()),
-            tokenize("1.5.5")
+            Err(ParseError::MalformedNumber { span: Span::new(0, 5) }),
+            tokens("1.5.5")
         );
     }
 
     #[test]
     fn invalid_character() {
         assert_eq!(
-            Err("Invalid character in input: $".to_string()),
-            tokenize("1$")
+            Err(ParseError::UnexpectedChar { ch: '$', span: Span::new(1, 2) }),
+            tokens("1$")
         );
     }
-}
 
-#[cfg(test)]
-mod postfix_tests {
-    use super::{to_postfix, Token, Operator};
+    #[test]
+    fn identifiers() {
+        assert_eq!(
+            Ok(vec![
+                Token::Identifier("rate".to_string()),
+                Token::Operator(Operator::Multiply),
+                Token::Identifier("hours_2".to_string()),
+                Token::Operator(Operator::Plus),
+                Token::Operand(2.),
+                Token::Identifier("x".to_string()),
+                token_eoi(),
+            ]),
+            tokens("rate*hours_2+2x")
+        );
+    }
 
-    fn token_eoi() -> Token { Token::Operator(Operator::EndOfInput) }
+    #[test]
+    fn constants() {
+        assert_eq!(
+            Ok(vec![
+                Token::Constant(Constant::Pi),
+                Token::Operator(Operator::Plus),
+                Token::Constant(Constant::Tau),
+                Token::Operator(Operator::Plus),
+                Token::Constant(Constant::E),
+                Token::Operator(Operator::Plus),
+                Token::Constant(Constant::Phi),
+                Token::Operator(Operator::Plus),
+                Token::Identifier("pie".to_string()),
+                token_eoi(),
+            ]),
+            tokens("π+tau+e+φ+pie")
+        );
+    }
 
     #[test]
-    fn single_number() {
+    fn coefficient_of_constant() {
         assert_eq!(
-            Ok(vec![Token::Operand(1.)]),
-            to_postfix(vec![Token::Operand(1.), token_eoi()])
+            Ok(vec![
+                SpannedToken::operand(2., "2".to_string(), Span::new(0, 1)),
+                SpannedToken::new(Token::Constant(Constant::Pi), Span::new(1, 3)),
+                SpannedToken::new(Token::Operator(Operator::EndOfInput), Span::new(3, 3)),
+            ]),
+            tokenize("2π", &ParserOptions::default())
         );
     }
 
     #[test]
-    fn simple_addition() {
+    fn function_call() {
         assert_eq!(
-            Ok(vec![Token::Operand(1.), Token::Operand(2.), Token::Operator(Operator::Plus)]),
-            to_postfix(vec![Token::Operand(1.), Token::Operator(Operator::Plus), Token::Operand(2.), token_eoi()])
+            Ok(vec![
+                Token::Function("f".to_string()),
+                Token::Operand(1.5),
+                Token::Comma,
+                Token::Operator(Operator::OpenParen),
+                Token::Operand(2.5),
+                Token::Operator(Operator::CloseParen),
+                Token::Comma,
+                Token::Identifier("x".to_string()),
+                Token::Operator(Operator::CloseParen),
+                Token::Operand(0.5),
+                token_eoi(),
+            ]),
+            tokens("f(1.5,(2,5), x)0,5")
         );
     }
 
     #[test]
-    fn large_composite_equation() {
-        // (3-1)*2+(3!)-5√(8^5)
+    fn spans_of_tokens() {
+        assert_eq!(
+            Ok(vec![
+                SpannedToken::operand(12.5, "12.5".to_string(), Span::new(0, 4)),
+                SpannedToken::new(Token::Operator(Operator::Root), Span::new(4, 7)),
+                SpannedToken::operand(3., "3".to_string(), Span::new(7, 8)),
+                SpannedToken::new(Token::Operator(Operator::Factorial), Span::new(8, 9)),
+                SpannedToken::new(Token::Operator(Operator::EndOfInput), Span::new(9, 9)),
+            ]),
+            tokenize("12,5√3!", &ParserOptions::default())
+        );
+    }
+
+    #[test]
+    fn invalid_multibyte_character() {
+        assert_eq!(
+            Err(ParseError::UnexpectedChar { ch: '€', span: Span::new(2, 5) }),
+            tokens("1+€")
+        );
+    }
+
+    #[test]
+    fn prefix_operators() {
         assert_eq!(
             Ok(vec![
-                Token::Operand(3.),
-                Token::Operand(1.),
                 Token::Operator(Operator::Minus),
                 Token::Operand(2.),
                 Token::Operator(Operator::Multiply),
-                Token::Operand(3.),
-                Token::Operand(1.), // Implicit operand for factorial
-                Token::Operator(Operator::Factorial),
                 Token::Operator(Operator::Plus),
-                Token::Operand(5.),
-                Token::Operand(8.),
-                Token::Operand(5.),
-                Token::Operator(Operator::Power),
                 Token::Operator(Operator::Root),
+                Token::Operand(4.),
+                Token::Operator(Operator::Minus),
+                Token::Operator(Operator::OpenParen),
+                Token::Operator(Operator::Minus),
+                Token::Operand(1.),
+                Token::Operator(Operator::CloseParen),
+                token_eoi()
+            ]),
+            tokens("-2*+√4-(-1)")
+        );
+    }
+
+    #[test]
+    fn exponents() {
+        assert_eq!(
+            Ok(vec![
+                SpannedToken::operand(1.5e-9, "1.5E-9".to_string(), Span::new(0, 6)),
+                SpannedToken::new(Token::Operator(Operator::Plus), Span::new(6, 7)),
+                SpannedToken::operand(6.022e23, "6.022e23".to_string(), Span::new(7, 15)),
+                SpannedToken::new(Token::Operator(Operator::EndOfInput), Span::new(15, 15)),
+            ]),
+            tokenize("1.5E-9+6.022e23", &ParserOptions::default())
+        );
+    }
+
+    #[test]
+    fn e_without_digits_is_constant() {
+        assert_eq!(
+            Ok(vec![
+                Token::Operand(2.),
+                Token::Constant(Constant::E),
                 Token::Operator(Operator::Minus),
+                Token::Operand(2.),
+                Token::Identifier("ex".to_string()),
+                token_eoi(),
             ]),
-            to_postfix(vec![
+            tokens("2e -2ex")
+        );
+    }
+}
+
+#[cfg(test)]
+mod grammar_tests {
+    use super::{Operator, OperatorRegistry, ParseError, Parser, ParserOptions, Span, SpannedToken, Token};
+
+    fn token_eoi() -> Token { Token::Operator(Operator::EndOfInput) }
+
+    // Parses the tokens and prints the tree fully parenthesized, giving the n-th token the span n..n+1
+    fn tree(tokens: Vec<Token>) -> Result<String, ParseError> {
+        let tokens = tokens
+            .into_iter()
+            .enumerate()
+            .map(|(i, token)| SpannedToken::new(token, Span::new(i, i + 1)))
+            .collect();
+        Parser::new(tokens, OperatorRegistry::standard(), &ParserOptions::default()).parse().map(|expr| expr.to_string())
+    }
+
+    #[test]
+    fn single_number() {
+        assert_eq!(
+            Ok("1".to_string()),
+            tree(vec![Token::Operand(1.), token_eoi()])
+        );
+    }
+
+    #[test]
+    fn simple_addition() {
+        assert_eq!(
+            Ok("1+2".to_string()),
+            tree(vec![Token::Operand(1.), Token::Operator(Operator::Plus), Token::Operand(2.), token_eoi()])
+        );
+    }
+
+    #[test]
+    fn large_composite_equation() {
+        // (3-1)*2+(3!)-5√(8^5)
+        assert_eq!(
+            Ok("(((3-1)*2)+(3!))-(5√(8^5))".to_string()),
+            tree(vec![
                 Token::Operator(Operator::OpenParen),
                 Token::Operand(3.),
                 Token::Operator(Operator::Minus),
@@ -573,7 +1378,6 @@ mod postfix_tests {
                 Token::Operator(Operator::OpenParen),
                 Token::Operand(3.),
                 Token::Operator(Operator::Factorial),
-                Token::Operand(1.), // Implicit operand for factorial
                 Token::Operator(Operator::CloseParen),
                 Token::Operator(Operator::Minus),
                 Token::Operand(5.),
@@ -588,25 +1392,143 @@ mod postfix_tests {
         );
     }
 
+    #[test]
+    fn function_call() {
+        // 2*f(1+3, g())
+        assert_eq!(
+            Ok("2*f(1+3, g())".to_string()),
+            tree(vec![
+                Token::Operand(2.),
+                Token::Operator(Operator::Multiply),
+                Token::Function("f".to_string()),
+                Token::Operand(1.),
+                Token::Operator(Operator::Plus),
+                Token::Operand(3.),
+                Token::Comma,
+                Token::Function("g".to_string()),
+                Token::Operator(Operator::CloseParen),
+                Token::Operator(Operator::CloseParen),
+                token_eoi(),
+            ])
+        );
+    }
+
+    #[test]
+    fn negation_binds_looser_than_power() {
+        // -2^2
+        assert_eq!(
+            Ok("-(2^2)".to_string()),
+            tree(vec![
+                Token::Operator(Operator::Minus),
+                Token::Operand(2.),
+                Token::Operator(Operator::Power),
+                Token::Operand(2.),
+                token_eoi()
+            ])
+        );
+    }
+
+    #[test]
+    fn negation_binds_tighter_than_multiplication() {
+        // -2*3
+        assert_eq!(
+            Ok("(-2)*3".to_string()),
+            tree(vec![
+                Token::Operator(Operator::Minus),
+                Token::Operand(2.),
+                Token::Operator(Operator::Multiply),
+                Token::Operand(3.),
+                token_eoi()
+            ])
+        );
+    }
+
     // These are invalid operations
     #[test]
     fn invalid_expression() {
         assert_eq!(
-            Err("Invalid expression".to_string()),
-            to_postfix(vec![Token::Operand(1.), Token::Operator(Operator::OpenParen), token_eoi()])
+            Err(ParseError::InvalidExpression { span: Span::new(1, 2) }),
+            tree(vec![Token::Operand(1.), Token::Operand(2.), token_eoi()])
+        );
+    }
+
+    #[test]
+    fn missing_operand() {
+        assert_eq!(
+            Err(ParseError::InvalidExpression { span: Span::new(1, 2) }),
+            tree(vec![Token::Operand(1.), Token::Operator(Operator::Plus), token_eoi()])
+        );
+    }
+
+    #[test]
+    fn operator_without_prefix_form() {
+        assert_eq!(
+            Err(ParseError::InvalidExpression { span: Span::new(0, 1) }),
+            tree(vec![Token::Operator(Operator::Multiply), Token::Operand(1.), token_eoi()])
+        );
+    }
+
+    #[test]
+    fn empty_argument() {
+        assert_eq!(
+            Err(ParseError::InvalidExpression { span: Span::new(3, 4) }),
+            tree(vec![
+                Token::Function("f".to_string()),
+                Token::Operand(1.),
+                Token::Comma,
+                Token::Operator(Operator::CloseParen),
+                token_eoi(),
+            ])
+        );
+    }
+
+    #[test]
+    fn unclosed_function_call() {
+        assert_eq!(
+            Err(ParseError::UnbalancedParen { span: Span::new(0, 1) }),
+            tree(vec![Token::Function("f".to_string()), Token::Operand(1.), token_eoi()])
+        );
+    }
+
+    #[test]
+    fn unclosed_paren() {
+        assert_eq!(
+            Err(ParseError::UnbalancedParen { span: Span::new(0, 1) }),
+            tree(vec![Token::Operator(Operator::OpenParen), Token::Operand(1.), token_eoi()])
+        );
+    }
+
+    #[test]
+    fn unopened_paren() {
+        assert_eq!(
+            Err(ParseError::UnbalancedParen { span: Span::new(1, 2) }),
+            tree(vec![Token::Operand(1.), Token::Operator(Operator::CloseParen), token_eoi()])
         );
     }
 }
 
+
 #[cfg(test)]
 mod evaluate_tests {
-    use super::{evaluate_expression, Token, Operator};
+    use super::{Operator, OperatorRegistry, ParseError, Parser, ParserOptions, Span, SpannedToken, Token};
+
+    // Parses the tokens and evaluates the tree, giving the n-th token the span n..n+1
+    fn evaluate(tokens: Vec<Token>) -> Result<f64, ParseError> {
+        let mut tokens: Vec<SpannedToken> = tokens
+            .into_iter()
+            .enumerate()
+            .map(|(i, token)| SpannedToken::new(token, Span::new(i, i + 1)))
+            .collect();
+        let end = tokens.len();
+        tokens.push(SpannedToken::new(Token::Operator(Operator::EndOfInput), Span::new(end, end)));
+        Parser::new(tokens, OperatorRegistry::standard(), &ParserOptions::default()).parse()?.evaluate()
+    }
 
     #[test]
     fn add_two_values() {
         assert_eq!(
             Ok(2.),
-            evaluate_expression(vec![Token::Operand(1.), Token::Operand(1.), Token::Operator(Operator::Plus)])
+            evaluate(vec![Token::Operand(1.), Token::Operator(Operator::Plus), Token::Operand(1.)])
         );
     }
 
@@ -614,119 +1536,1030 @@ mod evaluate_tests {
     fn negative_sqrt() {
         assert_eq!(
             Ok(0.25),
-            evaluate_expression(vec![Token::Operand(-2.), Token::Operand(16.), Token::Operator(Operator::Root)])
+            evaluate(vec![Token::Operand(-2.), Token::Operator(Operator::Root), Token::Operand(16.)])
+        );
+    }
+
+    #[test]
+    fn square_root_of_16_without_one_operand() {
+        assert_eq!(
+            Ok(4.),
+            evaluate(vec![
+                Token::Operator(Operator::Root),
+                Token::Operand(16.)
+            ])
+        );
+    }
+
+    #[test]
+    fn add_multiple_values() {
+        assert_eq!(
+            Ok(6.),
+            evaluate(vec![
+                Token::Operand(1.),
+                Token::Operator(Operator::Plus),
+                Token::Operand(2.),
+                Token::Operator(Operator::Plus),
+                Token::Operand(3.),
+            ])
         );
     }
 
     #[test]
-    fn square_root_of_16_without_one_operand() {
-        assert_eq!(
-            Ok(4.),
-            evaluate_expression(vec![
-                Token::Operand(16.),
-                Token::Operator(Operator::Root)
-            ])
-        );
+    fn large_composite_equation() {
+        assert!((2. - evaluate(vec![
+            Token::Operator(Operator::OpenParen),
+            Token::Operand(3.),
+            Token::Operator(Operator::Minus),
+            Token::Operand(1.),
+            Token::Operator(Operator::CloseParen),
+            Token::Operator(Operator::Multiply),
+            Token::Operand(2.),
+            Token::Operator(Operator::Plus),
+            Token::Operand(3.),
+            Token::Operator(Operator::Factorial),
+            Token::Operator(Operator::Minus),
+            Token::Operand(5.),
+            Token::Operator(Operator::Root),
+            Token::Operator(Operator::OpenParen),
+            Token::Operand(8.),
+            Token::Operator(Operator::Power),
+            Token::Operand(5.),
+            Token::Operator(Operator::CloseParen),
+        ]).unwrap()).abs() < 0.00000001);
+    }
+
+    #[test]
+    fn negative_of_addition() {
+        assert_eq!(
+            Ok(-7.),
+            evaluate(vec![
+                Token::Operator(Operator::Minus),
+                Token::Operator(Operator::OpenParen),
+                Token::Operand(4.),
+                Token::Operator(Operator::Plus),
+                Token::Operand(3.),
+                Token::Operator(Operator::CloseParen)
+            ])
+        );
+    }
+
+    #[test]
+    fn positive_of_addition() {
+        assert_eq!(
+            Ok(7.),
+            evaluate(vec![
+                Token::Operand(4.),
+                Token::Operator(Operator::Plus),
+                Token::Operand(3.)
+            ])
+        );
+    }
+
+    // These are invalid operations
+    #[test]
+    fn divide_by_zero() {
+        assert_eq!(
+            Err(ParseError::DivisionByZero { span: Span::new(1, 2) }),
+            evaluate(vec![Token::Operand(1.), Token::Operator(Operator::Divide), Token::Operand(0.)])
+        );
+    }
+
+    #[test]
+    fn invalid_expression() {
+        assert_eq!(
+            Err(ParseError::InvalidExpression { span: Span::new(1, 2) }),
+            evaluate(vec![Token::Operand(1.), Token::Operator(Operator::Plus)])
+        );
+    }
+
+    #[test]
+    fn missing_operator() {
+        assert_eq!(
+            Err(ParseError::InvalidExpression { span: Span::new(1, 2) }),
+            evaluate(vec![Token::Operand(1.), Token::Operand(1.)])
+        );
+    }
+
+    #[test]
+    fn negative_factorial() {
+        assert_eq!(
+            Err(ParseError::DomainError {
+                message: "Cannot take factorial of a negative whole number".to_string(),
+                span: Span::new(1, 2),
+            }),
+            evaluate(vec![Token::Operand(-1.), Token::Operator(Operator::Factorial)])
+        );
+    }
+
+    #[test]
+    fn root_of_negative_number() {
+        assert_eq!(
+            Err(ParseError::DomainError {
+                message: "Cannot take the root of a negative number".to_string(),
+                span: Span::new(1, 2),
+            }),
+            evaluate(vec![Token::Operand(2.), Token::Operator(Operator::Root), Token::Operand(-4.)])
+        );
+    }
+}
+
+
+#[cfg(test)]
+mod ast_tests {
+    use super::{parse_to_ast, Expr, Operator, ParseError, Span};
+
+    #[test]
+    fn single_number() {
+        assert_eq!(
+            Ok(Expr::Number { value: 1.5, literal: Some("1.5".to_string()), span: Span::new(0, 3) }),
+            parse_to_ast("1.5")
+        );
+    }
+
+    #[test]
+    fn binary_with_spans() {
+        assert_eq!(
+            Ok(Expr::Binary {
+                op: Operator::Minus,
+                left: Box::new(Expr::Number { value: 10., literal: Some("10".to_string()), span: Span::new(0, 2) }),
+                right: Box::new(Expr::Number { value: 4., literal: Some("4".to_string()), span: Span::new(3, 4) }),
+                add_on: false,
+                span: Span::new(2, 3),
+            }),
+            parse_to_ast("10-4")
+        );
+    }
+
+    #[test]
+    fn unary_operators() {
+        assert_eq!(
+            Ok(Expr::Unary {
+                op: Operator::Minus,
+                operand: Box::new(Expr::Unary {
+                    op: Operator::Factorial,
+                    operand: Box::new(Expr::Number {
+                        value: 3.,
+                        literal: Some("3".to_string()),
+                        span: Span::new(1, 2),
+                    }),
+                    span: Span::new(2, 3),
+                }),
+                span: Span::new(0, 1),
+            }),
+            parse_to_ast("-3!")
+        );
+    }
+
+    #[test]
+    fn square_root() {
+        assert_eq!(
+            Ok(Expr::Unary {
+                op: Operator::Root,
+                operand: Box::new(Expr::Number { value: 16., literal: Some("16".to_string()), span: Span::new(3, 5) }),
+                span: Span::new(0, 3),
+            }),
+            parse_to_ast("√16")
+        );
+    }
+
+    #[test]
+    fn print_and_parse_again() {
+        let expr = parse_to_ast("(3-1)*2+(3!)-5√(8^5)").unwrap();
+        let printed = expr.to_string();
+        assert_eq!("(((3-1)*2)+(3!))-(5√(8^5))", printed);
+        assert_eq!(expr.evaluate(), parse_to_ast(&printed).unwrap().evaluate());
+    }
+
+    #[test]
+    fn evaluate_repeatedly() {
+        let expr = parse_to_ast("2^10").unwrap();
+        assert_eq!(Ok(1024.), expr.evaluate());
+        assert_eq!(Ok(1024.), expr.evaluate());
+    }
+
+    // These are invalid operations
+    #[test]
+    fn evaluation_error_is_not_a_parse_error() {
+        let expr = parse_to_ast("1/0").unwrap();
+        assert_eq!(Err(ParseError::DivisionByZero { span: Span::new(1, 2) }), expr.evaluate());
+    }
+
+    #[test]
+    fn missing_operand() {
+        assert_eq!(
+            Err(ParseError::InvalidExpression { span: Span::new(1, 2) }),
+            parse_to_ast("2*")
+        );
+    }
+}
+
+#[cfg(test)]
+mod context_tests {
+    use super::{evaluate_with, parse_to_ast, Context, ImplicitMultiplication, ParseError, ParserOptions, Span};
+
+    fn billing_context() -> Context {
+        let mut context = Context::new();
+        context.set_variable("rate", 25.);
+        context.set_variable("hours", 8.);
+        context.set_variable("fee", 10.);
+        context
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(
+            Ok(210.),
+            evaluate_with("rate*hours+fee", &billing_context())
+        );
+    }
+
+    #[test]
+    fn variables_in_parentheses() {
+        assert_eq!(
+            Ok(450.),
+            evaluate_with("rate*(hours+10)", &billing_context())
+        );
+    }
+
+    #[test]
+    fn same_tree_different_contexts() {
+        let expr = parse_to_ast("x^2").unwrap();
+        let mut context = Context::new();
+        context.set_variable("x", 3.);
+        assert_eq!(Ok(9.), expr.evaluate_with(&context));
+        context.set_variable("x", 4.);
+        assert_eq!(Ok(16.), expr.evaluate_with(&context));
+    }
+
+    // These are invalid operations
+    #[test]
+    fn unknown_variable() {
+        assert_eq!(
+            Err(ParseError::UnknownVariable { name: "tax".to_string(), span: Span::new(11, 14) }),
+            evaluate_with("rate*hours+tax", &billing_context())
+        );
+    }
+
+    #[test]
+    fn two_variables_in_a_row() {
+        let mut context = billing_context();
+        assert_eq!(Ok(250.), evaluate_with("rate fee", &context));
+        context.set_parser_options(ParserOptions { implicit_multiplication: ImplicitMultiplication::Disabled, ..ParserOptions::default() });
+        assert_eq!(
+            Err(ParseError::InvalidExpression { span: Span::new(5, 8) }),
+            evaluate_with("rate fee", &context)
+        );
+    }
+
+    #[test]
+    fn variable_called_as_function() {
+        assert_eq!(
+            Err(ParseError::UnknownFunction { name: "rate".to_string(), span: Span::new(0, 4) }),
+            evaluate_with("rate(fee)", &billing_context())
+        );
+    }
+}
+
+#[cfg(test)]
+mod script_tests {
+    use super::{evaluate_script, parse_script, parse_to_ast, Context, ParseError, Span};
+
+    #[test]
+    fn value_of_last_statement() {
+        let mut context = Context::new();
+        assert_eq!(
+            Ok(10.),
+            evaluate_script("a = 3; b = a^2; b + 1", &mut context)
+        );
+        assert_eq!(Some(3.), context.variable("a"));
+        assert_eq!(Some(9.), context.variable("b"));
+    }
+
+    #[test]
+    fn assignment_is_the_last_statement() {
+        let mut context = Context::new();
+        assert_eq!(Ok(12.), evaluate_script("x = 4*3;", &mut context));
+        assert_eq!(Some(12.), context.variable("x"));
+    }
+
+    #[test]
+    fn reassignment() {
+        let mut context = Context::new();
+        context.set_variable("total", 1.);
+        assert_eq!(
+            Ok(7.),
+            evaluate_script("total = total + 2; total = total * 2; total + 1", &mut context)
+        );
+        assert_eq!(Some(6.), context.variable("total"));
+    }
+
+    #[test]
+    fn whitespace_and_newlines() {
+        let mut context = Context::new();
+        assert_eq!(
+            Ok(2.),
+            evaluate_script("  width = 4 ;\n height = 0,5\n;\twidth * height ", &mut context)
+        );
+    }
+
+    #[test]
+    fn printed_statements() {
+        let statements = parse_script("a=1;b=a+2").unwrap();
+        let printed: Vec<String> = statements.iter().map(|statement| statement.to_string()).collect();
+        assert_eq!(vec!["a = 1", "b = a+2"], printed);
+    }
+
+    // These are invalid operations
+    #[test]
+    fn failing_statement_keeps_context() {
+        let mut context = Context::new();
+        assert_eq!(
+            Err(ParseError::DivisionByZero { span: Span::new(12, 13) }),
+            evaluate_script("a = 1; b = a/0", &mut context)
+        );
+        assert_eq!(None, context.variable("a"));
+    }
+
+    #[test]
+    fn empty_script() {
+        assert_eq!(
+            Err(ParseError::InvalidExpression { span: Span::new(3, 3) }),
+            evaluate_script(" ; ", &mut Context::new())
+        );
+    }
+
+    #[test]
+    fn missing_value() {
+        assert_eq!(
+            Err(ParseError::InvalidExpression { span: Span::new(3, 3) }),
+            parse_script("a =; 1")
+        );
+    }
+
+    #[test]
+    fn assignment_to_expression() {
+        assert_eq!(
+            Err(ParseError::InvalidExpression { span: Span::new(4, 5) }),
+            parse_script("a+1 = 2")
+        );
+    }
+
+    #[test]
+    fn chained_assignment() {
+        assert_eq!(
+            Err(ParseError::InvalidExpression { span: Span::new(6, 7) }),
+            parse_script("a = b = 2")
+        );
+    }
+
+    #[test]
+    fn statements_in_single_expression() {
+        assert_eq!(
+            Err(ParseError::InvalidExpression { span: Span::new(1, 2) }),
+            parse_to_ast("1;2")
+        );
+    }
+}
+
+#[cfg(test)]
+mod function_tests {
+    use super::{evaluate_script, evaluate_with, parse, parse_script, Context, Expr, ParseError, Span, Statement};
+    use crate::functions::Arity;
+
+    #[test]
+    fn define_and_call() {
+        let mut context = Context::new();
+        assert_eq!(
+            Ok(13.),
+            evaluate_script("f(x, y) = x^2 + y; f(3, 4)", &mut context)
+        );
+        assert_eq!(Ok(7.), evaluate_with("f(2, 3)", &context));
+    }
+
+    #[test]
+    fn definition_statement() {
+        let statements = parse_script("area(w, h) = w*h").unwrap();
+        match &statements[0] {
+            Statement::Define { name, params, body, span } => {
+                assert_eq!("area", name);
+                assert_eq!(&vec!["w".to_string(), "h".to_string()], params);
+                assert!(matches!(body, Expr::Binary { .. }));
+                assert_eq!(Span::new(0, 4), *span);
+            }
+            statement => panic!("Expected a definition, found {statement:?}"),
+        }
+        assert_eq!("area(w, h) = w*h", statements[0].to_string());
+    }
+
+    #[test]
+    fn function_without_parameters() {
+        assert_eq!(
+            Ok(42.),
+            evaluate_script("answer() = 6*7; answer()", &mut Context::new())
+        );
+    }
+
+    #[test]
+    fn parameters_shadow_variables() {
+        let mut context = Context::new();
+        assert_eq!(
+            Ok(15.),
+            evaluate_script("x = 10; f(x) = x*3; f(5)", &mut context)
+        );
+        assert_eq!(Some(10.), context.variable("x"));
+    }
+
+    #[test]
+    fn body_sees_context_variables() {
+        assert_eq!(
+            Ok(21.),
+            evaluate_script("fee = 1; price(n) = n*4 + fee; price(5)", &mut Context::new())
+        );
+    }
+
+    #[test]
+    fn body_does_not_see_caller_parameters() {
+        assert_eq!(
+            Err(ParseError::UnknownVariable { name: "x".to_string(), span: Span::new(7, 8) }),
+            evaluate_script("g(y) = x + y; f(x) = g(1); f(2)", &mut Context::new())
+        );
+    }
+
+    #[test]
+    fn nested_calls() {
+        assert_eq!(
+            Ok(82.),
+            evaluate_script("sq(x) = x^2; f(a, b) = sq(a) + sq(b); f(sq(3), 1)", &mut Context::new())
+        );
+    }
+
+    #[test]
+    fn decimal_comma_outside_of_call() {
+        assert_eq!(
+            Ok(3.),
+            evaluate_script("f(a, b) = a + b; 0,5 * f(2,4)", &mut Context::new())
+        );
+    }
+
+    #[test]
+    fn builtin_functions() {
+        assert_eq!(Ok(12.), parse("sqrt(16)+root(3,8)*max(1,4,2)"));
+        assert_eq!(Ok(2.), parse("abs(min(-2, 5))"));
+        assert_eq!(Ok(10.), parse("sum(1, 2, 3, 4)"));
+    }
+
+    #[test]
+    fn native_closure() {
+        let mut context = Context::new();
+        let rate = 0.21;
+        context.register_function("vat", Arity::Fixed(1), move |args| Ok(args[0] * rate));
+        context.register_function("count", Arity::Variadic { min: 0 }, |args| Ok(args.len() as f64));
+        assert_eq!(Ok(21.), evaluate_with("vat(100)", &context));
+        assert_eq!(Ok(0.), evaluate_with("count()", &context));
+        assert_eq!(Ok(3.), evaluate_with("count(1, vat(2), 3)", &context));
+    }
+
+    #[test]
+    fn user_function_replaces_builtin() {
+        assert_eq!(
+            Ok(-3.),
+            evaluate_script("abs(x) = -x; abs(3)", &mut Context::new())
+        );
+    }
+
+    // These are invalid operations
+    #[test]
+    fn native_error() {
+        let mut context = Context::new();
+        context.register_function("positive", Arity::Fixed(1), |args| {
+            if args[0] > 0. { Ok(args[0]) } else { Err("Expected a positive number".to_string()) }
+        });
+        assert_eq!(
+            Err(ParseError::DomainError { message: "Expected a positive number".to_string(), span: Span::new(2, 10) }),
+            evaluate_with("1+positive(0)", &context)
+        );
+    }
+
+    #[test]
+    fn too_few_arguments_for_variadic() {
+        assert_eq!(
+            Err(ParseError::ArityMismatch { name: "max".to_string(), expected: Arity::Variadic { min: 1 }, found: 0, span: Span::new(0, 3) }),
+            parse("max()")
+        );
+    }
+
+    #[test]
+    fn unknown_function() {
+        assert_eq!(
+            Err(ParseError::UnknownFunction { name: "g".to_string(), span: Span::new(2, 3) }),
+            evaluate_with("1+g(2)", &Context::new())
+        );
+    }
+
+    #[test]
+    fn wrong_number_of_arguments() {
+        assert_eq!(
+            Err(ParseError::ArityMismatch { name: "f".to_string(), expected: Arity::Fixed(2), found: 1, span: Span::new(17, 18) }),
+            evaluate_script("f(x, y) = x + y; f(1)", &mut Context::new())
+        );
+    }
+
+    #[test]
+    fn endless_recursion() {
+        let mut context = Context::new();
+        context.set_max_call_depth(16);
+        assert_eq!(
+            Err(ParseError::RecursionLimit { name: "f".to_string(), span: Span::new(7, 8) }),
+            evaluate_script("f(x) = f(x + 1); f(0)", &mut context)
+        );
+        assert!(context.function("f").is_none());
+    }
+
+    #[test]
+    fn default_recursion_limit() {
+        assert!(matches!(
+            evaluate_script("f(x) = 1 + f(x); f(0)", &mut Context::new()),
+            Err(ParseError::RecursionLimit { .. })
+        ));
+    }
+
+    #[test]
+    fn repeated_parameter() {
+        assert_eq!(
+            Err(ParseError::InvalidExpression { span: Span::new(5, 6) }),
+            parse_script("f(x, x) = x")
+        );
+    }
+
+    #[test]
+    fn expression_as_parameter() {
+        assert_eq!(
+            Err(ParseError::InvalidExpression { span: Span::new(2, 3) }),
+            parse_script("f(2) = 3")
+        );
+    }
+
+    #[test]
+    fn only_definitions() {
+        assert_eq!(
+            Err(ParseError::InvalidExpression { span: Span::new(8, 8) }),
+            evaluate_script("f(x) = x", &mut Context::new())
+        );
+    }
+}
+
+#[cfg(test)]
+mod constant_tests {
+    use std::f64::consts::{E, PI, TAU};
+
+    use super::{parse, parse_script, parse_to_ast, Constant, Expr, ParseError, Span};
+
+    #[test]
+    fn full_precision() {
+        assert_eq!(Ok(PI), parse("π"));
+        assert_eq!(Ok(PI), parse("pi"));
+        assert_eq!(Ok(E), parse("e"));
+        assert_eq!(Ok(TAU), parse("tau"));
+    }
+
+    #[test]
+    fn coefficient() {
+        assert_eq!(Ok(2. * PI), parse("2π"));
+        assert_eq!(Ok(1.5 * E), parse("1,5e"));
+        assert_eq!(Ok(4. * PI + 1.), parse("2*2pi+1"));
+    }
+
+    #[test]
+    fn golden_ratio_identity() {
+        assert!((parse("φ^2-φ-1").unwrap()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn constant_in_tree() {
+        assert_eq!(
+            Ok(Expr::Constant { constant: Constant::Pi, span: Span::new(0, 2) }),
+            parse_to_ast("pi")
+        );
+        assert_eq!("2*π", parse_to_ast("2π").unwrap().to_string());
+    }
+
+    // These are invalid operations
+    #[test]
+    fn error_position_after_constant() {
+        assert_eq!(
+            Err(ParseError::DivisionByZero { span: Span::new(3, 4) }),
+            parse("2π/0")
+        );
+    }
+
+    #[test]
+    fn constant_cannot_be_assigned() {
+        assert_eq!(
+            Err(ParseError::InvalidExpression { span: Span::new(3, 4) }),
+            parse_script("pi = 3")
+        );
+    }
+}
+
+#[cfg(test)]
+mod trigonometry_tests {
+    use std::f64::consts::{FRAC_PI_4, PI};
+
+    use super::{evaluate_with, parse, Context, ParseError, Span};
+    use crate::functions::AngleMode;
+
+    fn context(angle_mode: AngleMode) -> Context {
+        let mut context = Context::new();
+        context.set_angle_mode(angle_mode);
+        context
+    }
+
+    fn assert_close(expected: f64, actual: Result<f64, ParseError>) {
+        let actual = actual.unwrap();
+        assert!((expected - actual).abs() < 1e-12, "expected {expected}, found {actual}");
+    }
+
+    #[test]
+    fn radians_by_default() {
+        assert_eq!(Ok(0.), parse("sin(π)"));
+        assert_eq!(Ok(-1.), parse("cos(pi)"));
+        assert_close(1., parse("tan(π/4)"));
+        assert_close(FRAC_PI_4, parse("atan(1)"));
+    }
+
+    #[test]
+    fn degrees() {
+        let context = context(AngleMode::Degrees);
+        assert_eq!(Ok(0.), evaluate_with("sin(180)", &context));
+        assert_eq!(Ok(1.), evaluate_with("sin(450)", &context));
+        assert_eq!(Ok(0.), evaluate_with("cos(-90)", &context));
+        assert_close(0.5, evaluate_with("sin(30)", &context));
+        assert_close(1., evaluate_with("tan(45)", &context));
+        assert_eq!(Ok(90.), evaluate_with("asin(1)", &context));
+        assert_eq!(Ok(180.), evaluate_with("acos(-1)", &context));
+        assert_close(60., evaluate_with("acos(0.5)", &context));
+        assert_eq!(Ok(-90.), evaluate_with("atan2(-1, 0)", &context));
+    }
+
+    #[test]
+    fn gradians() {
+        let context = context(AngleMode::Gradians);
+        assert_eq!(Ok(-1.), evaluate_with("sin(300)", &context));
+        assert_close(0.5f64.sqrt(), evaluate_with("cos(50)", &context));
+        assert_eq!(Ok(100.), evaluate_with("atan2(1, 0)", &context));
+    }
+
+    #[test]
+    fn same_expression_different_modes() {
+        assert_eq!(Ok(0.), evaluate_with("sin(2π)", &context(AngleMode::Radians)));
+        assert_close((2. * PI).to_radians().sin(), evaluate_with("sin(2π)", &context(AngleMode::Degrees)));
+    }
+
+    #[test]
+    fn hyperbolic() {
+        assert_close(1f64.sinh(), parse("sinh(1)"));
+        assert_close(2., parse("cosh(acosh(2))"));
+        assert_close(0.5, parse("tanh(atanh(0.5))"));
+        assert_close(3., parse("asinh(sinh(3))"));
+        // Hyperbolic functions do not take angles
+        assert_close(1f64.cosh(), evaluate_with("cosh(1)", &context(AngleMode::Degrees)));
+    }
+
+    // These are invalid operations
+    #[test]
+    fn inverse_outside_of_domain() {
+        assert_eq!(
+            Err(ParseError::DomainError {
+                message: "Cannot take asin of a number outside of [-1, 1]".to_string(),
+                span: Span::new(0, 4),
+            }),
+            parse("asin(2)")
+        );
+        assert!(parse("acosh(0.5)").is_err());
+        assert!(parse("atanh(1)").is_err());
+    }
+
+    #[test]
+    fn tangent_of_right_angle() {
+        assert_eq!(
+            Err(ParseError::DomainError {
+                message: "Cannot take tan of an odd multiple of a right angle".to_string(),
+                span: Span::new(2, 5),
+            }),
+            evaluate_with("1+tan(270)", &context(AngleMode::Degrees))
+        );
+    }
+}
+
+#[cfg(test)]
+mod logarithm_tests {
+    use std::f64::consts::E;
+
+    use super::{parse, ParseError, Span};
+
+    #[test]
+    fn natural_logarithm_and_exponential() {
+        assert_eq!(Ok(1.), parse("ln(e)"));
+        assert_eq!(Ok(2f64.exp()), parse("exp(2)"));
+        assert_eq!(Ok(E), parse("exp(1)"));
+        assert!((parse("ln(exp(3.5))").unwrap() - 3.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn fixed_bases() {
+        assert_eq!(Ok(5.), parse("log10(100000)"));
+        assert_eq!(Ok(-3.), parse("log2(0.125)"));
+    }
+
+    #[test]
+    fn arbitrary_base() {
+        assert_eq!(Ok(3.), parse("log(10, 1000)"));
+        assert_eq!(Ok(4.), parse("log(3, 81)"));
+        assert!((parse("log(2, 10)").unwrap() - 10f64.log2()).abs() < 1e-12);
+    }
+
+    // These are invalid operations
+    #[test]
+    fn logarithm_of_zero() {
+        assert_eq!(
+            Err(ParseError::DomainError {
+                message: "Cannot take the logarithm of a non-positive number".to_string(),
+                span: Span::new(2, 4),
+            }),
+            parse("1+ln(0)")
+        );
+    }
+
+    #[test]
+    fn base_one() {
+        assert_eq!(
+            Err(ParseError::DomainError {
+                message: "Cannot take the logarithm to a non-positive base or base 1".to_string(),
+                span: Span::new(0, 3),
+            }),
+            parse("log(1, 2)")
+        );
+    }
+}
+
+#[cfg(test)]
+mod gamma_tests {
+    use std::f64::consts::PI;
+
+    use super::{parse, ParseError, Span};
+
+    #[test]
+    fn non_integer_factorial() {
+        assert!((parse("2.5!").unwrap() - 3.323350970447842).abs() < 1e-12);
+        assert!((parse("(0-0.5)!").unwrap() - PI.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn factorial_overflow() {
+        assert_eq!(Ok(f64::INFINITY), parse("(10^15)!"));
+        assert_eq!(Ok(f64::INFINITY), parse("171!"));
+    }
+
+    #[test]
+    fn special_functions() {
+        assert_eq!(Ok(24.), parse("gamma(5)"));
+        assert!((parse("lgamma(100)").unwrap() - parse("ln(99!)").unwrap()).abs() < 1e-9);
+        assert!((parse("beta(2, 3)").unwrap() - 1. / 12.).abs() < 1e-15);
+    }
+
+    // These are invalid operations
+    #[test]
+    fn negative_integer_factorial() {
+        assert_eq!(
+            Err(ParseError::DomainError {
+                message: "Cannot take factorial of a negative whole number".to_string(),
+                span: Span::new(5, 6),
+            }),
+            parse("(0-3)!")
+        );
+    }
+}
+
+#[cfg(test)]
+mod unary_tests {
+    use super::{evaluate_script, parse, parse_to_ast, Context, ParseError, Span};
+
+    #[test]
+    fn negation_after_operator() {
+        assert_eq!(Ok(-6.), parse("2*-3"));
+        assert_eq!(Ok(0.5), parse("2^-1"));
+        assert_eq!(Ok(-1.), parse("3/-3"));
+    }
+
+    #[test]
+    fn negation_inside_larger_expression() {
+        assert_eq!(Ok(5.), parse("1+(-2)^2"));
+        assert_eq!(Ok(-1.), parse("max(-1, -2)"));
+        assert_eq!(Ok(-4.), parse("abs(-1)*-4"));
+    }
+
+    #[test]
+    fn repeated_prefix_operators() {
+        assert_eq!(Ok(5.), parse("--5"));
+        assert_eq!(Ok(-5.), parse("-+-+-5"));
+        assert_eq!(Ok(2.), parse("√√16"));
+    }
+
+    #[test]
+    fn unary_plus() {
+        assert_eq!(Ok(3.), parse("+3"));
+        assert_eq!(Ok(5.), parse("2++3"));
+        assert_eq!("+3", parse_to_ast("+3").unwrap().to_string());
+    }
+
+    #[test]
+    fn negation_precedence() {
+        // Negation binds looser than power and factorial, but tighter than multiplication
+        assert_eq!(Ok(-4.), parse("-2^2"));
+        assert_eq!(Ok(-6.), parse("-3!"));
+        assert_eq!(Ok(2.), parse("-2*-1"));
+        assert_eq!("(-2)*3", parse_to_ast("-2*3").unwrap().to_string());
+    }
+
+    #[test]
+    fn prefix_root_after_operator() {
+        assert_eq!(Ok(6.), parse("3*√4"));
+        assert_eq!(Ok(-2.), parse("-√4"));
+        assert_eq!(Ok(3.), parse("1+√4"));
+    }
+
+    #[test]
+    fn negation_in_statements() {
+        let mut context = Context::new();
+        assert_eq!(Ok(-9.), evaluate_script("x = -3; f(y) = -y^2; f(x)", &mut context));
+    }
+
+    // These are invalid operations
+    #[test]
+    fn missing_operand() {
+        assert_eq!(Err(ParseError::InvalidExpression { span: Span::new(0, 1) }), parse("-"));
+        assert_eq!(Err(ParseError::InvalidExpression { span: Span::new(2, 3) }), parse("2-*3"));
+    }
+}
+
+// Conformance tests for the precedence and associativity documented on `Operator`
+#[cfg(test)]
+mod precedence_tests {
+    use super::{parse_to_ast, parse_to_ast_with, ParserOptions, PercentMode};
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum Associativity {
+        Left,
+        Right,
+    }
+
+    // Infix operators with their level and associativity, the bitwise operators bind looser
+    // than level 1
+    const INFIX: [(&str, i8, Associativity); 13] = [
+        ("|", -3, Associativity::Left),
+        (" xor ", -2, Associativity::Left),
+        ("&", -1, Associativity::Left),
+        ("<<", 0, Associativity::Left),
+        (">>", 0, Associativity::Left),
+        ("+", 1, Associativity::Left),
+        ("-", 1, Associativity::Left),
+        ("*", 2, Associativity::Left),
+        ("/", 2, Associativity::Left),
+        ("%", 2, Associativity::Left),
+        (" mod ", 2, Associativity::Left),
+        ("^", 4, Associativity::Right),
+        ("√", 4, Associativity::Right),
+    ];
+
+    // Prefix operators with their level
+    const PREFIX: [(&str, i8); 4] = [("+", 3), ("-", 3), ("~", 3), ("√", 4)];
+
+    // Parses the input and prints it fully parenthesized
+    fn grouping(input: &str) -> String {
+        parse_to_ast(input).unwrap().to_string()
+    }
+
+    // Like `grouping`, with `%` as a postfix operator
+    fn postfix_grouping(input: &str) -> String {
+        let options = ParserOptions { percent: PercentMode::Postfix, ..ParserOptions::default() };
+        parse_to_ast_with(input, &options).unwrap().to_string()
+    }
+
+    #[test]
+    fn infix_after_infix() {
+        for (first, first_level, first_associativity) in INFIX {
+            for (second, second_level, _) in INFIX {
+                let input = format!("2{first}3{second}4");
+                let groups_left = first_level > second_level
+                    || (first_level == second_level && first_associativity == Associativity::Left);
+                let expected = if groups_left {
+                    format!("(2{first}3){second}4")
+                } else {
+                    format!("2{first}(3{second}4)")
+                };
+                assert_eq!(expected, grouping(&input), "{input}");
+            }
+        }
     }
 
     #[test]
-    fn add_multiple_values() {
-        assert_eq!(
-            Ok(6.),
-            evaluate_expression(vec![
-                Token::Operand(1.),
-                Token::Operand(2.),
-                Token::Operand(3.),
-                Token::Operator(Operator::Plus),
-                Token::Operator(Operator::Plus),
-            ])
-        );
+    fn prefix_before_infix() {
+        for (prefix, prefix_level) in PREFIX {
+            for (infix, infix_level, _) in INFIX {
+                let input = format!("{prefix}2{infix}3");
+                // A prefix operator takes every operator on its own or a higher level into its operand
+                let expected = if infix_level >= prefix_level {
+                    format!("{prefix}(2{infix}3)")
+                } else {
+                    format!("({prefix}2){infix}3")
+                };
+                assert_eq!(expected, grouping(&input), "{input}");
+            }
+        }
     }
 
     #[test]
-    fn large_composite_equation() {
-        assert!((2. - evaluate_expression(vec![
-            Token::Operand(3.),
-            Token::Operand(1.),
-            Token::Operator(Operator::Minus),
-            Token::Operand(2.),
-            Token::Operator(Operator::Multiply),
-            Token::Operand(3.),
-            Token::Operand(1.), // Implicit operand for factorial
-            Token::Operator(Operator::Factorial),
-            Token::Operator(Operator::Plus),
-            Token::Operand(5.),
-            Token::Operand(8.),
-            Token::Operand(5.),
-            Token::Operator(Operator::Power),
-            Token::Operator(Operator::Root),
-            Token::Operator(Operator::Minus),
-        ]).unwrap()).abs() < 0.00000001);
+    fn prefix_after_infix() {
+        for (infix, _, _) in INFIX {
+            for (prefix, _) in PREFIX {
+                let input = format!("2{infix}{prefix}3");
+                assert_eq!(format!("2{infix}({prefix}3)"), grouping(&input), "{input}");
+            }
+        }
     }
 
     #[test]
-    fn negative_of_addition() {
-        assert_eq!(
-            Ok(-7.),
-            evaluate_expression(vec![
-                Token::Operand(4.),
-                Token::Operand(3.),
-                Token::Operator(Operator::Plus),
-                Token::Operator(Operator::Minus)
-            ])
-        );
+    fn postfix_after_infix() {
+        for (infix, _, _) in INFIX {
+            let input = format!("2{infix}3!");
+            assert_eq!(format!("2{infix}(3!)"), grouping(&input), "{input}");
+        }
+        for (prefix, _) in PREFIX {
+            let input = format!("{prefix}3!");
+            assert_eq!(format!("{prefix}(3!)"), grouping(&input), "{input}");
+        }
+        assert_eq!("(3!)!", grouping("3!!"));
     }
 
-        #[test]
-    fn positive_of_addition() {
-        assert_eq!(
-            Ok(7.),
-            evaluate_expression(vec![
-                Token::Operand(4.),
-                Token::Operand(3.),
-                Token::Operator(Operator::Plus)
-            ])
-        );
+    #[test]
+    fn postfix_percent() {
+        for (infix, _, _) in INFIX.into_iter().filter(|(infix, _, _)| *infix != "%") {
+            let input = format!("2{infix}3%");
+            assert_eq!(format!("2{infix}(3%)"), postfix_grouping(&input), "{input}");
+        }
+        for (prefix, _) in PREFIX {
+            let input = format!("{prefix}3%");
+            assert_eq!(format!("{prefix}(3%)"), postfix_grouping(&input), "{input}");
+        }
+        assert_eq!("(3!)%", postfix_grouping("3!%"));
+        assert_eq!("(3%)!", postfix_grouping("3%!"));
     }
 
-    // These are invalid operations
+    #[cfg(feature = "units")]
     #[test]
-    fn divide_by_zero() {
-        assert_eq!(
-            Err("Cannot divide by zero".to_string()),
-            evaluate_expression(vec![Token::Operand(1.), Token::Operand(0.), Token::Operator(Operator::Divide)])
-        );
+    fn conversion_below_all_infix() {
+        for (infix, _, _) in INFIX {
+            let input = format!("2{infix}3 to 4{infix}5");
+            assert_eq!(format!("(2{infix}3) to (4{infix}5)"), grouping(&input), "{input}");
+        }
+        assert_eq!("(2 to 3) to 4", grouping("2 to 3 to 4"));
     }
 
     #[test]
-    fn invalid_expression() {
-        assert_eq!(
-            Err("Invalid expression".to_string()),
-            evaluate_expression(vec![Token::Operand(1.), Token::Operator(Operator::Plus)])
-        );
+    fn prefix_after_prefix() {
+        for (outer, _) in PREFIX {
+            for (inner, _) in PREFIX {
+                let input = format!("{outer}{inner}2");
+                assert_eq!(format!("{outer}({inner}2)"), grouping(&input), "{input}");
+            }
+        }
     }
 
     #[test]
-    fn invalid_operator() {
-        assert_eq!(
-            Err("OpenParen is an invalid operator".to_string()),
-            evaluate_expression(vec![Token::Operand(1.), Token::Operand(1.), Token::Operator(Operator::OpenParen)])
-        );
+    fn parentheses_and_calls_group_explicitly() {
+        assert_eq!("(1+2)*3", grouping("(1+2)*3"));
+        assert_eq!("(2^3)^2", grouping("(2^3)^2"));
+        assert_eq!("(-2)^2", grouping("(-2)^2"));
+        assert_eq!("max(1, 2)^2", grouping("max(1, 2)^2"));
+        assert_eq!("-max(1+2, 3)", grouping("-max(1+2, 3)"));
     }
 
     #[test]
-    fn negative_factorial() {
-        assert_eq!(
-            Err("Invalid expression".to_string()),
-            evaluate_expression(vec![Token::Operand(-1.), Token::Operator(Operator::Factorial)])
-        );
+    fn documented_examples() {
+        assert_eq!("(1-2)+3", grouping("1-2+3"));
+        assert_eq!("(8/4)*2", grouping("8/4*2"));
+        assert_eq!("2^(3^2)", grouping("2^3^2"));
+        assert_eq!("2^(3!)", grouping("2^3!"));
+        assert_eq!("-(2^2)", grouping("-2^2"));
+        assert_eq!("(2^(-1))*4", grouping("2^-1*4"));
+        assert_eq!("3√(2√64)", grouping("3√2√64"));
     }
-
 }
 
 #[cfg(test)]
 mod parse_tests {
-    use super::parse;
+    use super::{parse, ParseError, Span};
 
     #[test]
     fn add_two_values() {
@@ -810,7 +2643,7 @@ mod parse_tests {
     #[test]
     fn divide_by_zero() {
         assert_eq!(
-            Err("Cannot divide by zero".to_string()),
+            Err(ParseError::DivisionByZero { span: Span::new(1, 2) }),
             parse("1/0")
         );
     }
@@ -818,7 +2651,7 @@ mod parse_tests {
     #[test]
     fn invalid_operation() {
         assert_eq!(
-            Err("Invalid expression".to_string()),
+            Err(ParseError::UnbalancedParen { span: Span::new(0, 1) }),
             parse("(1+")
         );
     }
@@ -826,8 +2659,481 @@ mod parse_tests {
     #[test]
     fn missing_paren() {
         assert_eq!(
-            Err("Invalid expression".to_string()),
+            Err(ParseError::UnbalancedParen { span: Span::new(0, 1) }),
             parse("(1+2")
         );
     }
+
+    #[test]
+    fn extra_closing_paren() {
+        assert_eq!(
+            Err(ParseError::UnbalancedParen { span: Span::new(3, 4) }),
+            parse("1+2)")
+        );
+    }
+
+    #[test]
+    fn missing_operand() {
+        assert_eq!(
+            Err(ParseError::InvalidExpression { span: Span::new(1, 2) }),
+            parse("1+")
+        );
+    }
+
+    #[test]
+    fn error_points_at_offending_character() {
+        let input = "(1+2)/(3-3)";
+        let error = parse(input).unwrap_err();
+        let span = error.span();
+        assert_eq!("/", &input[span.start..span.end]);
+        assert_eq!("Cannot divide by zero", error.to_string());
+    }
+}
+
+#[cfg(test)]
+mod implicit_multiplication_tests {
+    use super::{
+        evaluate_script, parse, parse_to_ast, parse_to_ast_with, Context, Expr, ImplicitMultiplication, Operator,
+        ParseError, ParserOptions, Span,
+    };
+
+    fn options(implicit_multiplication: ImplicitMultiplication) -> ParserOptions {
+        ParserOptions { implicit_multiplication, ..ParserOptions::default() }
+    }
+
+    #[test]
+    fn coefficients() {
+        assert_eq!(Ok(14.), parse("2(3+4)"));
+        assert_eq!(Ok(3. * std::f64::consts::PI), parse("3π"));
+        assert_eq!(Ok(21.), parse("(1+2)(3+4)"));
+        assert_eq!(Ok(2.), parse("2sin(π/2)"));
+        assert_eq!(Ok(12.), parse("3!(2)"));
+    }
+
+    #[test]
+    fn variables_and_definitions() {
+        let mut context = Context::new();
+        assert_eq!(Ok(33.), evaluate_script("x = 3; y = 2; f(a) = 2a + 1; x y f(x) - 3x", &mut context));
+    }
+
+    #[test]
+    fn zero_width_operator() {
+        match parse_to_ast("2π").unwrap() {
+            Expr::Binary { op: Operator::Multiply, span, .. } => assert_eq!(Span::new(1, 1), span),
+            expr => panic!("unexpected tree {expr}"),
+        }
+    }
+
+    #[test]
+    fn same_precedence_as_multiplication() {
+        let options = options(ImplicitMultiplication::SamePrecedence);
+        assert_eq!("(1/2)*π", parse_to_ast_with("1/2π", &options).unwrap().to_string());
+        assert_eq!("(2*π)/4", parse_to_ast_with("2π/4", &options).unwrap().to_string());
+        assert_eq!("2*(π^2)", parse_to_ast_with("2π^2", &options).unwrap().to_string());
+        assert_eq!("(-2)*π", parse_to_ast_with("-2π", &options).unwrap().to_string());
+    }
+
+    #[test]
+    fn tight() {
+        let options = options(ImplicitMultiplication::Tight);
+        assert_eq!("1/(2*π)", parse_to_ast_with("1/2π", &options).unwrap().to_string());
+        assert_eq!("(2*π)/4", parse_to_ast_with("2π/4", &options).unwrap().to_string());
+        assert_eq!("2*(π^2)", parse_to_ast_with("2π^2", &options).unwrap().to_string());
+        assert_eq!("(-2)*π", parse_to_ast_with("-2π", &options).unwrap().to_string());
+        assert_eq!("6/((2*e)*π)", parse_to_ast_with("6/2e π", &options).unwrap().to_string());
+        assert_eq!("(1*2)+(3*(4+1))", parse_to_ast_with("1*2+3(4+1)", &options).unwrap().to_string());
+    }
+
+    // These are invalid operations
+    #[test]
+    fn number_after_operand() {
+        assert_eq!(Err(ParseError::InvalidExpression { span: Span::new(2, 3) }), parse("2 3"));
+        assert_eq!(Err(ParseError::InvalidExpression { span: Span::new(5, 6) }), parse("(1+2)3"));
+    }
+
+    #[test]
+    fn disabled() {
+        let options = options(ImplicitMultiplication::Disabled);
+        assert_eq!(
+            Err(ParseError::InvalidExpression { span: Span::new(1, 2) }),
+            parse_to_ast_with("2(3+4)", &options)
+        );
+        assert_eq!(
+            Err(ParseError::InvalidExpression { span: Span::new(1, 3) }),
+            parse_to_ast_with("2π", &options)
+        );
+    }
+}
+
+#[cfg(test)]
+mod percent_tests {
+    use super::{evaluate_with, parse, parse_to_ast_with, Context, Expr, ParseError, ParserOptions, PercentMode, Span};
+
+    fn options(percent: PercentMode) -> ParserOptions {
+        ParserOptions { percent, ..ParserOptions::default() }
+    }
+
+    fn percent(input: &str, percent: PercentMode) -> Result<f64, ParseError> {
+        parse_to_ast_with(input, &options(percent))?.evaluate()
+    }
+
+    #[test]
+    fn modulo() {
+        assert_eq!(Ok(2.), parse("17 mod 5"));
+        assert_eq!(Ok(2.), parse("-7 mod 3"));
+        assert_eq!(Ok(-2.), parse("7 mod -3"));
+        assert_eq!(Ok(-1.), parse("-7 mod -3"));
+        assert_eq!(Ok(1.5), parse("5.5 mod 2"));
+        assert_eq!(Ok(0.), parse("6 mod 3"));
+        assert_eq!(Ok(3.), parse("2*7 mod 4 + 1"));
+    }
+
+    #[test]
+    fn tiny_divisor() {
+        let remainder = parse("5 mod 1e-16").unwrap();
+        assert!((0. ..1e-16).contains(&remainder));
+        assert_eq!(Ok(2f64.powi(-61)), parse("3*2^(-61) mod 2^(-60)"));
+    }
+
+    #[test]
+    fn modulo_is_a_word() {
+        let mut context = Context::new();
+        context.set_variable("modulus", 7.);
+        assert_eq!(Ok(3.), evaluate_with("10 mod modulus", &context));
+        assert_eq!(Ok(1.), evaluate_with("(2+3)mod(4)", &context));
+    }
+
+    #[test]
+    fn ratio() {
+        assert_eq!(Ok(25.), percent("1%4", PercentMode::Ratio));
+        assert_eq!(Ok(400.), percent("200+10%5", PercentMode::Ratio));
+    }
+
+    #[test]
+    fn postfix() {
+        assert_eq!(Ok(0.1), percent("10%", PercentMode::Postfix));
+        assert_eq!(Ok(20.), percent("200*10%", PercentMode::Postfix));
+        assert_eq!(Ok(200.1), percent("200+10%", PercentMode::Postfix));
+        assert_eq!(Ok(10.24), percent("(2^10)%", PercentMode::Postfix));
+        assert_eq!(Ok(2f64.powf(0.1)), percent("2^10%", PercentMode::Postfix));
+        assert_eq!(Ok(-0.5), percent("-50%", PercentMode::Postfix));
+        assert_eq!(
+            "200+(10%)",
+            parse_to_ast_with("200+10%", &options(PercentMode::Postfix)).unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn add_on() {
+        assert_eq!(Ok(220.), percent("200+10%", PercentMode::AddOn));
+        assert_eq!(Ok(180.), percent("200-10%", PercentMode::AddOn));
+        assert_eq!(Ok(20.), percent("200*10%", PercentMode::AddOn));
+        assert_eq!(Ok(2000.), percent("200/10%", PercentMode::AddOn));
+        assert_eq!(Ok(1100.), percent("5*200+10%", PercentMode::AddOn));
+        assert_eq!(Ok(0.1), percent("10%", PercentMode::AddOn));
+        assert_eq!(
+            "200+(10%)",
+            parse_to_ast_with("200+10%", &options(PercentMode::AddOn)).unwrap().to_string()
+        );
+        let is_add_on = |input: &str, mode| {
+            matches!(parse_to_ast_with(input, &options(mode)), Ok(Expr::Binary { add_on: true, .. }))
+        };
+        assert!(is_add_on("200-10%", PercentMode::AddOn));
+        assert!(!is_add_on("200-10%", PercentMode::Postfix));
+        assert!(!is_add_on("200*10%", PercentMode::AddOn));
+        assert!(!is_add_on("200+10", PercentMode::AddOn));
+    }
+
+    #[test]
+    fn chained_add_on() {
+        assert_eq!(Ok(198.), percent("200-10%+10%", PercentMode::AddOn));
+        // Every term only refers to the value before it, so the tree grows linearly
+        let input = format!("100{}", "+1%".repeat(200));
+        let tree = parse_to_ast_with(&input, &options(PercentMode::AddOn)).unwrap();
+        assert!(tree.to_string().len() < 10 * input.len());
+        let value = tree.evaluate().unwrap();
+        assert!((value - 100. * 1.01f64.powi(200)).abs() < 1e-6 * value);
+    }
+
+    // These are invalid operations
+    #[test]
+    fn modulo_by_zero() {
+        assert_eq!(Err(ParseError::DivisionByZero { span: Span::new(2, 5) }), parse("5 mod 0"));
+    }
+
+    #[test]
+    fn binary_percent_in_postfix_mode() {
+        assert_eq!(Err(ParseError::InvalidExpression { span: Span::new(4, 5) }), percent("5 % 3", PercentMode::Postfix));
+    }
+}
+
+#[cfg(test)]
+mod literal_tests {
+    use super::{evaluate_with, parse, parse_to_ast_with, Context, ParseError, ParserOptions, Span};
+
+    fn engineering(input: &str) -> Result<f64, ParseError> {
+        let options = ParserOptions { si_suffixes: true, ..ParserOptions::default() };
+        parse_to_ast_with(input, &options)?.evaluate()
+    }
+
+    #[test]
+    fn scientific_notation() {
+        assert_eq!(Ok(6.022e23), parse("6.022e23"));
+        assert_eq!(Ok(1.5e-9), parse("1.5E-9"));
+        assert_eq!(Ok(200.), parse("2E+2"));
+        assert_eq!(Ok(1500.), parse("1,5e3"));
+        assert_eq!(Ok(0.5), parse(".5e0"));
+    }
+
+    #[test]
+    fn exponent_and_constant() {
+        assert_eq!(Ok(2. * std::f64::consts::E), parse("2e"));
+        assert_eq!(Ok(0.2), parse("2e-1"));
+        assert_eq!(Ok(2. * std::f64::consts::E - 1.), parse("2e - 1"));
+        assert_eq!(Ok(2.), parse("2exp(0)"));
+    }
+
+    #[test]
+    fn si_suffixes() {
+        assert_eq!(Ok(4700.), engineering("4.7k"));
+        assert_eq!(Ok(2.2e6), engineering("2.2M"));
+        assert_eq!(Ok(1e-5), engineering("10µ"));
+        assert_eq!(Ok(1e-5), engineering("10μ"));
+        assert_eq!(Ok(1e-5), engineering("10u"));
+        assert_eq!(Ok(0.003), engineering("3m"));
+        assert_eq!(Ok(1e-15), engineering("1f"));
+        assert_eq!(Ok(3e12), engineering("3T"));
+        assert_eq!(Ok(1.), engineering("1k/1000"));
+    }
+
+    #[test]
+    fn si_suffix_before_name() {
+        let mut context = Context::new();
+        context.set_variable("min", 60.);
+        context.set_parser_options(ParserOptions { si_suffixes: true, ..ParserOptions::default() });
+        assert_eq!(Ok(120.), evaluate_with("2min", &context));
+    }
+
+    #[test]
+    fn radix_literals() {
+        assert_eq!(Ok(265.), parse("0xFF + 0b1010"));
+        assert_eq!(Ok(15.), parse("0o17"));
+        assert_eq!(Ok(171.), parse("0Xab"));
+        assert_eq!(Ok(240.), parse("0b1111_0000"));
+        assert_eq!(Ok(485.), parse("0x1e5"));
+        assert_eq!(Ok(-16.), parse("-0x10"));
+        assert_eq!(Ok(6.), engineering("0x2*3"));
+    }
+
+    #[test]
+    fn prefix_without_digits() {
+        let mut context = Context::new();
+        context.set_variable("x", 4.);
+        context.set_variable("b2", 5.);
+        assert_eq!(Ok(0.), evaluate_with("0x", &context));
+        assert_eq!(Ok(0.), evaluate_with("0b2", &context));
+    }
+
+    // These are invalid operations
+    #[test]
+    fn si_suffixes_disabled() {
+        assert_eq!(
+            Err(ParseError::UnknownVariable { name: "k".to_string(), span: Span::new(3, 4) }),
+            parse("4.7k")
+        );
+    }
+
+    #[test]
+    fn exponent_with_suffix() {
+        assert_eq!(
+            Err(ParseError::UnknownVariable { name: "k".to_string(), span: Span::new(3, 4) }),
+            engineering("1e3k")
+        );
+    }
+
+    #[test]
+    fn malformed_radix_literals() {
+        assert_eq!(Err(ParseError::MalformedNumber { span: Span::new(2, 7) }), parse("1+0b102"));
+        assert_eq!(Err(ParseError::MalformedNumber { span: Span::new(0, 5) }), parse("0xFG1"));
+        assert_eq!(Err(ParseError::MalformedNumber { span: Span::new(0, 6) }), parse("0x1__2"));
+        assert_eq!(Err(ParseError::MalformedNumber { span: Span::new(0, 4) }), parse("0o7_"));
+    }
+}
+
+#[cfg(test)]
+mod number_format_tests {
+    use super::{parse, parse_to_ast_with, NumberFormat, ParseError, ParserOptions, Span};
+
+    fn localized(input: &str, format: NumberFormat) -> Result<f64, ParseError> {
+        let options = ParserOptions { number_format: Some(format), ..ParserOptions::default() };
+        parse_to_ast_with(input, &options)?.evaluate()
+    }
+
+    #[test]
+    fn legacy_decimal_comma() {
+        assert_eq!(Ok(1.5), parse("1,5"));
+        assert_eq!(Ok(3.), parse("1,5+1.5"));
+    }
+
+    #[test]
+    fn english_grouping() {
+        assert_eq!(Ok(1000000.), localized("1,000,000", NumberFormat::ENGLISH));
+        assert_eq!(Ok(1234.5), localized("1 234.5", NumberFormat::ENGLISH));
+        assert_eq!(Ok(1234.5), localized("1_234.5", NumberFormat::ENGLISH));
+        assert_eq!(Ok(1234.5), localized("1'234.5", NumberFormat::ENGLISH));
+        assert_eq!(Ok(123.), localized("123", NumberFormat::ENGLISH));
+        assert_eq!(Ok(1e6), localized("1,000e3", NumberFormat::ENGLISH));
+    }
+
+    #[test]
+    fn german_grouping() {
+        assert_eq!(Ok(1000000.5), localized("1.000.000,5", NumberFormat::GERMAN));
+        assert_eq!(Ok(0.25), localized("0,25", NumberFormat::GERMAN));
+        assert_eq!(Ok(2500.), localized("2 500", NumberFormat::FRENCH));
+        assert_eq!(Ok(2500.), localized("2'500", NumberFormat::SWISS));
+    }
+
+    #[test]
+    fn separators_outside_numbers() {
+        assert_eq!(Ok(3.), localized("1 + 2", NumberFormat::ENGLISH));
+        assert_eq!(Ok(3.), localized("max(1,3)", NumberFormat::ENGLISH));
+        assert_eq!(Ok(3.), localized("max((1,5) , 3)", NumberFormat::GERMAN));
+    }
+
+    // These are invalid operations
+    #[test]
+    fn ambiguous_groups() {
+        let ambiguous = |start, end| Err(ParseError::AmbiguousNumber { span: Span::new(start, end) });
+        assert_eq!(ambiguous(0, 3), localized("1,5", NumberFormat::ENGLISH));
+        assert_eq!(ambiguous(0, 6), localized("1,0000", NumberFormat::ENGLISH));
+        assert_eq!(ambiguous(0, 8), localized("1000,000", NumberFormat::ENGLISH));
+        assert_eq!(ambiguous(2, 11), localized("2+1,000 000", NumberFormat::ENGLISH));
+        assert_eq!(ambiguous(0, 9), localized("1.000,000", NumberFormat::ENGLISH));
+        assert_eq!(ambiguous(0, 6), localized("0.5,00", NumberFormat::ENGLISH));
+        assert_eq!(ambiguous(0, 4), localized("0.25", NumberFormat::GERMAN));
+        assert_eq!(ambiguous(0, 3), localized("1,5", NumberFormat::SWISS));
+    }
+
+    #[test]
+    fn ambiguous_groups_in_calls() {
+        let ambiguous = |start, end| Err(ParseError::AmbiguousNumber { span: Span::new(start, end) });
+        assert_eq!(ambiguous(4, 9), localized("max(1,000, 2)", NumberFormat::ENGLISH));
+        assert_eq!(ambiguous(4, 13), localized("max(1 000,000, 2)", NumberFormat::ENGLISH));
+        assert_eq!(ambiguous(7, 12), localized("max(2, 1,000)", NumberFormat::ENGLISH));
+        assert_eq!(Ok(1000.), localized("max(1 000, 2)", NumberFormat::ENGLISH));
+        assert_eq!(Ok(2.), localized("max(1,2)", NumberFormat::ENGLISH));
+        assert_eq!(Ok(1.5), localized("max(1.5,0000)", NumberFormat::ENGLISH));
+        assert_eq!(Ok(1000.), localized("max(1, 000, 1000)", NumberFormat::ENGLISH));
+        // Without `,` as a grouping separator, it only separates the arguments
+        assert_eq!(Ok(2.), localized("max(1,000, 2)", NumberFormat::SWISS));
+        assert_eq!(Ok(2.), parse("max(1,000, 2)"));
+    }
+
+    #[test]
+    fn decimal_comma_in_calls() {
+        let ambiguous = |start, end| Err(ParseError::AmbiguousNumber { span: Span::new(start, end) });
+        assert_eq!(ambiguous(4, 7), localized("max(2,5, 3)", NumberFormat::GERMAN));
+        assert_eq!(ambiguous(5, 9), localized("sqrt(2,25)", NumberFormat::GERMAN));
+        assert_eq!(ambiguous(4, 11), localized("max(1 000,5, 3)", NumberFormat::FRENCH));
+        // A space after the comma separates the arguments, parentheses keep a decimal comma
+        assert_eq!(Ok(5.), localized("max(2, 5)", NumberFormat::GERMAN));
+        assert_eq!(Ok(3.), localized("max((2,5), 3)", NumberFormat::GERMAN));
+        assert_eq!(Ok(1.5), localized("sqrt((2,25))", NumberFormat::FRENCH));
+    }
+
+    #[test]
+    fn malformed_numbers() {
+        assert_eq!(Err(ParseError::MalformedNumber { span: Span::new(0, 5) }), localized("1.5.5", NumberFormat::ENGLISH));
+        assert_eq!(Err(ParseError::MalformedNumber { span: Span::new(1, 2) }), localized("1,+2", NumberFormat::ENGLISH));
+    }
+}
+
+#[cfg(test)]
+mod normalization_tests {
+    use super::{evaluate_with, parse, parse_to_ast, Context, Normalized, ParseError, Span};
+
+    #[test]
+    fn typographic_operators() {
+        assert_eq!(Ok(6.), parse("2×3"));
+        assert_eq!(Ok(6.), parse("2·3"));
+        assert_eq!(Ok(6.), parse("2⋅3"));
+        assert_eq!(Ok(2.), parse("6÷3"));
+        assert_eq!(Ok(-1.), parse("2−3"));
+        assert_eq!(Ok(-5.), parse("−5"));
+        assert_eq!(Ok(-1.), parse("2 – 3"));
+    }
+
+    #[test]
+    fn superscripts() {
+        assert_eq!(Ok(9.), parse("3²"));
+        assert_eq!(Ok(8.), parse("2³"));
+        assert_eq!(Ok(0.5), parse("2⁻¹"));
+        assert_eq!(Ok(2f64.powi(10)), parse("2¹⁰"));
+        assert_eq!(Ok(13.), parse("2²+3²"));
+        let mut context = Context::new();
+        context.set_variable("x", 3.);
+        assert_eq!(Ok(18.), evaluate_with("2x²", &context));
+    }
+
+    #[test]
+    fn unicode_spaces() {
+        assert_eq!(Ok(3.), parse("1\u{a0}+\t2\u{202f}"));
+    }
+
+    #[test]
+    fn normalized_text() {
+        let normalized = Normalized::new("2×x⁻¹");
+        assert_eq!("2*x^-1", normalized.text);
+        assert_eq!(Span::new(1, 3), normalized.original_span(Span::new(1, 2)));
+        assert_eq!(Span::new(4, 9), normalized.original_span(Span::new(3, 6)));
+        assert_eq!(Span::new(9, 9), normalized.original_span(Span::new(6, 6)));
+    }
+
+    #[test]
+    fn spans_refer_to_original() {
+        let expr = parse_to_ast("2×3÷4").unwrap();
+        assert_eq!(Span::new(4, 6), expr.span());
+        // These are invalid operations
+        assert_eq!(Err(ParseError::DivisionByZero { span: Span::new(4, 6) }), parse("1×2÷0"));
+        assert_eq!(Err(ParseError::UnexpectedChar { ch: '$', span: Span::new(3, 4) }), parse("1×$"));
+        assert_eq!(Err(ParseError::InvalidExpression { span: Span::new(4, 7) }), parse("2³ −"));
+    }
+}
+
+#[cfg(test)]
+mod nesting_tests {
+    use super::{parse, ParseError, Span, MAX_NESTING_DEPTH, MAX_TREE_HEIGHT};
+
+    #[test]
+    fn deep_nesting() {
+        let error = Err(ParseError::NestingLimit { span: Span::new(256, 257) });
+        assert_eq!(error, parse(&"(".repeat(100_000)));
+        assert_eq!(error, parse(&format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000))));
+        assert_eq!(error, parse(&format!("{}1", "-".repeat(100_000))));
+        let error = Err(ParseError::NestingLimit { span: Span::new(512, 513) });
+        assert_eq!(error, parse(&format!("{}2", "2^".repeat(100_000))));
+        let error = Err(ParseError::NestingLimit { span: Span::new(1024, 1027) });
+        assert_eq!(error, parse(&format!("{}1{}", "sin(".repeat(100_000), ")".repeat(100_000))));
+    }
+
+    #[test]
+    fn long_chains() {
+        // Infix operators in a row are evaluated in a loop, so they are only limited by the
+        // height of the tree
+        assert_eq!(Ok(301.), parse(&format!("1{}", "+1".repeat(300))));
+        assert_eq!(Ok(MAX_TREE_HEIGHT as f64), parse(&format!("1{}", "+1".repeat(MAX_TREE_HEIGHT - 1))));
+        assert_eq!(Ok(1.), parse(&format!("1{}", "*2/2".repeat(MAX_TREE_HEIGHT / 3))));
+        let error = Err(ParseError::NestingLimit { span: Span::new(1999, 2000) });
+        assert_eq!(error, parse(&format!("1{}", "+1".repeat(100_000))));
+        // Postfix operators in a row are nested
+        let error = Err(ParseError::NestingLimit { span: Span::new(256, 257) });
+        assert_eq!(error, parse(&format!("3{}", "!".repeat(100_000))));
+    }
+
+    #[test]
+    fn nesting_below_limit() {
+        let depth = MAX_NESTING_DEPTH - 1;
+        assert_eq!(Ok(1.), parse(&format!("{}1{}", "(".repeat(depth), ")".repeat(depth))));
+        assert_eq!(Ok(-1.), parse(&format!("{}1", "-".repeat(depth))));
+    }
 }
@@ -0,0 +1,164 @@
+//! # Description
+//! This module provides the formatting of whole numbers in bases other than ten.
+
+/// Largest magnitude [`format_radix`] can write, 2^128.
+const LIMIT: f64 = 340_282_366_920_938_463_463_374_607_431_768_211_456.;
+
+/// How [`format_radix`] writes a number.
+///
+/// # Examples
+/// ```
+/// use math_lib::RadixFormat;
+///
+/// let format = RadixFormat { prefix: true, group_size: Some(4), ..RadixFormat::new(2) };
+/// assert_eq!(format.separator, '_');
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RadixFormat {
+    /// Base from 2 to 36, digits above 9 are written as the letters `A` to `Z`.
+    pub radix: u32,
+    /// Whether bases 2, 8 and 16 are written with the prefix `0b`, `0o` or `0x`.
+    pub prefix: bool,
+    /// Number of digits in a group, counted from the last digit, or `None` to leave the
+    /// digits ungrouped.
+    pub group_size: Option<usize>,
+    /// Character written between groups of digits.
+    pub separator: char,
+}
+
+impl RadixFormat {
+    /// Creates a format for `radix` without prefix and grouping, whose groups would be
+    /// separated by `_` like in literals.
+    pub const fn new(radix: u32) -> RadixFormat {
+        RadixFormat { radix, prefix: false, group_size: None, separator: '_' }
+    }
+}
+
+/// Writes the whole number `value` in the base of `format`.
+///
+/// With a prefix and `_` as the separator, the result can be read back by the parser.
+///
+/// # Arguments
+///
+/// * `value` - The number to write, which must be whole and below 2^128 in magnitude
+/// * `format` - The base, prefix and grouping of the digits
+///
+/// # Returns
+/// A Result containing the digits of `value` or a message if the base is not between 2
+/// and 36 or `value` cannot be written in it.
+///
+/// # Examples
+/// ```
+/// use math_lib::{format_radix, parse, RadixFormat};
+///
+/// let value = parse("0xFF + 0b1010").unwrap();
+/// assert_eq!(format_radix(value, RadixFormat::new(16)), Ok("109".to_string()));
+/// let binary = RadixFormat { prefix: true, group_size: Some(4), ..RadixFormat::new(2) };
+/// assert_eq!(format_radix(value, binary), Ok("0b1_0000_1001".to_string()));
+/// assert!(format_radix(0.5, RadixFormat::new(16)).is_err());
+/// ```
+pub fn format_radix(value: f64, format: RadixFormat) -> Result<String, String> {
+    if !(2..=36).contains(&format.radix) {
+        return Err(format!("Base must be between 2 and 36, found {}", format.radix));
+    }
+    if value.fract() != 0. || value.is_nan() {
+        return Err(format!("Cannot write {value} in base {}, it is not a whole number", format.radix));
+    }
+    if value.abs() >= LIMIT {
+        return Err(format!("Cannot write {value} in base {}, it is too large", format.radix));
+    }
+
+    let mut magnitude = value.abs() as u128;
+    let radix = u128::from(format.radix);
+    let mut digits = Vec::new();
+    loop {
+        let digit = char::from_digit((magnitude % radix) as u32, format.radix).expect("digit is below the radix");
+        digits.push(digit.to_ascii_uppercase());
+        magnitude /= radix;
+        if magnitude == 0 {
+            break;
+        }
+    }
+
+    let mut text = String::new();
+    if value < 0. {
+        text.push('-');
+    }
+    if format.prefix {
+        text.push_str(match format.radix {
+            2 => "0b",
+            8 => "0o",
+            16 => "0x",
+            _ => "",
+        });
+    }
+    // The digits were collected from the last one, so groups are counted from there
+    for (index, digit) in digits.iter().enumerate().rev() {
+        text.push(*digit);
+        let group_ends = format.group_size.is_some_and(|size| size > 0 && index % size == 0);
+        if group_ends && index > 0 {
+            text.push(format.separator);
+        }
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod radix_tests {
+    use super::{format_radix, RadixFormat};
+    use crate::parse;
+
+    fn radix(value: f64, radix: u32) -> String {
+        format_radix(value, RadixFormat::new(radix)).unwrap()
+    }
+
+    #[test]
+    fn common_bases() {
+        assert_eq!("11111111", radix(255., 2));
+        assert_eq!("377", radix(255., 8));
+        assert_eq!("255", radix(255., 10));
+        assert_eq!("FF", radix(255., 16));
+        assert_eq!("0", radix(0., 16));
+        assert_eq!("-1A", radix(-26., 16));
+    }
+
+    #[test]
+    fn any_base() {
+        assert_eq!("ZZ", radix(1295., 36));
+        assert_eq!("1012", radix(32., 3));
+        assert_eq!("100000000000000000000000000000000", radix(2f64.powi(32), 2));
+        assert_eq!("FFFFFFFFFFFFF800", radix(u64::MAX as f64 - 2047., 16));
+    }
+
+    #[test]
+    fn prefix_and_grouping() {
+        let hex = RadixFormat { prefix: true, group_size: Some(4), ..RadixFormat::new(16) };
+        assert_eq!("0xDEAD_BEEF", format_radix(3_735_928_559., hex).unwrap());
+        assert_eq!("-0xFF", format_radix(-255., hex).unwrap());
+        let octal = RadixFormat { prefix: true, ..RadixFormat::new(8) };
+        assert_eq!("0o17", format_radix(15., octal).unwrap());
+        let decimal = RadixFormat { prefix: true, group_size: Some(3), separator: ' ', ..RadixFormat::new(10) };
+        assert_eq!("1 234 567", format_radix(1_234_567., decimal).unwrap());
+        let base_36 = RadixFormat { prefix: true, ..RadixFormat::new(36) };
+        assert_eq!("ZZ", format_radix(1295., base_36).unwrap());
+    }
+
+    #[test]
+    fn round_trip() {
+        let binary = RadixFormat { prefix: true, group_size: Some(4), ..RadixFormat::new(2) };
+        for value in [0., 1., 10., 255., 4096., 123_456_789.] {
+            assert_eq!(Ok(value), parse(&format_radix(value, binary).unwrap()));
+        }
+    }
+
+    // These are invalid operations
+    #[test]
+    fn unwritable_numbers() {
+        assert!(format_radix(1.5, RadixFormat::new(2)).is_err());
+        assert!(format_radix(f64::NAN, RadixFormat::new(2)).is_err());
+        assert!(format_radix(f64::INFINITY, RadixFormat::new(2)).is_err());
+        assert!(format_radix(1e40, RadixFormat::new(16)).is_err());
+        assert!(format_radix(5., RadixFormat::new(1)).is_err());
+        assert!(format_radix(5., RadixFormat::new(37)).is_err());
+    }
+}
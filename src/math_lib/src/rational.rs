@@ -0,0 +1,491 @@
+//! # Description
+//! This module provides the evaluation of expressions with exact fractions.
+//! It is only compiled with the `rational` feature.
+
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{Signed, ToPrimitive, Zero};
+
+use crate::ast::{self, call_native, called_function, evaluate_chain, Expr};
+use crate::context::Context;
+use crate::error::{ParseError, Span};
+use crate::functions::{self, Implementation};
+use crate::integer::{is_bitwise, IntegerMode};
+use crate::parser::{parse_to_ast_with, Operator};
+use crate::special;
+
+/// Largest absolute value of an exponent a fraction is raised to exactly; larger
+/// exponents are computed with `f64`.
+const MAX_EXACT_EXPONENT: i32 = 10_000;
+
+/// Largest number of bits the numerator and denominator of an exact power may have
+/// together; larger powers are computed with `f64`.
+const MAX_EXACT_POWER_BITS: u64 = 1 << 20;
+
+/// Largest root degree taken exactly; higher degrees are computed with `f64`.
+const MAX_EXACT_ROOT_DEGREE: u32 = 1_000;
+
+/// Largest number whose factorial is multiplied out exactly; the factorials of larger
+/// numbers are computed with `f64`.
+const MAX_EXACT_FACTORIAL: u64 = 10_000;
+
+/// Value of an expression evaluated with exact fractions.
+///
+/// Operations stay exact as long as their result is a fraction. Once an operation has an
+/// irrational result, like `√2`, the value is computed with `f64` from then on and only
+/// approximate.
+///
+/// # Examples
+/// ```
+/// use math_lib::{evaluate_rational, Context, RationalValue};
+///
+/// let half = evaluate_rational("1/3 + 1/6", &Context::new()).unwrap();
+/// assert!(half.is_exact());
+/// assert_eq!(half.to_string(), "1/2");
+///
+/// let root = evaluate_rational("√2/2", &Context::new()).unwrap();
+/// assert!(!root.is_exact());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum RationalValue {
+    /// The exact value.
+    Exact(BigRational),
+    /// A floating-point approximation of a value that is not a fraction.
+    Approximate(f64),
+}
+
+impl RationalValue {
+    /// Returns `true` if the value is exact.
+    pub fn is_exact(&self) -> bool {
+        matches!(self, RationalValue::Exact(_))
+    }
+
+    /// Returns the value as the nearest `f64`.
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            RationalValue::Exact(value) => value.to_f64().unwrap_or(f64::NAN),
+            RationalValue::Approximate(value) => *value,
+        }
+    }
+}
+
+/// Prints exact values as a fraction in lowest terms, like `-1/2`, or as a whole number,
+/// and approximate values as a decimal.
+impl fmt::Display for RationalValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RationalValue::Exact(value) => write!(f, "{value}"),
+            RationalValue::Approximate(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+/// Parses and evaluates `input` with exact fractions.
+///
+/// The input is parsed with the parser options of `context`, see
+/// [`Expr::evaluate_rational`] for how it is evaluated.
+///
+/// # Arguments
+///
+/// * `input` - A string slice containing the expression to evaluate
+/// * `context` - The context providing variables, functions and parser options
+///
+/// # Returns
+/// A Result containing the value of the expression or a ParseError if the expression
+/// cannot be parsed or evaluated.
+///
+/// # Examples
+/// ```
+/// use math_lib::{evaluate_rational, Context};
+///
+/// assert_eq!(evaluate_rational("(2/3)^-2", &Context::new()).unwrap().to_string(), "9/4");
+/// assert_eq!(evaluate_rational("0.1+0.2", &Context::new()).unwrap().to_string(), "3/10");
+/// ```
+pub fn evaluate_rational(input: &str, context: &Context) -> Result<RationalValue, ParseError> {
+    parse_to_ast_with(input, context.parser_options())?.evaluate_rational(context)
+}
+
+impl Expr {
+    /// Evaluates the expression with exact fractions, looking variables up in `context`.
+    ///
+    /// Number literals and the variables of `context` are read as the decimal they are
+    /// written as, so `0.1` is `1/10`. `+`, `-`, `*`, `/`, `%` and factorials of whole
+    /// numbers are exact. Powers and roots are exact if their result is a fraction, like
+    /// `8^(2/3)` or `√(9/4)`. Constants, functions and the remaining powers and roots are
+    /// computed with `f64`, which makes the result [`RationalValue::Approximate`].
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - The context providing the values of variables and functions
+    ///
+    /// # Returns
+    /// A Result containing the value of the expression or a ParseError if an error occurs
+    /// during evaluation.
+    pub fn evaluate_rational(&self, context: &Context) -> Result<RationalValue, ParseError> {
+        RationalScope { context, locals: &[], depth: 0 }.evaluate(self)
+    }
+}
+
+/// Variables visible while evaluating an expression with fractions.
+struct RationalScope<'a> {
+    context: &'a Context,
+    // Parameters of the function being called, empty outside of calls
+    locals: &'a [(&'a str, RationalValue)],
+    // Number of function calls being evaluated
+    depth: usize,
+}
+
+impl RationalScope<'_> {
+    fn evaluate(&self, expr: &Expr) -> Result<RationalValue, ParseError> {
+        match expr {
+            Expr::Number { value, literal, .. } => match literal.as_deref().and_then(rational_of_literal) {
+                Some(exact) => Ok(RationalValue::Exact(exact)),
+                None => Ok(rational_of(*value)),
+            },
+            Expr::Variable { name, span } => {
+                if let Some((_, value)) = self.locals.iter().find(|(local, _)| local == name) {
+                    return Ok(value.clone());
+                }
+                let value = self.context.value(name).ok_or_else(|| ParseError::UnknownVariable {
+                    name: name.clone(),
+                    span: *span,
+                })?;
+                Ok(rational_of(value))
+            }
+            Expr::Constant { constant, .. } => Ok(RationalValue::Approximate(constant.value())),
+            Expr::Unary { op, operand, span } => apply_unary(*op, self.evaluate(operand)?, *span),
+            Expr::Binary { .. } => evaluate_chain(
+                expr,
+                |operand| self.evaluate(operand),
+                |left, op, right, add_on, span| {
+                    let mut right = self.evaluate(right)?;
+                    if add_on {
+                        right = apply_binary(Operator::Multiply, left.clone(), right, span)?;
+                    }
+                    apply_binary(op, left, right, span)
+                },
+            ),
+            Expr::Call { name, args, span } => {
+                let function = called_function(self.context, name, args.len(), *span)?;
+                match function.implementation() {
+                    Implementation::Native(native) => {
+                        let values = args.iter().map(|arg| self.evaluate(arg).map(|value| value.to_f64()));
+                        let values = values.collect::<Result<Vec<_>, _>>()?;
+                        let result = call_native(function, native.as_ref(), values, self.context, *span)?;
+                        Ok(RationalValue::Approximate(result))
+                    }
+                    Implementation::User(function) => {
+                        if self.depth >= self.context.max_call_depth() {
+                            return Err(ParseError::RecursionLimit { name: name.clone(), span: *span });
+                        }
+                        let mut locals = Vec::with_capacity(args.len());
+                        for (param, arg) in function.params.iter().zip(args) {
+                            locals.push((param.as_str(), self.evaluate(arg)?));
+                        }
+                        // The body only sees its own parameters, not those of the caller
+                        let scope = RationalScope { context: self.context, locals: &locals, depth: self.depth + 1 };
+                        scope.evaluate(&function.body)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Converts an `f64` to the fraction of the shortest decimal that converts back to it, so
+/// `0.1` is `1/10`. Values that are not finite stay approximate.
+fn rational_of(value: f64) -> RationalValue {
+    if !value.is_finite() {
+        return RationalValue::Approximate(value);
+    }
+    // Display of f64 never uses an exponent, so the text is always a plain decimal
+    let text = value.to_string();
+    let (whole, fraction) = text.split_once('.').unwrap_or((&text, ""));
+    let numerator: BigInt = format!("{whole}{fraction}").parse().expect("f64 is displayed as a decimal");
+    let denominator = BigInt::from(10).pow(fraction.len() as u32);
+    RationalValue::Exact(BigRational::new(numerator, denominator))
+}
+
+/// Converts the exact value of a literal, a decimal like `1.5e3`, to a fraction.
+///
+/// Returns `None` if the literal is malformed or its power of ten is too large to be
+/// multiplied out.
+fn rational_of_literal(literal: &str) -> Option<BigRational> {
+    let (mantissa, exponent) = match literal.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i32>().ok()?),
+        None => (literal, 0),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits: BigInt = format!("{whole}{fraction}").parse().ok()?;
+    let scale = exponent.checked_sub(i32::try_from(fraction.len()).ok()?)?;
+    if scale.abs() > MAX_EXACT_EXPONENT {
+        return None;
+    }
+    let power = BigInt::from(10).pow(scale.unsigned_abs());
+    if scale < 0 {
+        Some(BigRational::new(digits, power))
+    } else {
+        Some(BigRational::from_integer(digits * power))
+    }
+}
+
+/// Applies a unary operator, exactly if the operand is exact.
+fn apply_unary(op: Operator, operand: RationalValue, span: Span) -> Result<RationalValue, ParseError> {
+    let RationalValue::Exact(operand) = operand else {
+        return ast::apply_unary(op, operand.to_f64(), span).map(RationalValue::Approximate);
+    };
+    match op {
+        Operator::Plus => Ok(RationalValue::Exact(operand)),
+        Operator::Minus => Ok(RationalValue::Exact(-operand)),
+        Operator::Percent => Ok(RationalValue::Exact(operand / BigInt::from(100))),
+        Operator::Root => root(BigRational::from_integer(BigInt::from(2)), operand, span),
+        Operator::Factorial => factorial(operand, span),
+        Operator::BitNot => {
+            let value = IntegerMode::default().apply_unary(op, integer_of(&operand, span)?, span)?;
+            Ok(RationalValue::Exact(BigRational::from_integer(BigInt::from(value))))
+        }
+        _ => Err(ParseError::InvalidOperator { op, span }),
+    }
+}
+
+/// Applies a binary operator, exactly if both operands are exact.
+fn apply_binary(op: Operator, left: RationalValue, right: RationalValue, span: Span) -> Result<RationalValue, ParseError> {
+    let (RationalValue::Exact(left), RationalValue::Exact(right)) = (&left, &right) else {
+        if op == Operator::Power {
+            return approximate_power(left.to_f64(), right.to_f64(), span);
+        }
+        return ast::apply_binary(op, left.to_f64(), right.to_f64(), span).map(RationalValue::Approximate);
+    };
+    match op {
+        Operator::Plus => Ok(RationalValue::Exact(left + right)),
+        Operator::Minus => Ok(RationalValue::Exact(left - right)),
+        Operator::Multiply => Ok(RationalValue::Exact(left * right)),
+        Operator::Divide => {
+            if right.is_zero() {
+                return Err(ParseError::DivisionByZero { span });
+            }
+            Ok(RationalValue::Exact(left / right))
+        }
+        Operator::Percent => {
+            if right.is_zero() {
+                return Err(ParseError::DomainError {
+                    message: "Cannot take the percentage of zero".to_string(),
+                    span,
+                });
+            }
+            Ok(RationalValue::Exact(left / right * BigInt::from(100)))
+        }
+        Operator::Modulo => {
+            if right.is_zero() {
+                return Err(ParseError::DivisionByZero { span });
+            }
+            // The remainder takes the sign of the divisor
+            let remainder = left % right;
+            if !remainder.is_zero() && remainder.is_negative() != right.is_negative() {
+                Ok(RationalValue::Exact(remainder + right))
+            } else {
+                Ok(RationalValue::Exact(remainder))
+            }
+        }
+        Operator::Power => power(left, right, span),
+        Operator::Root => root(left.clone(), right.clone(), span),
+        // Bitwise operators work on 64-bit signed integers
+        op if is_bitwise(op) => {
+            let mode = IntegerMode::default();
+            let value = mode.apply_binary(op, integer_of(left, span)?, integer_of(right, span)?, span)?;
+            Ok(RationalValue::Exact(BigRational::from_integer(BigInt::from(value))))
+        }
+        _ => Err(ParseError::InvalidOperator { op, span }),
+    }
+}
+
+/// Converts an operand of a bitwise operator to an integer, which it must be.
+fn integer_of(value: &BigRational, span: Span) -> Result<i128, ParseError> {
+    if !value.is_integer() {
+        return Err(ParseError::DomainError { message: format!("{value} is not a whole number"), span });
+    }
+    value.to_integer().to_i128().filter(|integer| integer.unsigned_abs() < 1 << 64).ok_or_else(|| {
+        ParseError::DomainError { message: format!("{value} does not fit into 64 bits"), span }
+    })
+}
+
+/// Raises `base` to `exponent`, exactly if the result is a fraction.
+///
+/// With the exponent `p/q` in lowest terms, the result is a fraction if `base` is the
+/// `q`-th power of a fraction.
+fn power(base: &BigRational, exponent: &BigRational, span: Span) -> Result<RationalValue, ParseError> {
+    let approximate = || {
+        approximate_power(base.to_f64().unwrap_or(f64::NAN), exponent.to_f64().unwrap_or(f64::NAN), span)
+    };
+    if base.is_zero() && exponent.is_negative() {
+        return Err(ParseError::DivisionByZero { span });
+    }
+    let numerator = exponent.numer().to_i32().filter(|numerator| numerator.abs() <= MAX_EXACT_EXPONENT);
+    let denominator = exponent.denom().to_u32().filter(|denominator| *denominator <= MAX_EXACT_ROOT_DEGREE);
+    let (Some(numerator), Some(denominator)) = (numerator, denominator) else {
+        return approximate();
+    };
+    // Even roots of negative numbers are not real, and odd ones are left to f64 as well,
+    // so `(-8)^(1/3)` is rejected like in the decimal evaluation mode
+    if base.is_negative() && denominator > 1 {
+        return approximate();
+    }
+    let Some(root) = exact_root(base, denominator) else {
+        return approximate();
+    };
+    // The size of the power is estimated first, so huge powers are not multiplied out
+    let bits = (root.numer().bits() + root.denom().bits()).saturating_mul(u64::from(numerator.unsigned_abs()));
+    if bits > MAX_EXACT_POWER_BITS {
+        return approximate();
+    }
+    Ok(RationalValue::Exact(root.pow(numerator)))
+}
+
+/// Raises `base` to `exponent` with `f64`.
+fn approximate_power(base: f64, exponent: f64, span: Span) -> Result<RationalValue, ParseError> {
+    let value = ast::apply_binary(Operator::Power, base, exponent, span)?;
+    // Like with decimals, a power that is not a real number is an error
+    if value.is_nan() {
+        let message = format!("Cannot raise {base} to the power {exponent}");
+        return Err(ParseError::DomainError { message, span });
+    }
+    Ok(RationalValue::Approximate(value))
+}
+
+/// Computes the `degree`-th root of `radicand`, exactly if the result is a fraction.
+fn root(degree: BigRational, radicand: BigRational, span: Span) -> Result<RationalValue, ParseError> {
+    // The f64 root checks the domain, so both representations reject the same operands
+    functions::root(degree.to_f64().unwrap_or(f64::NAN), radicand.to_f64().unwrap_or(f64::NAN))
+        .map_err(|message| ParseError::DomainError { message, span })?;
+    power(&radicand, &degree.recip(), span)
+}
+
+/// Returns the `degree`-th root of a non-negative fraction if it is a fraction itself.
+fn exact_root(value: &BigRational, degree: u32) -> Option<BigRational> {
+    let numerator = value.numer().nth_root(degree);
+    let denominator = value.denom().nth_root(degree);
+    let root = BigRational::new(numerator, denominator);
+    (root.pow(degree as i32) == *value).then_some(root)
+}
+
+/// Computes `x!`, exactly for whole numbers.
+fn factorial(x: BigRational, span: Span) -> Result<RationalValue, ParseError> {
+    let n = x.is_integer().then(|| x.to_integer().to_u64()).flatten();
+    match n {
+        Some(n) if n <= MAX_EXACT_FACTORIAL => {
+            let product = (2..=n).fold(BigInt::from(1), |product, i| product * i);
+            Ok(RationalValue::Exact(BigRational::from_integer(product)))
+        }
+        _ => special::factorial(x.to_f64().unwrap_or(f64::NAN))
+            .map(RationalValue::Approximate)
+            .map_err(|message| ParseError::DomainError { message, span }),
+    }
+}
+
+#[cfg(test)]
+mod rational_tests {
+    use super::{evaluate_rational, RationalValue};
+    use crate::{parse_to_ast, Context, ParseError, Span};
+
+    fn rational(input: &str) -> Result<String, ParseError> {
+        evaluate_rational(input, &Context::new()).map(|value| value.to_string())
+    }
+
+    fn exact(input: &str) -> bool {
+        evaluate_rational(input, &Context::new()).unwrap().is_exact()
+    }
+
+    #[test]
+    fn fractions() {
+        assert_eq!(Ok("1/2".to_string()), rational("1/3 + 1/6"));
+        assert_eq!(Ok("3/10".to_string()), rational("0.1+0.2"));
+        assert_eq!(Ok("-5/6".to_string()), rational("1/6-1"));
+        assert_eq!(Ok("2".to_string()), rational("4/6*3"));
+        assert_eq!(Ok("50".to_string()), rational("1%2"));
+        assert_eq!(Ok("1/3".to_string()), rational("1/3"));
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        assert_eq!(Ok("15".to_string()), rational("0xFF & 0x0F"));
+        assert_eq!(Ok("-256".to_string()), rational("~0xFF"));
+        assert!(matches!(rational("1/2 << 1"), Err(ParseError::DomainError { .. })));
+        assert!(matches!(rational("2^64 | 0"), Err(ParseError::DomainError { .. })));
+    }
+
+    #[test]
+    fn literals_keep_their_digits() {
+        assert_eq!(Ok("1/100000000000000000000".to_string()), rational("1.00000000000000000001-1"));
+        assert_eq!(Ok("12345678901234567890".to_string()), rational("12345678901234567890"));
+        assert_eq!(Ok("3/2000".to_string()), rational("1.5e-3"));
+        assert_eq!(Ok("18446744073709551615".to_string()), rational("0xFFFFFFFFFFFFFFFF"));
+        assert_eq!(Ok("1".to_string()), rational("1e-400*1e400"));
+    }
+
+    #[test]
+    fn powers_and_roots() {
+        assert_eq!(Ok("9/4".to_string()), rational("(2/3)^-2"));
+        assert_eq!(Ok("4".to_string()), rational("8^(2/3)"));
+        assert_eq!(Ok("3/2".to_string()), rational("√(9/4)"));
+        assert_eq!(Ok("1/2".to_string()), rational("3√0.125"));
+        assert_eq!(Ok("1267650600228229401496703205376".to_string()), rational("2^100"));
+        assert!(exact("(1/2)^0.5^0"));
+    }
+
+    #[test]
+    fn huge_powers() {
+        assert!(exact("2^10000"));
+        assert!(exact("(2^1000)^1000"));
+        assert!(!exact("(2^10000)^10000"));
+        let nested = evaluate_rational("((2^10000)^10000)^10000", &Context::new());
+        assert_eq!(Ok(RationalValue::Approximate(f64::INFINITY)), nested);
+        assert_eq!(Ok(RationalValue::Approximate(0.)), evaluate_rational("(2^10000)^-10000", &Context::new()));
+    }
+
+    #[test]
+    fn factorials() {
+        assert_eq!(Ok("30414093201713378043612608166064768844377641568960512000000000000".to_string()), rational("50!"));
+        assert!(!exact("0.5!"));
+    }
+
+    #[test]
+    fn irrational_results() {
+        assert!(!exact("√2"));
+        assert!(!exact("2^0.5"));
+        assert!(!exact("π/2"));
+        assert!(!exact("max(1, 2)"));
+        assert!(!exact("√2*√2"));
+        assert_eq!(RationalValue::Approximate(2f64.sqrt() + 1.), evaluate_rational("√2+1", &Context::new()).unwrap());
+    }
+
+    #[test]
+    fn variables_and_functions() {
+        let mut context = Context::new();
+        context.set_variable("price", 19.99);
+        context.define_function("third", vec!["x".to_string()], parse_to_ast("x/3").unwrap());
+        assert_eq!(Ok("1999/300".to_string()), evaluate_rational("third(price)", &context).map(|value| value.to_string()));
+    }
+
+    // These are invalid operations
+    #[test]
+    fn invalid_operations() {
+        assert_eq!(Err(ParseError::DivisionByZero { span: Span::new(1, 2) }), rational("1/(1/2-0.5)"));
+        assert_eq!(Err(ParseError::DivisionByZero { span: Span::new(1, 2) }), rational("0^-1"));
+        assert!(matches!(rational("√-4"), Err(ParseError::DomainError { .. })));
+        assert!(matches!(rational("(-3)!"), Err(ParseError::DomainError { .. })));
+    }
+
+    #[test]
+    fn powers_without_real_value() {
+        for input in ["(-8)^(1/3)", "(-2)^0.5", "(-1/8)^(2/3)", "(-π)^0.5", "(-2)^(π/4)"] {
+            assert!(matches!(rational(input), Err(ParseError::DomainError { .. })), "{input}");
+            #[cfg(feature = "decimal")]
+            {
+                let decimal = crate::evaluate_decimal(input, &Context::new(), &crate::DecimalOptions::default());
+                assert!(matches!(decimal, Err(ParseError::DomainError { .. })), "{input}");
+            }
+        }
+    }
+}
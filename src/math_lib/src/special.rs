@@ -0,0 +1,219 @@
+//! # Description
+//! This module provides the Gamma function and the special functions built on it.
+
+use std::f64::consts::PI;
+
+/// Largest `n` for which `n!` is finite as an `f64`.
+const MAX_FACTORIAL: f64 = 170.;
+
+/// Largest argument for which the Gamma function is finite as an `f64`.
+const MAX_GAMMA: f64 = 171.624_376_956_302_7;
+
+/// Parameter `g` of the Lanczos approximation.
+const LANCZOS_G: f64 = 7.;
+
+/// Coefficients of the Lanczos approximation for `g = 7`.
+const LANCZOS_COEFFICIENTS: [f64; 9] = [
+    0.999_999_999_999_809_9,
+    676.520_368_121_885_1,
+    -1_259.139_216_722_402_8,
+    771.323_428_777_653_1,
+    -176.615_029_162_140_6,
+    12.507_343_278_686_905,
+    -0.138_571_095_265_720_12,
+    9.984_369_578_019_572e-6,
+    1.505_632_735_149_311_6e-7,
+];
+
+/// Returns `true` if `x` is a whole number that is not positive, where Gamma has its poles.
+fn is_pole(x: f64) -> bool {
+    x <= 0. && x.fract() == 0.
+}
+
+/// Computes `n!` by multiplication, for a whole number `n` between 0 and [`MAX_FACTORIAL`].
+fn integer_factorial(n: f64) -> f64 {
+    (2..=(n as u32)).fold(1., |product, i| product * i as f64)
+}
+
+/// Computes the sum of the Lanczos series for `x ≥ 0.5`, shifted by one.
+fn lanczos_sum(x: f64) -> f64 {
+    LANCZOS_COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(LANCZOS_COEFFICIENTS[0], |sum, (i, coefficient)| sum + coefficient / (x + i as f64 + 1.))
+}
+
+/// Computes `sin(πx)`, exactly zero at whole numbers.
+fn sin_pi(x: f64) -> f64 {
+    if x.fract() == 0. {
+        return 0.;
+    }
+    (PI * x.rem_euclid(2.)).sin()
+}
+
+/// Computes the Gamma function, which extends the factorial to real numbers with
+/// `Γ(n) = (n - 1)!`.
+///
+/// # Arguments
+/// * `x` - Argument, anything but zero or a negative whole number
+///
+/// # Returns
+/// * `Ok(f64)` - Value of the function, infinite once it exceeds the range of `f64`
+/// * `Err(String)` - Message if `x` is a pole of the function
+pub(crate) fn gamma(x: f64) -> Result<f64, String> {
+    if is_pole(x) {
+        return Err("Cannot take gamma of zero or a negative whole number".to_string());
+    }
+    if x.fract() == 0. && x - 1. <= MAX_FACTORIAL {
+        return Ok(integer_factorial(x - 1.));
+    }
+    if x > MAX_GAMMA {
+        return Ok(f64::INFINITY);
+    }
+    if x < 0.5 {
+        // Reflection formula Γ(x)Γ(1 - x) = π / sin(πx)
+        return Ok(PI / (sin_pi(x) * gamma(1. - x)?));
+    }
+    let x = x - 1.;
+    let t = x + LANCZOS_G + 0.5;
+    // t^(x + 0.5) is split in two, as it overflows on its own close to MAX_GAMMA
+    let power = t.powf((x + 0.5) / 2.);
+    Ok((2. * PI).sqrt() * power * (power * (-t).exp()) * lanczos_sum(x))
+}
+
+/// Computes the natural logarithm of the absolute value of the Gamma function, which stays
+/// finite long after the Gamma function itself overflows.
+///
+/// # Arguments
+/// * `x` - Argument, anything but zero or a negative whole number
+///
+/// # Returns
+/// * `Ok(f64)` - Value of `ln|Γ(x)|`
+/// * `Err(String)` - Message if `x` is a pole of the Gamma function
+pub(crate) fn lgamma(x: f64) -> Result<f64, String> {
+    if is_pole(x) {
+        return Err("Cannot take lgamma of zero or a negative whole number".to_string());
+    }
+    if x.abs() <= MAX_FACTORIAL {
+        return Ok(gamma(x)?.abs().ln());
+    }
+    if x < 0.5 {
+        return Ok((PI / sin_pi(x).abs()).ln() - lgamma(1. - x)?);
+    }
+    let x = x - 1.;
+    let t = x + LANCZOS_G + 0.5;
+    Ok(0.5 * (2. * PI).ln() + (x + 0.5) * t.ln() - t + lanczos_sum(x).ln())
+}
+
+/// Returns the sign of the Gamma function at `x`, which must not be a pole.
+fn gamma_sign(x: f64) -> f64 {
+    if x > 0. || x.floor().rem_euclid(2.) == 0. {
+        1.
+    } else {
+        -1.
+    }
+}
+
+/// Computes the Beta function `B(a, b) = Γ(a)Γ(b) / Γ(a + b)`.
+///
+/// # Arguments
+/// * `a`, `b` - Arguments, neither zero nor a negative whole number
+///
+/// # Returns
+/// * `Ok(f64)` - Value of the function, zero if `a + b` is a pole of the Gamma function
+/// * `Err(String)` - Message if `a` or `b` is a pole of the Gamma function
+pub(crate) fn beta(a: f64, b: f64) -> Result<f64, String> {
+    if is_pole(a) || is_pole(b) {
+        return Err("Cannot take beta of zero or a negative whole number".to_string());
+    }
+    if is_pole(a + b) {
+        return Ok(0.);
+    }
+    if [a, b, a + b].iter().all(|x| x.abs() <= MAX_FACTORIAL) {
+        return Ok(gamma(a)? * gamma(b)? / gamma(a + b)?);
+    }
+    let sign = gamma_sign(a) * gamma_sign(b) * gamma_sign(a + b);
+    Ok(sign * (lgamma(a)? + lgamma(b)? - lgamma(a + b)?).exp())
+}
+
+/// Computes the factorial `x! = Γ(x + 1)` for any real `x` but the negative whole numbers.
+///
+/// Whole numbers are multiplied out exactly, and results beyond the range of `f64` are
+/// infinite without being computed.
+///
+/// # Arguments
+/// * `x` - Argument, anything but a negative whole number
+///
+/// # Returns
+/// * `Ok(f64)` - Value of `x!`
+/// * `Err(String)` - Message if `x` is a negative whole number
+pub(crate) fn factorial(x: f64) -> Result<f64, String> {
+    if x < 0. && x.fract() == 0. {
+        return Err("Cannot take factorial of a negative whole number".to_string());
+    }
+    gamma(x + 1.)
+}
+
+#[cfg(test)]
+mod special_tests {
+    use std::f64::consts::PI;
+
+    use super::{beta, factorial, gamma, lgamma};
+
+    fn assert_close(expected: f64, actual: Result<f64, String>) {
+        let actual = actual.unwrap();
+        assert!(((expected - actual) / expected).abs() < 1e-12, "expected {expected}, found {actual}");
+    }
+
+    #[test]
+    fn whole_factorials() {
+        assert_eq!(Ok(1.), factorial(0.));
+        assert_eq!(Ok(120.), factorial(5.));
+        assert_eq!(Ok(2432902008176640000.), factorial(20.));
+        assert!(factorial(170.).unwrap().is_finite());
+    }
+
+    #[test]
+    fn real_factorials() {
+        assert_close(PI.sqrt(), factorial(-0.5));
+        assert_close(3.323350970447842, factorial(2.5));
+        assert_close(9.483367566824801e307, factorial(170.5));
+    }
+
+    #[test]
+    fn overflow() {
+        assert_eq!(Ok(f64::INFINITY), factorial(171.));
+        assert_eq!(Ok(f64::INFINITY), factorial(1e15));
+        assert_eq!(Ok(f64::INFINITY), gamma(f64::MAX));
+    }
+
+    #[test]
+    fn poles() {
+        assert_eq!(Err("Cannot take factorial of a negative whole number".to_string()), factorial(-3.));
+        assert_eq!(Err("Cannot take gamma of zero or a negative whole number".to_string()), gamma(0.));
+        assert!(lgamma(-2.).is_err());
+        assert!(beta(-1., 0.5).is_err());
+    }
+
+    #[test]
+    fn negative_gamma() {
+        assert_close(-2. * PI.sqrt(), gamma(-0.5));
+        assert_close(4. * PI.sqrt() / 3., gamma(-1.5));
+    }
+
+    #[test]
+    fn log_gamma() {
+        assert_eq!(Ok(0.), lgamma(1.));
+        assert_close(5905.220423209181, lgamma(1000.));
+        assert_close((2. * PI.sqrt()).ln(), lgamma(-0.5));
+    }
+
+    #[test]
+    fn beta_function() {
+        assert_close(1. / 12., beta(2., 3.));
+        assert_close(PI, beta(0.5, 0.5));
+        assert_eq!(Ok(0.), beta(-0.5, -0.5));
+        assert_close(beta(300.5, 2.).unwrap(), beta(2., 300.5));
+        assert_close(1. / (300.5 * 301.5), beta(300.5, 2.));
+    }
+}
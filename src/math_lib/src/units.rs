@@ -0,0 +1,576 @@
+//! # Description
+//! This module provides the evaluation of expressions with physical units.
+//! It is only compiled with the `units` feature.
+
+use std::fmt;
+
+use crate::ast::{apply_binary as apply_real_binary, apply_unary as apply_real_unary};
+use crate::ast::{call_native, called_function, evaluate_chain, Expr};
+use crate::constants::NamedConstant;
+use crate::context::Context;
+use crate::error::{ParseError, Span};
+use crate::functions::Implementation;
+use crate::integer::is_bitwise;
+#[cfg(doc)]
+use crate::options::ImplicitMultiplication;
+use crate::parser::{parse_to_ast_with, Operator};
+
+/// Symbols of the SI base units, in the order their exponents are stored in a [`Dimension`].
+const BASE_UNITS: [&str; 7] = ["kg", "m", "s", "A", "K", "mol", "cd"];
+
+/// Physical dimension of a quantity, the exponents of the seven SI base units.
+///
+/// A dimension is written as a product of base units, like `kg*m/s^2`, and a dimensionless
+/// quantity as `1`.
+///
+/// # Examples
+/// ```
+/// use math_lib::{evaluate_quantity, Context};
+///
+/// let force = evaluate_quantity("9.81 m/s^2 * 70 kg", &Context::new()).unwrap();
+/// assert_eq!(force.dimension().to_string(), "kg*m/s^2");
+/// assert!(evaluate_quantity("(2 m)/(4 m)", &Context::new()).unwrap().dimension().is_dimensionless());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Dimension([i32; 7]);
+
+impl Dimension {
+    /// The dimension of plain numbers.
+    pub const NONE: Dimension = Dimension([0; 7]);
+
+    const MASS: Dimension = Dimension([1, 0, 0, 0, 0, 0, 0]);
+    const LENGTH: Dimension = Dimension([0, 1, 0, 0, 0, 0, 0]);
+    const TIME: Dimension = Dimension([0, 0, 1, 0, 0, 0, 0]);
+    const CURRENT: Dimension = Dimension([0, 0, 0, 1, 0, 0, 0]);
+    const TEMPERATURE: Dimension = Dimension([0, 0, 0, 0, 1, 0, 0]);
+    const AMOUNT: Dimension = Dimension([0, 0, 0, 0, 0, 1, 0]);
+    const LUMINOUS_INTENSITY: Dimension = Dimension([0, 0, 0, 0, 0, 0, 1]);
+    const VOLUME: Dimension = Dimension([0, 3, 0, 0, 0, 0, 0]);
+    const VELOCITY: Dimension = Dimension([0, 1, -1, 0, 0, 0, 0]);
+    const FREQUENCY: Dimension = Dimension([0, 0, -1, 0, 0, 0, 0]);
+    const FORCE: Dimension = Dimension([1, 1, -2, 0, 0, 0, 0]);
+    const PRESSURE: Dimension = Dimension([1, -1, -2, 0, 0, 0, 0]);
+    const ENERGY: Dimension = Dimension([1, 2, -2, 0, 0, 0, 0]);
+    const POWER: Dimension = Dimension([1, 2, -3, 0, 0, 0, 0]);
+    const CHARGE: Dimension = Dimension([0, 0, 1, 1, 0, 0, 0]);
+    const VOLTAGE: Dimension = Dimension([1, 2, -3, -1, 0, 0, 0]);
+    const RESISTANCE: Dimension = Dimension([1, 2, -3, -2, 0, 0, 0]);
+    const CAPACITANCE: Dimension = Dimension([-1, -2, 4, 2, 0, 0, 0]);
+    const INDUCTANCE: Dimension = Dimension([1, 2, -2, -2, 0, 0, 0]);
+
+    /// Returns the exponents of kg, m, s, A, K, mol and cd, in this order.
+    pub fn exponents(&self) -> [i32; 7] {
+        self.0
+    }
+
+    /// Returns whether all exponents are zero.
+    pub fn is_dimensionless(&self) -> bool {
+        *self == Dimension::NONE
+    }
+
+    fn combine(self, other: Dimension, combine: fn(i32, i32) -> i32) -> Dimension {
+        let mut exponents = self.0;
+        for (exponent, other) in exponents.iter_mut().zip(other.0) {
+            *exponent = combine(*exponent, other);
+        }
+        Dimension(exponents)
+    }
+
+    /// Scales the exponents by `factor`, or returns `None` if one would not be whole.
+    fn scale(self, factor: f64) -> Option<Dimension> {
+        let mut exponents = self.0;
+        for exponent in &mut exponents {
+            let scaled = f64::from(*exponent) * factor;
+            if scaled.fract() != 0. || scaled.abs() > f64::from(i32::MAX) {
+                return None;
+            }
+            *exponent = scaled as i32;
+        }
+        Some(Dimension(exponents))
+    }
+}
+
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let factors = |sign: i32| {
+            BASE_UNITS
+                .iter()
+                .zip(self.0)
+                .filter(|(_, exponent)| exponent.signum() == sign)
+                .map(|(unit, exponent)| match exponent.abs() {
+                    1 => unit.to_string(),
+                    exponent => format!("{unit}^{exponent}"),
+                })
+                .collect::<Vec<_>>()
+        };
+        let (numerator, denominator) = (factors(1), factors(-1));
+        let numerator = if numerator.is_empty() { "1".to_string() } else { numerator.join("*") };
+        match denominator.len() {
+            0 => write!(f, "{numerator}"),
+            1 => write!(f, "{numerator}/{}", denominator[0]),
+            _ => write!(f, "{numerator}/({})", denominator.join("*")),
+        }
+    }
+}
+
+/// Value of an expression with units, see [`Expr::evaluate_quantity`].
+///
+/// The value is kept in SI base units and written in them, unless the quantity is the
+/// result of a conversion with `to`.
+///
+/// # Examples
+/// ```
+/// use math_lib::{evaluate_quantity, Context};
+///
+/// let speed = evaluate_quantity("36 km/h", &Context::new()).unwrap();
+/// assert_eq!(speed.to_string(), "10 m/s");
+/// let speed = evaluate_quantity("10 m/s to km/h", &Context::new()).unwrap();
+/// assert_eq!((speed.value(), speed.unit()), (36., "km/h".to_string()));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantity {
+    // Value in SI base units
+    value: f64,
+    dimension: Dimension,
+    // Unit the quantity was converted to with its size in SI base units
+    unit: Option<(String, f64)>,
+}
+
+impl Quantity {
+    /// Creates a quantity from its value in SI base units.
+    pub fn new(value: f64, dimension: Dimension) -> Quantity {
+        Quantity { value, dimension, unit: None }
+    }
+
+    /// Returns the value in the unit of the quantity, see [`Quantity::unit`].
+    pub fn value(&self) -> f64 {
+        match &self.unit {
+            Some((_, factor)) => self.value / factor,
+            None => self.value,
+        }
+    }
+
+    /// Returns the value in SI base units.
+    pub fn si_value(&self) -> f64 {
+        self.value
+    }
+
+    /// Returns the dimension of the quantity.
+    pub fn dimension(&self) -> Dimension {
+        self.dimension
+    }
+
+    /// Returns the unit the value is written in, which is empty for dimensionless quantities.
+    pub fn unit(&self) -> String {
+        match &self.unit {
+            Some((name, _)) => name.clone(),
+            None if self.dimension.is_dimensionless() => String::new(),
+            None => self.dimension.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.unit() {
+            unit if unit.is_empty() => write!(f, "{}", self.value()),
+            unit => write!(f, "{} {unit}", self.value()),
+        }
+    }
+}
+
+/// Units that can be written in expressions: symbol, size in SI base units, dimension and
+/// whether SI prefixes like `k` or `m` may be put in front of the symbol.
+const UNITS: &[(&str, f64, Dimension, bool)] = &[
+    ("m", 1., Dimension::LENGTH, true),
+    ("g", 1e-3, Dimension::MASS, true),
+    ("s", 1., Dimension::TIME, true),
+    ("A", 1., Dimension::CURRENT, true),
+    ("K", 1., Dimension::TEMPERATURE, true),
+    ("mol", 1., Dimension::AMOUNT, true),
+    ("cd", 1., Dimension::LUMINOUS_INTENSITY, true),
+    ("N", 1., Dimension::FORCE, true),
+    ("Pa", 1., Dimension::PRESSURE, true),
+    ("J", 1., Dimension::ENERGY, true),
+    ("W", 1., Dimension::POWER, true),
+    ("Hz", 1., Dimension::FREQUENCY, true),
+    ("C", 1., Dimension::CHARGE, true),
+    ("V", 1., Dimension::VOLTAGE, true),
+    ("Ω", 1., Dimension::RESISTANCE, true),
+    ("ohm", 1., Dimension::RESISTANCE, true),
+    ("F", 1., Dimension::CAPACITANCE, true),
+    ("H", 1., Dimension::INDUCTANCE, true),
+    ("L", 1e-3, Dimension::VOLUME, true),
+    ("l", 1e-3, Dimension::VOLUME, true),
+    ("Wh", 3600., Dimension::ENERGY, true),
+    ("eV", 1.602176634e-19, Dimension::ENERGY, true),
+    ("cal", 4.184, Dimension::ENERGY, true),
+    ("bar", 1e5, Dimension::PRESSURE, true),
+    ("min", 60., Dimension::TIME, false),
+    ("h", 3600., Dimension::TIME, false),
+    ("d", 86400., Dimension::TIME, false),
+    ("t", 1000., Dimension::MASS, false),
+    ("in", 0.0254, Dimension::LENGTH, false),
+    ("ft", 0.3048, Dimension::LENGTH, false),
+    ("yd", 0.9144, Dimension::LENGTH, false),
+    ("mi", 1609.344, Dimension::LENGTH, false),
+    ("lb", 0.45359237, Dimension::MASS, false),
+    ("oz", 0.028349523125, Dimension::MASS, false),
+    ("mph", 0.44704, Dimension::VELOCITY, false),
+    ("atm", 101325., Dimension::PRESSURE, false),
+];
+
+/// SI prefixes with the factor they multiply a unit by.
+const PREFIXES: &[(&str, f64)] = &[
+    ("da", 1e1),
+    ("Y", 1e24),
+    ("Z", 1e21),
+    ("E", 1e18),
+    ("P", 1e15),
+    ("T", 1e12),
+    ("G", 1e9),
+    ("M", 1e6),
+    ("k", 1e3),
+    ("h", 1e2),
+    ("d", 1e-1),
+    ("c", 1e-2),
+    ("m", 1e-3),
+    ("µ", 1e-6),
+    ("μ", 1e-6),
+    ("u", 1e-6),
+    ("n", 1e-9),
+    ("p", 1e-12),
+    ("f", 1e-15),
+    ("a", 1e-18),
+    ("z", 1e-21),
+    ("y", 1e-24),
+];
+
+/// Returns one of the named unit as a quantity, or `None` if there is no such unit.
+///
+/// A symbol of the table is preferred over a prefixed unit, so `min` is a minute and `cd`
+/// a candela.
+fn unit(name: &str) -> Option<Quantity> {
+    if let Some((_, factor, dimension, _)) = UNITS.iter().find(|(symbol, ..)| *symbol == name) {
+        return Some(Quantity::new(*factor, *dimension));
+    }
+    PREFIXES.iter().find_map(|(prefix, prefix_factor)| {
+        let symbol = name.strip_prefix(prefix)?;
+        let (_, factor, dimension, _) = UNITS.iter().find(|(unit, .., prefixable)| *unit == symbol && *prefixable)?;
+        Some(Quantity::new(prefix_factor * factor, *dimension))
+    })
+}
+
+/// Parses and evaluates `input` with physical units.
+///
+/// The input is parsed with the parser options of `context`, see
+/// [`Expr::evaluate_quantity`] for how it is evaluated.
+///
+/// # Arguments
+///
+/// * `input` - A string slice containing the expression to evaluate
+/// * `context` - The context providing variables, functions and parser options
+///
+/// # Returns
+/// A Result containing the value of the expression or a ParseError if the expression
+/// cannot be parsed or evaluated.
+///
+/// # Examples
+/// ```
+/// use math_lib::{evaluate_quantity, Context, ParseError};
+///
+/// assert_eq!(evaluate_quantity("3 m + 20 cm", &Context::new()).unwrap().to_string(), "3.2 m");
+/// assert_eq!(evaluate_quantity("1 h to min", &Context::new()).unwrap().to_string(), "60 min");
+/// assert!(matches!(evaluate_quantity("3 m + 2 s", &Context::new()), Err(ParseError::IncompatibleUnits { .. })));
+/// ```
+pub fn evaluate_quantity(input: &str, context: &Context) -> Result<Quantity, ParseError> {
+    parse_to_ast_with(input, context.parser_options())?.evaluate_quantity(context)
+}
+
+impl Expr {
+    /// Evaluates the expression with physical units, looking variables up in `context`.
+    ///
+    /// Names that are neither parameters, variables of `context` nor constants of the
+    /// [`NamedConstant`] catalogue, if `context` enables it, are read as units, like `m`,
+    /// `km`, `h` or `N`, so `3 m` is three meters. Catalogue constants carry their units.
+    /// Sums and differences need
+    /// operands of the same dimension, products and quotients combine their dimensions, and
+    /// exponents, factorials and the arguments of functions must be dimensionless.
+    ///
+    /// The operator `to` converts its left operand into the unit on its right, which must
+    /// have the same dimension. Other results are written in SI base units.
+    ///
+    /// A unit is multiplied implicitly like a variable, so `4 m / 2 m` is `(4*m/2)*m` unless
+    /// the parser options of `context` use [`ImplicitMultiplication::Tight`].
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - The context providing the values of variables and functions
+    ///
+    /// # Returns
+    /// A Result containing the value of the expression or a ParseError if an error occurs
+    /// during evaluation.
+    pub fn evaluate_quantity(&self, context: &Context) -> Result<Quantity, ParseError> {
+        QuantityScope { context, locals: &[], depth: 0 }.evaluate(self)
+    }
+}
+
+/// Variables visible while evaluating an expression with physical units.
+struct QuantityScope<'a> {
+    context: &'a Context,
+    // Parameters of the function being called, empty outside of calls
+    locals: &'a [(&'a str, Quantity)],
+    // Number of function calls being evaluated
+    depth: usize,
+}
+
+impl QuantityScope<'_> {
+    fn evaluate(&self, expr: &Expr) -> Result<Quantity, ParseError> {
+        match expr {
+            Expr::Number { value, .. } => Ok(Quantity::new(*value, Dimension::NONE)),
+            Expr::Variable { name, span } => self.variable(name, *span),
+            Expr::Constant { constant, .. } => Ok(Quantity::new(constant.value(), Dimension::NONE)),
+            Expr::Unary { op, operand, span } => apply_unary(*op, self.evaluate(operand)?, *span),
+            Expr::Binary { .. } => evaluate_chain(
+                expr,
+                |operand| self.evaluate(operand),
+                |left, op, right, add_on, span| {
+                    if op == Operator::Convert {
+                        return convert(left, right.to_string(), self.evaluate(right)?, span);
+                    }
+                    let mut right = self.evaluate(right)?;
+                    if add_on {
+                        right = apply_binary(Operator::Multiply, left.clone(), right, span)?;
+                    }
+                    apply_binary(op, left, right, span)
+                },
+            ),
+            Expr::Call { name, args, span } => self.call(name, args, *span),
+        }
+    }
+
+    /// Looks a variable up in the parameters, the context, the catalogue of constants and
+    /// finally the units.
+    fn variable(&self, name: &str, span: Span) -> Result<Quantity, ParseError> {
+        if let Some((_, value)) = self.locals.iter().find(|(local, _)| *local == name) {
+            return Ok(value.clone());
+        }
+        self.context
+            .variable(name)
+            .map(|value| Quantity::new(value, Dimension::NONE))
+            .or_else(|| self.context.named_constant(name).map(NamedConstant::quantity))
+            .or_else(|| unit(name))
+            .ok_or_else(|| ParseError::UnknownVariable { name: name.to_string(), span })
+    }
+
+    fn call(&self, name: &str, args: &[Expr], span: Span) -> Result<Quantity, ParseError> {
+        let function = called_function(self.context, name, args.len(), span)?;
+        match function.implementation() {
+            Implementation::Native(native) => {
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    let value = self.evaluate(arg)?;
+                    if !value.dimension.is_dimensionless() {
+                        return Err(ParseError::DomainError {
+                            message: format!("Function {name} is not defined for quantities with units"),
+                            span,
+                        });
+                    }
+                    values.push(value.value);
+                }
+                call_native(function, native.as_ref(), values, self.context, span)
+                    .map(|value| Quantity::new(value, Dimension::NONE))
+            }
+            Implementation::User(function) => {
+                if self.depth >= self.context.max_call_depth() {
+                    return Err(ParseError::RecursionLimit { name: name.to_string(), span });
+                }
+                let mut locals = Vec::with_capacity(args.len());
+                for (param, arg) in function.params.iter().zip(args) {
+                    locals.push((param.as_str(), self.evaluate(arg)?));
+                }
+                // The body only sees its own parameters, not those of the caller
+                let scope = QuantityScope { context: self.context, locals: &locals, depth: self.depth + 1 };
+                scope.evaluate(&function.body)
+            }
+        }
+    }
+}
+
+/// Writes `value` in `unit`, the quantity the text `name` evaluated to.
+fn convert(value: Quantity, name: String, unit: Quantity, span: Span) -> Result<Quantity, ParseError> {
+    if value.dimension != unit.dimension {
+        return Err(incompatible(&value, &unit, span));
+    }
+    if unit.value == 0. {
+        return Err(ParseError::DivisionByZero { span });
+    }
+    Ok(Quantity { unit: Some((name, unit.value)), ..value })
+}
+
+fn incompatible(left: &Quantity, right: &Quantity, span: Span) -> ParseError {
+    ParseError::IncompatibleUnits { left: left.dimension.to_string(), right: right.dimension.to_string(), span }
+}
+
+/// Returns an error unless `value` is dimensionless, `what` describes the value.
+fn dimensionless(value: &Quantity, what: &str, span: Span) -> Result<(), ParseError> {
+    if value.dimension.is_dimensionless() {
+        return Ok(());
+    }
+    Err(ParseError::DomainError { message: format!("{what} cannot have a unit, found {}", value.dimension), span })
+}
+
+/// Takes the `degree`th root of a dimension, which must give whole exponents.
+fn root_dimension(dimension: Dimension, degree: f64, span: Span) -> Result<Dimension, ParseError> {
+    dimension.scale(1. / degree).ok_or_else(|| ParseError::DomainError {
+        message: format!("Cannot take the {degree}th root of {dimension}"),
+        span,
+    })
+}
+
+/// Applies a unary operator to an already evaluated operand.
+fn apply_unary(op: Operator, operand: Quantity, span: Span) -> Result<Quantity, ParseError> {
+    let dimension = match op {
+        Operator::Root => root_dimension(operand.dimension, 2., span)?,
+        Operator::Factorial => {
+            dimensionless(&operand, "Factorial operand", span)?;
+            Dimension::NONE
+        }
+        Operator::BitNot => {
+            dimensionless(&operand, "Bitwise operand", span)?;
+            Dimension::NONE
+        }
+        _ => operand.dimension,
+    };
+    Ok(Quantity::new(apply_real_unary(op, operand.value, span)?, dimension))
+}
+
+/// Applies a binary operator to already evaluated operands.
+fn apply_binary(op: Operator, left: Quantity, right: Quantity, span: Span) -> Result<Quantity, ParseError> {
+    let dimension = match op {
+        Operator::Plus | Operator::Minus | Operator::Modulo if left.dimension != right.dimension => {
+            return Err(incompatible(&left, &right, span));
+        }
+        Operator::Multiply => left.dimension.combine(right.dimension, |left, right| left + right),
+        Operator::Divide | Operator::Percent => left.dimension.combine(right.dimension, |left, right| left - right),
+        Operator::Power => {
+            dimensionless(&right, "Exponent", span)?;
+            left.dimension.scale(right.value).ok_or_else(|| ParseError::DomainError {
+                message: format!("Cannot raise {} to the power {}", left.dimension, right.value),
+                span,
+            })?
+        }
+        Operator::Root => {
+            dimensionless(&left, "Root degree", span)?;
+            root_dimension(right.dimension, left.value, span)?
+        }
+        op if is_bitwise(op) => {
+            dimensionless(&left, "Bitwise operand", span)?;
+            dimensionless(&right, "Bitwise operand", span)?;
+            Dimension::NONE
+        }
+        _ => left.dimension,
+    };
+    Ok(Quantity::new(apply_real_binary(op, left.value, right.value, span)?, dimension))
+}
+
+#[cfg(test)]
+mod units_tests {
+    use super::{evaluate_quantity, Dimension, Quantity};
+    use crate::{parse_to_ast, Context, ImplicitMultiplication, ParseError, ParserOptions, Span};
+
+    fn quantity(input: &str) -> Result<Quantity, ParseError> {
+        evaluate_quantity(input, &Context::new())
+    }
+
+    fn written(input: &str) -> String {
+        quantity(input).unwrap().to_string()
+    }
+
+    #[test]
+    fn units_and_prefixes() {
+        assert_eq!("3.2 m", written("3 m + 20 cm"));
+        assert_eq!("1 kg", written("1000 g"));
+        assert_eq!("60 s", written("1 min"));
+        assert_eq!("0.001 m^3", written("1 L"));
+        assert_eq!("1 cd", written("1 cd"));
+        assert_eq!("5000 kg*m^2/s^2", written("5 kJ"));
+        assert_eq!("100000 kg/(m*s^2)", written("1 bar"));
+        assert_eq!("0.000002 s", written("2 µs"));
+        assert_eq!("10 m", written("1 dam"));
+    }
+
+    #[test]
+    fn dimensions_combine() {
+        let force = quantity("9.81 m/s^2 * 70 kg").unwrap();
+        assert!((force.si_value() - 686.7).abs() < 1e-9);
+        assert_eq!([1, 1, -2, 0, 0, 0, 0], force.dimension().exponents());
+        assert_eq!("9 m^2", written("(3 m)^2"));
+        assert_eq!("3 m", written("√(9 m^2)"));
+        assert_eq!("2 m", written("3√(8 m^3)"));
+        assert_eq!("2", written("(4 m)/(2 m)"));
+        assert_eq!("2 m^2", written("4 m / 2 m"));
+        assert_eq!("0.5 1/s", written("1/(2 s)"));
+        assert_eq!("1/(m*s)", Dimension([0, -1, -1, 0, 0, 0, 0]).to_string());
+    }
+
+    #[test]
+    fn conversions() {
+        let speed = quantity("5 km/h to m/s").unwrap();
+        assert!((speed.value() - 25. / 18.).abs() < 1e-12);
+        assert_eq!("m/s", speed.unit());
+        assert_eq!("36 km/h", written("10 m/s to km/h"));
+        assert_eq!("2.5 h", written("150 min to h"));
+        assert_eq!("2000 m", written("2 km to m"));
+        assert_eq!("1 kWh", written("3.6 MJ to kWh"));
+        // Arithmetic with a converted quantity is back in SI base units
+        assert_eq!("120 s", written("(1 min to s) * 2"));
+    }
+
+    #[test]
+    fn variables_and_functions() {
+        let mut context = Context::new();
+        context.set_variable("m", 4.);
+        assert_eq!("8", evaluate_quantity("2 m", &context).unwrap().to_string());
+        context.define_function("area", vec!["w".to_string(), "l".to_string()], parse_to_ast("w*l").unwrap());
+        assert_eq!("6 m^2", evaluate_quantity("area(2 cm * 100, 3 km / 1000)", &context).unwrap().to_string());
+        assert_eq!("1", written("sin(π/2)"));
+    }
+
+    #[test]
+    fn tight_implicit_multiplication() {
+        let mut context = Context::new();
+        let tight = ParserOptions { implicit_multiplication: ImplicitMultiplication::Tight, ..ParserOptions::default() };
+        context.set_parser_options(tight);
+        assert_eq!("2", evaluate_quantity("4 m / 2 m", &context).unwrap().to_string());
+        assert_eq!("2 m/s", evaluate_quantity("6 m / 3 s", &context).unwrap().to_string());
+    }
+
+    #[test]
+    fn word_operator() {
+        let mut context = Context::new();
+        context.set_variable("total", 2.);
+        assert_eq!("2", evaluate_quantity("total", &context).unwrap().to_string());
+        assert_eq!("200 cm", evaluate_quantity("total m to cm", &context).unwrap().to_string());
+    }
+
+    // These are invalid operations
+    #[test]
+    fn incompatible_units() {
+        assert_eq!(
+            Err(ParseError::IncompatibleUnits { left: "m".to_string(), right: "s".to_string(), span: Span::new(4, 5) }),
+            quantity("3 m + 2 s")
+        );
+        assert_eq!(
+            Err(ParseError::IncompatibleUnits { left: "m".to_string(), right: "1".to_string(), span: Span::new(4, 5) }),
+            quantity("3 m - 2")
+        );
+        assert!(matches!(quantity("5 km/h to m"), Err(ParseError::IncompatibleUnits { .. })));
+        assert!(matches!(quantity("2^(3 m)"), Err(ParseError::DomainError { .. })));
+        assert!(matches!(quantity("√(2 m)"), Err(ParseError::DomainError { .. })));
+        assert!(matches!(quantity("(3 s)!"), Err(ParseError::DomainError { .. })));
+        assert!(matches!(quantity("sin(1 m)"), Err(ParseError::DomainError { .. })));
+        assert!(matches!(quantity("1 m / (0 s)"), Err(ParseError::DivisionByZero { .. })));
+        assert!(matches!(quantity("3 furlong"), Err(ParseError::UnknownVariable { .. })));
+    }
+}